@@ -0,0 +1,264 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of generating the ABI of a contract from its source code. The crate is parsed by `syn`,
+//! starting from the library root and following the `mod` declarations. Methods marked with `#[call]` in
+//! the `impl` block marked with `#[contract_methods]` are listed with their arguments and return types, and
+//! the fields of the struct marked with `#[contract]` are listed as the storage layout.
+//!
+//! The ABI is saved as `<contract>.abi.json` next to the compiled contract, and can be embedded into the
+//! contract as a WebAssembly custom section named `pchain.abi`.
+
+use std::path::{Path, PathBuf};
+
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use syn::{FnArg, ImplItem, Item, Pat, ReturnType};
+
+use crate::error::Error;
+
+/// Name of the custom section that stores the ABI.
+pub const ABI_SECTION: &str = "pchain.abi";
+
+/// Interface of a contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractAbi {
+    /// Name of the type that implements the contract methods.
+    pub contract: String,
+    /// Contract methods in the order they are defined.
+    pub methods: Vec<MethodAbi>,
+    /// Fields of the contract struct in the order they are defined. The storage key of a field is
+    /// derived from its position, so the order matters.
+    #[serde(default)]
+    pub storage: Vec<StorageFieldAbi>,
+}
+
+/// A contract method, i.e. a method marked with `#[call]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MethodAbi {
+    pub name: String,
+    /// Arguments excluding the receiver.
+    pub args: Vec<ArgumentAbi>,
+    /// Return type as written in the source code. None if the method returns nothing.
+    pub return_type: Option<String>,
+    /// Whether the method only reads the contract storage, i.e. its receiver is `&self`.
+    pub is_view: bool,
+}
+
+/// An argument of a contract method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArgumentAbi {
+    pub name: String,
+    /// Type as written in the source code, e.g. `Vec<u8>`.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// A field of the contract struct.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageFieldAbi {
+    /// Name of the field, or its index for tuple structs.
+    pub name: String,
+    /// Type as written in the source code.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// `generate_abi` parses the contract crate at `source_path` and returns its ABI.
+pub fn generate_abi(source_path: &Path) -> Result<ContractAbi, Error> {
+    let lib_path = source_path.join(crate::manifests::lib_path(source_path)?);
+    let mut abi = ContractAbi {
+        contract: String::new(),
+        methods: vec![],
+        storage: vec![],
+    };
+    let lib_dir = lib_path.parent().unwrap().to_path_buf();
+    collect_from_file(&lib_path, &lib_dir, &mut abi)?;
+    Ok(abi)
+}
+
+/// Returns the file name of the ABI of a contract, e.g. `contract.abi.json` for `contract.wasm`.
+pub fn abi_file(wasm_file: &str) -> String {
+    format!("{}.abi.json", wasm_file.trim_end_matches(".wasm"))
+}
+
+/// Returns the ABI embedded in the WebAssembly binary, or None if it does not exist.
+pub fn read_abi(wasm: &[u8]) -> Result<Option<ContractAbi>, Error> {
+    for section in crate::wasm::sections(wasm)? {
+        if section.id == 0 && section.name == ABI_SECTION {
+            let payload = crate::wasm::custom_section_content(&section)?;
+            return serde_json::from_slice(payload)
+                .map(Some)
+                .map_err(|e| Error::InvalidWasmFile(format!("Malformed ABI: {e}")));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the WebAssembly binary without the ABI section.
+pub fn strip_abi(wasm: &[u8]) -> Result<Vec<u8>, Error> {
+    crate::wasm::remove_custom_section(wasm, ABI_SECTION)
+}
+
+/// Generates the ABI of the contract, saves it next to the compiled contract, and embeds it into the contract if `embed` is set.
+pub(crate) fn save_abi(source_path: &Path, output_path: &Path, wasm_file: &str, embed: bool) -> Result<(), Error> {
+    let abi = generate_abi(source_path)?;
+    let map_err = |e: String| Error::AbiGenerationFailure(format!("Fail to save ABI: {e}"));
+
+    let content = serde_json::to_vec_pretty(&abi).map_err(|e| map_err(e.to_string()))?;
+    std::fs::write(output_path.join(abi_file(wasm_file)), content).map_err(|e| map_err(e.to_string()))?;
+
+    if embed {
+        let wasm_path = output_path.join(wasm_file);
+        let mut wasm = std::fs::read(&wasm_path).map_err(|e| map_err(e.to_string()))?;
+        let payload = serde_json::to_vec(&abi).map_err(|e| map_err(e.to_string()))?;
+        crate::wasm::append_custom_section(&mut wasm, ABI_SECTION, &payload);
+        std::fs::write(wasm_path, wasm).map_err(|e| map_err(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Parses the source file and collects the contract methods in it. `module_dir` is the folder where the files
+/// of its child modules are located.
+fn collect_from_file(file_path: &Path, module_dir: &Path, abi: &mut ContractAbi) -> Result<(), Error> {
+    let content = std::fs::read_to_string(file_path)
+        .map_err(|e| Error::AbiGenerationFailure(format!("Fail to read {}: {e}", file_path.display())))?;
+    let file = syn::parse_file(&content)
+        .map_err(|e| Error::AbiGenerationFailure(format!("Fail to parse {}: {e}", file_path.display())))?;
+    let file_dir = file_path.parent().unwrap();
+    collect_from_items(&file.items, file_dir, module_dir, abi)
+}
+
+/// Collects the contract methods in the items, following the `mod` declarations. `file_dir` is the folder of the
+/// source file that contains the items, which `#[path]` attributes are relative to.
+fn collect_from_items(items: &[Item], file_dir: &Path, module_dir: &Path, abi: &mut ContractAbi) -> Result<(), Error> {
+    for item in items {
+        match item {
+            Item::Mod(item_mod) => {
+                let name = item_mod.ident.to_string();
+                if let Some((_, items)) = &item_mod.content {
+                    collect_from_items(items, file_dir, &module_dir.join(&name), abi)?;
+                    continue;
+                }
+                // Files specified by #[path] resolve their child modules like mod.rs files.
+                let (file_path, child_dir) = match path_attribute(&item_mod.attrs) {
+                    Some(path) => {
+                        let file_path = file_dir.join(path);
+                        let child_dir = file_path.parent().unwrap().to_path_buf();
+                        (file_path, child_dir)
+                    }
+                    None => {
+                        let file_path = module_dir.join(format!("{name}.rs"));
+                        let file_path = if file_path.exists() {
+                            file_path
+                        } else {
+                            module_dir.join(&name).join("mod.rs")
+                        };
+                        (file_path, module_dir.join(&name))
+                    }
+                };
+                collect_from_file(&file_path, &child_dir, abi)?;
+            }
+            Item::Struct(item_struct) if has_attribute(&item_struct.attrs, "contract") => {
+                abi.storage = item_struct
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field)| StorageFieldAbi {
+                        name: field
+                            .ident
+                            .as_ref()
+                            .map_or(index.to_string(), |ident| ident.to_string()),
+                        ty: type_string(&field.ty),
+                    })
+                    .collect();
+            }
+            Item::Impl(item_impl) if has_attribute(&item_impl.attrs, "contract_methods") => {
+                abi.contract = type_string(&item_impl.self_ty);
+                for impl_item in &item_impl.items {
+                    if let ImplItem::Fn(method) = impl_item {
+                        if has_attribute(&method.attrs, "call") {
+                            abi.methods.push(method_abi(&method.sig));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn method_abi(sig: &syn::Signature) -> MethodAbi {
+    let mut is_view = false;
+    let mut args = vec![];
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                is_view = receiver.reference.is_some() && receiver.mutability.is_none();
+            }
+            FnArg::Typed(pat_type) => args.push(ArgumentAbi {
+                name: match pat_type.pat.as_ref() {
+                    Pat::Ident(pat_ident) => pat_ident.ident.to_string(),
+                    pat => normalize(&pat.to_token_stream().to_string()),
+                },
+                ty: type_string(&pat_type.ty),
+            }),
+        }
+    }
+    MethodAbi {
+        name: sig.ident.to_string(),
+        args,
+        return_type: match &sig.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => Some(type_string(ty)),
+        },
+        is_view,
+    }
+}
+
+/// Checks if any of the attributes has the name, e.g. `#[call]` or `#[pchain_sdk::call]`.
+fn has_attribute(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .any(|attr| attr.path().segments.last().is_some_and(|segment| segment.ident == name))
+}
+
+/// Returns the value of the attribute `#[path = "..."]`, if any.
+fn path_attribute(attrs: &[syn::Attribute]) -> Option<PathBuf> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(name_value) if name_value.path.is_ident("path") => match &name_value.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(path),
+                ..
+            }) => Some(PathBuf::from(path.value())),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn type_string(ty: &syn::Type) -> String {
+    normalize(&ty.to_token_stream().to_string())
+}
+
+/// Removes the spaces that the token stream puts around punctuations, e.g. `Vec < u8 >` becomes `Vec<u8>`.
+fn normalize(tokens: &str) -> String {
+    [
+        (" :: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        ("& ", "&"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+    ]
+    .iter()
+    .fold(tokens.to_string(), |s, (from, to)| s.replace(from, to))
+}
@@ -0,0 +1,547 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Build backends that compile the source code of a contract into a WebAssembly binary.
+//!
+//! [crate::Config::run] builds with [DockerBackend], [DockerCliBackend], [DockerlessBackend] or [SandboxBackend]
+//! according to its [crate::DockerOption].
+//! Other backends, e.g. a remote builder or a sandbox, can be plugged in with [crate::Config::run_with_backend]. The
+//! steps before and after the compilation (build cache, validation, size report, ABI and build metadata) are the same
+//! for all backends. See [crate::build].
+//!
+//! [FakeBackend] saves a given binary as the compiled contract without compiling anything. It is for testing the
+//! building process without docker and the wasm toolchain.
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bollard::Docker;
+use tokio_util::sync::CancellationToken;
+
+use crate::{error::Error, BuildOptions, DockerConfig, OutputLayout, SandboxConfig};
+
+/// Request to compile a contract, passed to [BuildBackend::compile].
+#[derive(Clone)]
+pub struct BuildRequest {
+    /// Absolute path to the source code folder.
+    pub source_path: PathBuf,
+    /// Options for building rust code.
+    pub options: BuildOptions,
+    /// File name of the binary built by cargo, e.g. `hello_contract.wasm`.
+    pub cargo_wasm_file: String,
+    /// Folder to save the compiled contract and the Cargo.lock used in building.
+    pub output_path: PathBuf,
+    /// File name of the compiled contract in `output_path`.
+    pub wasm_file: String,
+    /// Temporary folder that the backend can work in. It is removed after the build. If a size report is requested,
+    /// the intermediate binaries must be saved in it. See [crate::size].
+    pub working_path: PathBuf,
+}
+
+impl BuildRequest {
+    /// Returns true if the building environment is recorded in the build metadata, i.e. the backend should fill in
+    /// [BuildOutput::docker_image] and [BuildOutput::rustc_version].
+    pub fn needs_environment(&self) -> bool {
+        self.options.embed_metadata || self.options.output_layout == OutputLayout::PerContract
+    }
+}
+
+/// Result of a successful compilation, returned by [BuildBackend::compile].
+#[derive(Clone, Debug, Default)]
+pub struct BuildOutput {
+    /// Log of the building process.
+    pub build_log: String,
+    /// Tag and digest of the docker image if the contract is compiled in a docker container.
+    pub docker_image: Option<(String, Option<String>)>,
+    /// Output of `rustc --version` in the building environment.
+    pub rustc_version: Option<String>,
+}
+
+/// A backend compiles the source code of a contract, and saves the optimized binary to the output folder.
+pub trait BuildBackend: Send + Sync {
+    /// Returns the identifier of the toolchain used to build the source code at `source_path`, e.g. the digest of the
+    /// docker image, which is part of the key of the build cache (see [crate::cache]). None if it is not available, so
+    /// that the build cache is not used.
+    fn toolchain(&self, source_path: &Path) -> impl Future<Output = Option<String>> + Send;
+
+    /// Compiles the contract described by `request`. It saves the compiled contract as `wasm_file` and the Cargo.lock
+    /// in `output_path`. When `cancellation_token` is cancelled, it cleans up the resources it created and returns
+    /// [Error::BuildCancelled].
+    fn compile(
+        &self,
+        request: &BuildRequest,
+        cancellation_token: &CancellationToken,
+    ) -> impl Future<Output = Result<BuildOutput, Error>> + Send;
+}
+
+/// Backend that compiles contracts in a docker container created from the `pchain_compile` docker image.
+#[derive(Clone, Debug)]
+pub struct DockerBackend {
+    docker_image_tag: String,
+    docker_config: DockerConfig,
+}
+
+impl DockerBackend {
+    /// Creates a backend using the docker image of the tag and the docker daemon in `docker_config`. It fails if the
+    /// tag is unknown.
+    pub fn new(docker_config: DockerConfig) -> Result<Self, Error> {
+        Ok(Self {
+            docker_image_tag: docker_image_tag(&docker_config)?,
+            docker_config,
+        })
+    }
+}
+
+/// Returns the tag of the docker image in `docker_config`, or the default tag. It fails if the tag is unknown.
+pub(crate) fn docker_image_tag(docker_config: &DockerConfig) -> Result<String, Error> {
+    let docker_image_tag = docker_config
+        .tag
+        .clone()
+        .unwrap_or(crate::docker::PCHAIN_COMPILE_IMAGE_TAGS[0].0.to_string());
+    if crate::image::image_versions(&docker_image_tag).is_none() {
+        return Err(Error::UnkownDockerImageTag(docker_image_tag));
+    }
+    Ok(docker_image_tag)
+}
+
+impl BuildBackend for DockerBackend {
+    /// Returns the digest of the docker image if it exists locally. It is available after the image is pulled.
+    async fn toolchain(&self, _source_path: &Path) -> Option<String> {
+        let docker = crate::docker::connect(&self.docker_config).await.ok()?;
+        let image_name = match self.docker_config.local_image {
+            true => crate::image::local_image_name(&self.docker_image_tag),
+            false => format!("{}:{}", crate::docker::PCHAIN_COMPILE_IMAGE, self.docker_image_tag),
+        };
+        crate::docker::image_digest(&docker, &image_name).await
+    }
+
+    /// Pulls the docker image, starts a container and compiles the contract in it. The container is removed no
+    /// matter if the build is successful or cancelled.
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        // Retrieve dependency paths from manifest.
+        let mut dependencies = HashSet::new();
+        crate::manifests::get_dependency_paths(&request.source_path, &mut dependencies)?;
+
+        // Pull the Parallelchain Lab docker image
+        let container_name = crate::docker::random_container_name();
+        let docker = crate::docker::connect(&self.docker_config).await?;
+        let image_name = tokio::select! {
+            image_name = crate::docker::pull_image(&docker, &self.docker_image_tag, self.docker_config.local_image) => image_name?,
+            _ = cancellation_token.cancelled() => return Err(Error::BuildCancelled),
+        };
+
+        // The container is removed even if this future is dropped before the build finishes.
+        let container_guard = ContainerGuard::new({
+            let (docker, container_name) = (docker.clone(), container_name.clone());
+            async move {
+                let _ = crate::docker::remove_container(&docker, &container_name).await;
+            }
+        });
+
+        // Start the container, compile contract in it, and copy the intermediate binaries for size report to the working
+        // folder. It stops at the point of cancellation, and the container is removed afterwards.
+        let result = tokio::select! {
+            result = async {
+                crate::docker::start_container(&docker, &container_name, image_name.clone()).await?;
+                let build_log = compile_contract_in_docker_container(&docker, &container_name, dependencies, request).await?;
+                if request.options.size_report {
+                    copy_intermediates_from_container(&docker, &container_name, &request.working_path).await?;
+                }
+
+                // Collect information of the building environment before the container is removed
+                let mut output = BuildOutput { build_log, ..Default::default() };
+                if request.needs_environment() {
+                    output.docker_image = Some((
+                        self.docker_image_tag.clone(),
+                        crate::docker::image_digest(&docker, &image_name).await,
+                    ));
+                    output.rustc_version = crate::docker::rustc_version(&docker, &container_name).await;
+                }
+                Ok(output)
+            } => result,
+            _ = cancellation_token.cancelled() => Err(Error::BuildCancelled),
+        };
+
+        // Remove container no matter if build is successful. It is forced, so the running exec sessions are stopped.
+        container_guard.remove().await;
+
+        result
+    }
+}
+
+/// Inner process in [DockerBackend::compile] to compile contract in docker container. It does not remove docker container after use.
+/// It returns the build log if success.
+async fn compile_contract_in_docker_container(
+    docker: &Docker,
+    container_name: &str,
+    dependencies: impl IntoIterator<Item = String>,
+    request: &BuildRequest,
+) -> Result<String, Error> {
+    // Step 1. create dependency directory and copy source to docker
+    for dependency in dependencies {
+        crate::docker::copy_files(docker, container_name, &dependency).await?;
+    }
+
+    // Step 2: create directory paths inside docker and  copy file to container
+    crate::docker::copy_files(docker, container_name, request.source_path.to_str().unwrap()).await?;
+
+    // Step 3: build the source code inside docker
+    let (result_in_docker, build_log) = crate::docker::build_contracts(
+        docker,
+        container_name,
+        request.source_path.clone(),
+        &request.options,
+        &request.cargo_wasm_file,
+        &request.wasm_file,
+    )
+    .await?;
+
+    // Step 4: copy file from docker to given location
+    crate::docker::copy_files_from(
+        docker,
+        container_name,
+        &result_in_docker,
+        Some(request.output_path.clone()),
+        build_log.clone()
+    )
+    .await?;
+
+    Ok(build_log)
+}
+
+/// Copies the intermediate binaries kept for size report from docker container.
+async fn copy_intermediates_from_container(
+    docker: &Docker,
+    container_name: &str,
+    intermediates_path: &Path,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(intermediates_path).map_err(|_| Error::CreateTempDir)?;
+    crate::docker::copy_files_from(
+        docker,
+        container_name,
+        crate::docker::SIZE_REPORT_FOLDER,
+        Some(intermediates_path.to_path_buf()),
+        String::new(),
+    )
+    .await
+}
+
+/// Backend that compiles contracts in a docker container like [DockerBackend], but runs the container through a
+/// container CLI binary (e.g. `docker` or `podman`) instead of the API socket of the docker daemon.
+#[derive(Clone, Debug)]
+pub struct DockerCliBackend {
+    docker_image_tag: String,
+    local_image: bool,
+    cli: String,
+}
+
+impl DockerCliBackend {
+    /// Creates a backend using the docker image of the tag and the CLI binary in `docker_config`. The CLI binary
+    /// defaults to `docker`. It fails if the tag is unknown.
+    pub fn new(docker_config: DockerConfig) -> Result<Self, Error> {
+        Ok(Self {
+            docker_image_tag: docker_image_tag(&docker_config)?,
+            local_image: docker_config.local_image,
+            cli: docker_config.cli.unwrap_or("docker".to_string()),
+        })
+    }
+}
+
+impl BuildBackend for DockerCliBackend {
+    /// Returns the digest of the docker image if it exists locally. It is available after the image is pulled.
+    async fn toolchain(&self, _source_path: &Path) -> Option<String> {
+        let image_name = match self.local_image {
+            true => crate::image::local_image_name(&self.docker_image_tag),
+            false => crate::docker_cli::image_name(&self.docker_image_tag),
+        };
+        crate::docker_cli::image_digest(&self.cli, &image_name).await
+    }
+
+    /// Pulls the docker image, starts a container and compiles the contract in it by the CLI. The container is
+    /// removed no matter if the build is successful or cancelled.
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        let cli = self.cli.as_str();
+
+        // Retrieve dependency paths from manifest.
+        let mut dependencies = HashSet::new();
+        crate::manifests::get_dependency_paths(&request.source_path, &mut dependencies)?;
+
+        // Pull the Parallelchain Lab docker image
+        let container_name = crate::docker::random_container_name();
+        let image_name = tokio::select! {
+            image_name = crate::docker_cli::pull_image(cli, &self.docker_image_tag, self.local_image) => image_name?,
+            _ = cancellation_token.cancelled() => return Err(Error::BuildCancelled),
+        };
+
+        // The container is removed even if this future is dropped before the build finishes.
+        let container_guard = ContainerGuard::new({
+            let (cli, container_name) = (cli.to_string(), container_name.clone());
+            async move {
+                let _ = crate::docker_cli::remove_container(&cli, &container_name).await;
+            }
+        });
+
+        // Start the container, compile contract in it, and copy the intermediate binaries for size report to the working
+        // folder. It stops at the point of cancellation, and the container is removed afterwards.
+        let result = tokio::select! {
+            result = async {
+                crate::docker_cli::start_container(cli, &container_name, &image_name).await?;
+                for dependency in dependencies {
+                    crate::docker_cli::copy_files(cli, &container_name, &dependency).await?;
+                }
+                crate::docker_cli::copy_files(cli, &container_name, request.source_path.to_str().unwrap()).await?;
+                let (result_in_container, build_log) = crate::docker_cli::build_contracts(
+                    cli,
+                    &container_name,
+                    request.source_path.clone(),
+                    &request.options,
+                    &request.cargo_wasm_file,
+                    &request.wasm_file,
+                )
+                .await?;
+                crate::docker_cli::copy_files_from(
+                    cli,
+                    &container_name,
+                    &result_in_container,
+                    Some(request.output_path.clone()),
+                    build_log.clone(),
+                )
+                .await?;
+                if request.options.size_report {
+                    crate::docker_cli::copy_files_from(
+                        cli,
+                        &container_name,
+                        crate::docker::SIZE_REPORT_FOLDER,
+                        Some(request.working_path.clone()),
+                        String::new(),
+                    )
+                    .await?;
+                }
+
+                // Collect information of the building environment before the container is removed
+                let mut output = BuildOutput { build_log, ..Default::default() };
+                if request.needs_environment() {
+                    output.docker_image = Some((
+                        self.docker_image_tag.clone(),
+                        crate::docker_cli::image_digest(cli, &image_name).await,
+                    ));
+                    output.rustc_version = crate::docker_cli::rustc_version(cli, &container_name).await;
+                }
+                Ok(output)
+            } => result,
+            _ = cancellation_token.cancelled() => Err(Error::BuildCancelled),
+        };
+
+        // Remove container no matter if build is successful. It is forced, so the running exec sessions are stopped.
+        container_guard.remove().await;
+
+        result
+    }
+}
+
+/// Removes a container when it is dropped, so that the container does not leak if the future building in it is
+/// dropped before it finishes, e.g. when its task is aborted. As the removal is async, it is spawned on the runtime.
+struct ContainerGuard<F: Future<Output = ()> + Send + 'static> {
+    remove: Option<F>,
+}
+
+impl<F: Future<Output = ()> + Send + 'static> ContainerGuard<F> {
+    fn new(remove: F) -> Self {
+        Self { remove: Some(remove) }
+    }
+
+    /// Removes the container, and waits until it is removed.
+    async fn remove(mut self) {
+        if let Some(remove) = self.remove.take() {
+            remove.await;
+        }
+    }
+}
+
+impl<F: Future<Output = ()> + Send + 'static> Drop for ContainerGuard<F> {
+    fn drop(&mut self) {
+        if let (Some(remove), Ok(runtime)) = (self.remove.take(), tokio::runtime::Handle::try_current()) {
+            runtime.spawn(remove);
+        }
+    }
+}
+
+/// Backend that compiles contracts by cargo in the local environment. It requires the installation of Rust and
+/// the target `wasm32-unknown-unknown`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DockerlessBackend;
+
+impl BuildBackend for DockerlessBackend {
+    /// Returns the output of `rustc --version` of the toolchain selected for the source code.
+    async fn toolchain(&self, source_path: &Path) -> Option<String> {
+        crate::cargo::rustc_version(source_path)
+    }
+
+    /// Builds the contract by cargo in the working folder. Cargo runs on a blocking thread, so that it does not hold
+    /// a worker of the async runtime. It cannot be interrupted, so on cancellation this method returns immediately,
+    /// and the running cargo build finishes in the background. The build then fails because the working folder is
+    /// removed, so the contract is not saved.
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        if cancellation_token.is_cancelled() {
+            return Err(Error::BuildCancelled);
+        }
+
+        let cargo_request = request.clone();
+        let cargo_build = tokio::task::spawn_blocking(move || {
+            crate::cargo::build_contract(
+                &cargo_request.working_path,
+                &cargo_request.source_path,
+                Some(cargo_request.output_path.clone()),
+                &cargo_request.options,
+                &cargo_request.cargo_wasm_file,
+                &cargo_request.wasm_file,
+            )
+        });
+        let build_log = tokio::select! {
+            build_log = cargo_build => build_log.map_err(|e| Error::BuildFailure(e.to_string()))??,
+            _ = cancellation_token.cancelled() => return Err(Error::BuildCancelled),
+        };
+
+        Ok(BuildOutput {
+            build_log,
+            docker_image: None,
+            rustc_version: request.needs_environment().then(|| crate::cargo::rustc_version(&request.source_path)).flatten(),
+        })
+    }
+}
+
+/// Backend that compiles contracts by cargo in a Linux user-namespace sandbox created by bubblewrap. It isolates the
+/// build like a docker container, without a daemon. It requires the installation of bubblewrap, Rust and the target
+/// `wasm32-unknown-unknown`. See [crate::SandboxConfig].
+#[derive(Clone, Debug, Default)]
+pub struct SandboxBackend {
+    sandbox_config: SandboxConfig,
+}
+
+impl SandboxBackend {
+    /// Creates a backend using the toolchain and the bubblewrap binary in `sandbox_config`.
+    pub fn new(sandbox_config: SandboxConfig) -> Self {
+        Self { sandbox_config }
+    }
+}
+
+impl BuildBackend for SandboxBackend {
+    /// Returns the output of `rustc --version` of the toolchain selected for the source code.
+    async fn toolchain(&self, source_path: &Path) -> Option<String> {
+        crate::sandbox::rustc_version(&self.sandbox_config, source_path).await
+    }
+
+    /// Builds the contract in the sandbox. The sandbox is killed on cancellation.
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        let build_log = tokio::select! {
+            build_log = crate::sandbox::build_contract(
+                &self.sandbox_config,
+                &request.working_path,
+                &request.source_path,
+                &request.output_path,
+                &request.options,
+                &request.cargo_wasm_file,
+                &request.wasm_file,
+            ) => build_log?,
+            _ = cancellation_token.cancelled() => return Err(Error::BuildCancelled),
+        };
+
+        let rustc_version = match request.needs_environment() {
+            true => crate::sandbox::rustc_version(&self.sandbox_config, &request.source_path).await,
+            false => None,
+        };
+        Ok(BuildOutput {
+            build_log,
+            docker_image: None,
+            rustc_version,
+        })
+    }
+}
+
+/// In-memory backend that saves a given binary as the compiled contract, without compiling the source code. The
+/// Cargo.lock in the source code folder is copied to the output folder if it exists.
+#[derive(Clone, Debug, Default)]
+pub struct FakeBackend {
+    /// Binary saved as the compiled contract. If None, the compilation fails with [Error::BuildFailureWithLogs].
+    pub wasm: Option<Vec<u8>>,
+    /// Log of the building process.
+    pub build_log: String,
+    /// Identifier of the toolchain. The build cache is not used if it is None. (Default)
+    pub toolchain: Option<String>,
+    compilations: Arc<AtomicUsize>,
+}
+
+impl FakeBackend {
+    /// Creates a backend that saves `wasm` as the compiled contract.
+    pub fn new(wasm: Vec<u8>) -> Self {
+        Self {
+            wasm: Some(wasm),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a backend that fails every compilation with `build_log`.
+    pub fn failing(build_log: impl Into<String>) -> Self {
+        Self {
+            build_log: build_log.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of compilations requested to this backend and its clones.
+    pub fn compilations(&self) -> usize {
+        self.compilations.load(Ordering::SeqCst)
+    }
+}
+
+impl BuildBackend for FakeBackend {
+    async fn toolchain(&self, _source_path: &Path) -> Option<String> {
+        self.toolchain.clone()
+    }
+
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        self.compilations.fetch_add(1, Ordering::SeqCst);
+        if cancellation_token.is_cancelled() {
+            return Err(Error::BuildCancelled);
+        }
+        let wasm = self
+            .wasm
+            .as_ref()
+            .ok_or(Error::BuildFailureWithLogs(self.build_log.clone()))?;
+
+        let write = |path: PathBuf| {
+            std::fs::write(&path, wasm)
+                .map_err(|e| Error::BuildFailure(format!("Fail to save {}: {e}", path.display())))
+        };
+        write(request.output_path.join(&request.wasm_file))?;
+        if request.options.size_report {
+            for file in [
+                request.wasm_file.as_str(),
+                crate::size::OPTIMIZED_WASM,
+                crate::size::SNIPPED_WASM,
+                crate::size::NAMED_WASM,
+            ] {
+                write(request.working_path.join(file))?;
+            }
+        }
+        let _ = std::fs::copy(
+            request.source_path.join("Cargo.lock"),
+            request.output_path.join("Cargo.lock"),
+        );
+
+        Ok(BuildOutput {
+            build_log: self.build_log.clone(),
+            docker_image: None,
+            rustc_version: None,
+        })
+    }
+}
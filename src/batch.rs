@@ -0,0 +1,96 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of building multiple contracts at a time. The number of builds running in parallel is limited,
+//! because each build starts a docker container or an in-process cargo build, which takes a lot of memory.
+
+use std::{future::Future, sync::Arc};
+
+use tokio::{sync::Semaphore, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+
+use crate::{error::Error, BuildBackend, Config};
+
+/// Returns the default number of builds running in parallel, i.e. the number of available CPUs.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// `build_all` builds the contracts with at most `jobs` builds running in parallel. `on_build` is called with the
+/// result of each build as soon as it finishes. It returns the results in the order of `configs`.
+///
+/// When `cancellation_token` is cancelled, the running builds are cancelled (see [Config::run_cancellable]), and the
+/// builds not yet started return [Error::BuildCancelled].
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` build --source `source_path` --source `source_path` --jobs `jobs`
+pub async fn build_all<F>(
+    configs: Vec<Config>,
+    jobs: usize,
+    cancellation_token: CancellationToken,
+    on_build: F,
+) -> Vec<Result<String, Error>>
+where
+    F: FnMut(&Config, &Result<String, Error>),
+{
+    run_all(configs, jobs, cancellation_token, on_build, |config, cancellation_token| {
+        config.run_cancellable(cancellation_token)
+    })
+    .await
+}
+
+/// `build_all_with_backend` is the same as [build_all], but builds all the contracts with `backend`, instead of the
+/// backends selected by their `docker_option`. See [Config::run_with_backend].
+pub async fn build_all_with_backend<B, F>(
+    configs: Vec<Config>,
+    jobs: usize,
+    backend: Arc<B>,
+    cancellation_token: CancellationToken,
+    on_build: F,
+) -> Vec<Result<String, Error>>
+where
+    B: BuildBackend + 'static,
+    F: FnMut(&Config, &Result<String, Error>),
+{
+    run_all(configs, jobs, cancellation_token, on_build, move |config, cancellation_token| {
+        let backend = backend.clone();
+        async move { config.run_with_backend(&*backend, cancellation_token).await }
+    })
+    .await
+}
+
+/// Runs `run` on each config, with at most `jobs` of them running in parallel.
+async fn run_all<F, R, Fut>(
+    configs: Vec<Config>,
+    jobs: usize,
+    cancellation_token: CancellationToken,
+    mut on_build: F,
+    run: R,
+) -> Vec<Result<String, Error>>
+where
+    F: FnMut(&Config, &Result<String, Error>),
+    R: Fn(Config, CancellationToken) -> Fut,
+    Fut: Future<Output = Result<String, Error>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut builds = JoinSet::new();
+    for (index, config) in configs.iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let build = run(config.clone(), cancellation_token.clone());
+        builds.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("semaphore is never closed");
+            (index, build.await)
+        });
+    }
+
+    let mut results: Vec<Option<Result<String, Error>>> = configs.iter().map(|_| None).collect();
+    while let Some(joined) = builds.join_next().await {
+        let (index, result) = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        on_build(&configs[index], &result);
+        results[index] = Some(result);
+    }
+    results.into_iter().map(Option::unwrap).collect()
+}
@@ -8,7 +8,7 @@
 //! in a docker environment.
 
 use clap::Parser;
-use pchain_compile::{config::Config, DockerConfig, DockerOption, BuildOptions};
+use pchain_compile::{error::Error, run::ContractCall, scaffold::Template, CancellationToken, DockerConfig, BuildOptions, FloatPolicy, OutputLayout, ProjectConfig, SizeBaseline, WarningHandler};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Parser)]
@@ -24,7 +24,11 @@ enum PchainCompile {
     /// Build the source code. By default, it uses docker for building the contract. Please make sure:
     /// 1. Docker is installed and its execution permission under current user is granted.
     /// 2. Internet is reachable. (for pulling the docker image from docker hub)
-    #[clap(arg_required_else_help = true, display_order = 1, verbatim_doc_comment)]
+    ///
+    /// Default settings are read from the file "pchain_compile.toml" in the current folder, or the table
+    /// [package.metadata.pchain] in "Cargo.toml" of the current folder. The options below override the settings.
+    /// A flag set in the file can be turned off by "=false", e.g. "--locked=false".
+    #[clap(display_order = 1, verbatim_doc_comment)]
     Build {
         /// Absolute/Relative path to the source code directory. This field can be used multiple times to build multiple contracts at a time.
         /// For example,
@@ -42,8 +46,86 @@ enum PchainCompile {
         /// 
         /// With or without the file "Cargo.lock", the compilation output includes the file "Cargo.lock" which was used or
         /// generated in the building process.
-        #[clap(long = "locked", display_order = 3, verbatim_doc_comment)]
-        locked: bool,
+        #[clap(long = "locked", display_order = 3, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        locked: Option<bool>,
+
+        /// Build with the version-locked dependencies in strict mode. It implies "--locked", but the building process
+        /// fails if the file "Cargo.lock" does not exist in the source code directory, or if it needs to be updated
+        /// according to the manifest file.
+        #[clap(long = "frozen", display_order = 4, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        frozen: Option<bool>,
+
+        /// Comma separated list of features to activate. It is equivalent to running "cargo build"
+        /// with the flag "--features".
+        #[clap(long = "features", display_order = 5, value_delimiter = ',', verbatim_doc_comment)]
+        features: Vec<String>,
+
+        /// Embed build provenance metadata into the compiled contract as a WebAssembly custom section
+        /// named "pchain.build". The metadata includes the versions of pchain_compile and rustc, the docker
+        /// image tag and digest, the hashes of the source code and the file "Cargo.lock", and the features.
+        #[clap(long = "embed-metadata", display_order = 6, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        embed_metadata: Option<bool>,
+
+        /// Embed the ABI of the contract into the compiled contract as a WebAssembly custom section named "pchain.abi".
+        /// The ABI is always saved as "<contract>.abi.json" in the destination folder. It lists the methods marked with
+        /// "#[call]", their arguments, return types and whether they are views (i.e. the receiver is "&self").
+        #[clap(long = "embed-abi", display_order = 7, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        embed_abi: Option<bool>,
+
+        /// How to handle floating-point instructions found in the compiled contract. Floating-point operations are
+        /// non-deterministic, and may be added by dependencies unexpectedly. Possible values:
+        /// - allow: ignore them.
+        /// - warn: print the functions that use them. (Default)
+        /// - error: fail the building process.
+        #[clap(long = "float-policy", display_order = 8, verbatim_doc_comment)]
+        float_policy: Option<FloatPolicy>,
+
+        /// Print a size report of the compiled contract, and save it as "<contract>.size.json" in the destination
+        /// folder. The report shows the largest functions, their sizes grouped by crate, the data segments, and
+        /// how many bytes wasm-opt and wasm-snip removed.
+        #[clap(long = "size-report", display_order = 9, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        size_report: Option<bool>,
+
+        /// Maximum size (in bytes) of the compiled contract. The building process fails if the contract is larger.
+        #[clap(long = "max-wasm-size", display_order = 10, verbatim_doc_comment)]
+        max_wasm_size: Option<usize>,
+
+        /// Path to a baseline WebAssembly binary (or a folder containing the binary with the same file name) to compare
+        /// the size of the compiled contract with. The building process fails if the contract grows more than the
+        /// percentage set by "--max-size-growth".
+        #[clap(long = "size-baseline", display_order = 11, verbatim_doc_comment)]
+        size_baseline: Option<PathBuf>,
+
+        /// Maximum growth (in percentage) of the size of the compiled contract compared with "--size-baseline". (Default: 0)
+        #[clap(long = "max-size-growth", display_order = 12, verbatim_doc_comment)]
+        max_size_growth: Option<f64>,
+
+        /// Always build the source code. By default, a build is skipped and the saved output is copied to the destination
+        /// if the same source code was built with the same options and toolchain before. Successful builds are saved in the
+        /// cache folder ($PCHAIN_COMPILE_CACHE_DIR, or "pchain_compile" in the user's cache folder). See "pchain_compile cache".
+        #[clap(long = "no-cache", display_order = 13, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        no_cache: Option<bool>,
+
+        /// Keep running after the first build, and rebuild the contracts whenever their source code or path dependencies
+        /// change. Changes are debounced, and only the affected contracts are rebuilt. Press Ctrl-C to stop.
+        #[clap(long = "watch", display_order = 14, verbatim_doc_comment)]
+        watch: bool,
+
+        /// Layout of the output files in the destination folder. Possible values:
+        /// - flat: save the files of all contracts in the destination folder. (Default)
+        /// - per-contract: save the files of each contract in the folder "<destination>/<contract>", including its own
+        ///   "Cargo.lock", the build metadata "build.json" and the build log "build.log".
+        #[clap(long = "output-layout", display_order = 15, verbatim_doc_comment)]
+        output_layout: Option<OutputLayout>,
+
+        /// Append the version of the package to the file name of the compiled contract, e.g. "hello_contract-0.4.0.wasm".
+        #[clap(long = "versioned-name", display_order = 16, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        versioned_name: Option<bool>,
+
+        /// Maximum number of contracts being built in parallel. Each build runs a docker container, or a cargo build with
+        /// "--dockerless", so building many contracts at a time can run out of memory. (Default: the number of CPUs)
+        #[clap(long = "jobs", short = 'j', display_order = 17, value_parser = clap::value_parser!(u64).range(1..), verbatim_doc_comment)]
+        jobs: Option<u64>,
 
         /// Compile contract without using docker. This option requires installation of Rust and target "wasm32-unknown-unknown".
         /// **Please note the compiled contracts are not always consistent with the previous compiled ones, because the building 
@@ -54,11 +136,14 @@ enum PchainCompile {
         /// $ rustup target add wasm32-unknown-unknown
         #[clap(
             long = "dockerless",
-            display_order = 4,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true",
+            display_order = 18,
             verbatim_doc_comment,
             group = "docker-option"
         )]
-        dockerless: bool,
+        dockerless: Option<bool>,
 
         /// Tag of the docker image being pulled from Dockerhub. Please find the tags information in
         /// https://hub.docker.com/r/parallelchainlab/pchain_compile.
@@ -69,11 +154,358 @@ enum PchainCompile {
         /// - 0.4.3
         #[clap(
             long = "use-docker-tag",
-            display_order = 5,
+            display_order = 19,
             verbatim_doc_comment,
             group = "docker-option"
         )]
         docker_image_tag: Option<String>,
+
+        /// Compile contract in a Linux user-namespace sandbox created by bubblewrap, without using docker. The sandbox has
+        /// no network, and mounts the source code and the toolchain read-only. The dependencies are fetched before building.
+        /// This option requires installation of bubblewrap, Rust and target "wasm32-unknown-unknown".
+        #[clap(
+            long = "sandbox",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true",
+            display_order = 20,
+            verbatim_doc_comment,
+            group = "docker-option"
+        )]
+        sandbox: Option<bool>,
+
+        /// Rust toolchain installed by rustup (e.g. "1.71.0") to build in the sandbox. (Default: the toolchain selected by
+        /// rustup for the source code)
+        #[clap(long = "sandbox-toolchain", display_order = 21, verbatim_doc_comment)]
+        sandbox_toolchain: Option<String>,
+
+        /// Address of the docker daemon, either a unix socket or an HTTP address. (Default: the environment variable
+        /// DOCKER_HOST, or the default docker socket, or the Podman socket if the docker socket does not exist) For example,
+        /// - unix:///run/user/1000/podman/podman.sock
+        /// - tcp://127.0.0.1:2375
+        #[clap(long = "docker-host", display_order = 22, verbatim_doc_comment)]
+        docker_host: Option<String>,
+
+        /// Folder of the TLS certificates "ca.pem", "cert.pem" and "key.pem" to connect to the docker daemon at an HTTP address.
+        /// (Default: "$DOCKER_CERT_PATH" or "~/.docker" if the environment variable DOCKER_TLS_VERIFY is set, otherwise no TLS)
+        #[clap(long = "docker-tls-cert-path", display_order = 23, verbatim_doc_comment)]
+        docker_tls_cert_path: Option<PathBuf>,
+
+        /// Version of the Docker Engine API, e.g. "1.41". (Default: negotiated with the docker daemon)
+        #[clap(long = "docker-api-version", display_order = 24, verbatim_doc_comment)]
+        docker_api_version: Option<String>,
+
+        /// Timeout (in seconds) of the requests to the docker daemon. (Default: 120)
+        #[clap(long = "docker-timeout", display_order = 25, verbatim_doc_comment)]
+        docker_timeout: Option<u64>,
+
+        /// Container CLI binary (e.g. "docker" or "podman") to run the docker container with, for environments where the
+        /// API socket of the docker daemon is not reachable. The CLI uses its own connection settings, so the options
+        /// "--docker-host", "--docker-tls-cert-path", "--docker-api-version" and "--docker-timeout" are ignored.
+        #[clap(long = "docker-cli", display_order = 26, verbatim_doc_comment)]
+        docker_cli: Option<String>,
+
+        /// Build with the docker image built locally by "pchain_compile image build" (e.g. "localhost/pchain_compile_local:0.4.3")
+        /// instead of pulling the image from Dockerhub.
+        #[clap(long = "docker-local-image", display_order = 27, num_args = 0..=1, require_equals = true, default_missing_value = "true", verbatim_doc_comment)]
+        docker_local_image: Option<bool>,
+
+        /// Path to the project configuration file, which is either "pchain_compile.toml" or "Cargo.toml" with the table
+        /// [package.metadata.pchain]. (Default: the file found in the current folder) For example,
+        ///
+        /// contracts = ["contracts/token", "contracts/exchange"]
+        /// destination = "artifacts"
+        /// locked = true
+        /// docker-tag = "0.4.3"
+        ///
+        /// The keys are the names of the options above, except "contracts" for "--source", "destination" for
+        /// "--destination" and "docker-tag" for "--use-docker-tag". Relative paths are resolved against the folder
+        /// containing the file.
+        #[clap(long = "config", display_order = 28, verbatim_doc_comment)]
+        config_path: Option<PathBuf>,
+    },
+
+    /// Verify that a WebAssembly binary is reproducibly built from the source code. The source code is rebuilt in
+    /// docker with the file "Cargo.lock" (if it exists), and the SHA-256 of the result is compared with the given binary.
+    /// If they do not match, the differences are shown section by section.
+    #[clap(arg_required_else_help = true, display_order = 2, verbatim_doc_comment)]
+    Verify {
+        /// Absolute/Relative path to the source code directory.
+        #[clap(long = "source", display_order = 1, verbatim_doc_comment)]
+        source_path: PathBuf,
+
+        /// Absolute/Relative path to the WebAssembly binary to be verified.
+        #[clap(long = "wasm", display_order = 2, verbatim_doc_comment)]
+        wasm_path: PathBuf,
+
+        /// Tag of the docker image being pulled from Dockerhub. It should be the tag that was used to build
+        /// the WebAssembly binary. (Default: the tag recorded in the build metadata of the binary, or the
+        /// default tag if the binary has no build metadata)
+        #[clap(long = "use-docker-tag", display_order = 3, verbatim_doc_comment)]
+        docker_image_tag: Option<String>,
+
+        /// Comma separated list of features that were activated to build the WebAssembly binary. (Default: the
+        /// features recorded in the build metadata of the binary, or no features if the binary has no build metadata)
+        #[clap(long = "features", display_order = 4, value_delimiter = ',', verbatim_doc_comment)]
+        features: Option<Vec<String>>,
+
+        /// Address of the docker daemon. See "pchain_compile build --help".
+        #[clap(long = "docker-host", display_order = 5, verbatim_doc_comment)]
+        docker_host: Option<String>,
+
+        /// Folder of the TLS certificates to connect to the docker daemon. See "pchain_compile build --help".
+        #[clap(long = "docker-tls-cert-path", display_order = 6, verbatim_doc_comment)]
+        docker_tls_cert_path: Option<PathBuf>,
+
+        /// Version of the Docker Engine API. See "pchain_compile build --help".
+        #[clap(long = "docker-api-version", display_order = 7, verbatim_doc_comment)]
+        docker_api_version: Option<String>,
+
+        /// Timeout (in seconds) of the requests to the docker daemon. (Default: 120)
+        #[clap(long = "docker-timeout", display_order = 8, verbatim_doc_comment)]
+        docker_timeout: Option<u64>,
+
+        /// Container CLI binary to run the docker container with. See "pchain_compile build --help".
+        #[clap(long = "docker-cli", display_order = 9, verbatim_doc_comment)]
+        docker_cli: Option<String>,
+    },
+
+    /// Inspect a compiled WebAssembly binary. It lists the imports (grouped by host module), exports, memory and
+    /// table limits, data segments, custom sections and the embedded build metadata (if any).
+    #[clap(arg_required_else_help = true, display_order = 3, verbatim_doc_comment)]
+    Inspect {
+        /// Absolute/Relative path to the WebAssembly binary.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        wasm_path: PathBuf,
+
+        /// Print the result in JSON format.
+        #[clap(long = "json", display_order = 2, verbatim_doc_comment)]
+        json: bool,
+    },
+
+    /// Validate a compiled WebAssembly binary against the rules of ParallelChain runtime. It checks the imported host
+    /// functions, the required exports, the memory limit and the use of disallowed WebAssembly proposals.
+    #[clap(arg_required_else_help = true, display_order = 4, verbatim_doc_comment)]
+    Validate {
+        /// Absolute/Relative path to the WebAssembly binary.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        wasm_path: PathBuf,
+    },
+
+    /// Profile the size of a WebAssembly binary. It shows the largest functions, their sizes grouped by crate and
+    /// the data segments. Functions are named only if the binary contains the name section, e.g. the output of cargo
+    /// build in "target/wasm32-unknown-unknown/release". To profile the building steps, use "build --size-report".
+    #[clap(arg_required_else_help = true, display_order = 5, verbatim_doc_comment)]
+    Size {
+        /// Absolute/Relative path to the WebAssembly binary.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        wasm_path: PathBuf,
+
+        /// Print the result in JSON format.
+        #[clap(long = "json", display_order = 2, verbatim_doc_comment)]
+        json: bool,
+    },
+
+    /// Check the upgrade compatibility between two versions of a contract. It compares the methods marked with "#[call]"
+    /// and the storage fields of the "#[contract]" struct, and classifies each change as breaking or compatible.
+    /// It exits with code 1 if any change is breaking.
+    #[clap(arg_required_else_help = true, display_order = 6, verbatim_doc_comment)]
+    Compat {
+        /// Absolute/Relative path to the old version. It can be the source code directory, the ABI file
+        /// "<contract>.abi.json" generated in building, or the WebAssembly binary built with "--embed-abi".
+        #[clap(long = "old", display_order = 1, verbatim_doc_comment)]
+        old_path: PathBuf,
+
+        /// Absolute/Relative path to the source code directory of the new version.
+        #[clap(long = "new", display_order = 2, verbatim_doc_comment)]
+        new_path: PathBuf,
+
+        /// Print the result in JSON format.
+        #[clap(long = "json", display_order = 3, verbatim_doc_comment)]
+        json: bool,
+    },
+
+    /// Post-process a WebAssembly binary built by cargo (e.g. in "target/wasm32-unknown-unknown/release"), without
+    /// building the source code. It runs the same steps as the building process after "cargo build", i.e.
+    /// "wasm-opt -Oz", "wasm-snip" and "wasm-opt --dce", then validates the result.
+    #[clap(arg_required_else_help = true, display_order = 7, verbatim_doc_comment)]
+    Optimize {
+        /// Absolute/Relative path to the WebAssembly binary.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        input_path: PathBuf,
+
+        /// Absolute/Relative path for saving the optimized WebAssembly binary.
+        #[clap(short = 'o', long = "output", display_order = 2, verbatim_doc_comment)]
+        output_path: PathBuf,
+
+        /// How to handle floating-point instructions found in the optimized binary. Possible values:
+        /// - allow: ignore them.
+        /// - warn: print the functions that use them. (Default)
+        /// - error: fail the process.
+        #[clap(long = "float-policy", display_order = 3, default_value = "warn", verbatim_doc_comment)]
+        float_policy: FloatPolicy,
+
+        /// Print a size report of the optimized binary, and save it as "<contract>.size.json" next to the binary.
+        #[clap(long = "size-report", display_order = 4, verbatim_doc_comment)]
+        size_report: bool,
+
+        /// Maximum size (in bytes) of the optimized binary. The process fails if the binary is larger.
+        #[clap(long = "max-wasm-size", display_order = 5, verbatim_doc_comment)]
+        max_wasm_size: Option<usize>,
+
+        /// Path to a baseline WebAssembly binary (or a folder containing the binary with the same file name) to compare
+        /// the size of the optimized binary with. The process fails if the binary grows more than the percentage set
+        /// by "--max-size-growth".
+        #[clap(long = "size-baseline", display_order = 6, verbatim_doc_comment)]
+        size_baseline: Option<PathBuf>,
+
+        /// Maximum growth (in percentage) of the size of the optimized binary compared with "--size-baseline".
+        #[clap(long = "max-size-growth", display_order = 7, default_value = "0", requires = "size_baseline", verbatim_doc_comment)]
+        max_size_growth: f64,
+    },
+
+    /// Run a method of a compiled contract locally, without deploying it. The contract is executed by an embedded
+    /// WebAssembly runtime with in-memory host functions, and the logs, the return value and the storage changes are
+    /// printed. Internal calls, transfers, deposit commands and cryptographic functions other than sha256 are not supported.
+    #[clap(arg_required_else_help = true, display_order = 8, verbatim_doc_comment)]
+    Run {
+        /// Absolute/Relative path to the WebAssembly binary.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        wasm_path: PathBuf,
+
+        /// Name of the method marked with "#[call]".
+        #[clap(long = "method", display_order = 2, verbatim_doc_comment)]
+        method: String,
+
+        /// Arguments of the method, serialized according to the argument types in the ABI. For example,
+        /// --args Alice 42 true 0x0102 "[1, 2]"
+        /// Vec<u8> and byte arrays are written in hex. Other vectors, arrays and tuples are written as JSON arrays.
+        /// Arguments of other types are written as their borsh serialization in hex.
+        #[clap(long = "args", num_args = 1.., display_order = 3, verbatim_doc_comment)]
+        args: Vec<String>,
+
+        /// Absolute/Relative path to the ABI file. By default, the ABI embedded in the contract, or the file
+        /// "<contract>.abi.json" next to the contract is used.
+        #[clap(long = "abi", display_order = 4, verbatim_doc_comment)]
+        abi_path: Option<PathBuf>,
+
+        /// Address of the calling account in hex. (Default: zeros)
+        #[clap(long = "calling-account", display_order = 5, verbatim_doc_comment)]
+        calling_account: Option<String>,
+
+        /// Amount of tokens transferred with the call.
+        #[clap(long = "amount", display_order = 6, default_value = "0", verbatim_doc_comment)]
+        amount: u64,
+
+        /// Absolute/Relative path to a JSON file of the contract storage (keys and values in hex). The storage is
+        /// loaded from the file (if it exists) before the call, and saved to it after the call.
+        #[clap(long = "storage", display_order = 7, verbatim_doc_comment)]
+        storage_path: Option<PathBuf>,
+
+        /// Print the result in JSON format.
+        #[clap(long = "json", display_order = 8, verbatim_doc_comment)]
+        json: bool,
+    },
+
+    /// Manage the local build cache. Successful builds are saved in the cache, and building the same source code with the
+    /// same options and toolchain again copies the saved output instead.
+    #[clap(arg_required_else_help = true, display_order = 9, verbatim_doc_comment)]
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Create a contract project in a new folder. The project contains "Cargo.toml" with the crate type "cdylib",
+    /// the dependency "pchain-sdk" and a release profile that optimizes for size, and "src/lib.rs" that is ready to build.
+    /// The name of the folder is used as the package name.
+    #[clap(arg_required_else_help = true, display_order = 10, verbatim_doc_comment)]
+    New {
+        /// Absolute/Relative path to the new folder.
+        #[clap(display_order = 1, verbatim_doc_comment)]
+        path: PathBuf,
+
+        /// Template of "src/lib.rs". Possible values:
+        /// - minimal: a method that writes a log and returns a value. (Default)
+        /// - storage: a storage field, and methods to read and update it.
+        /// - cross-contract-call: a method that calls a method of another contract.
+        #[clap(long = "template", display_order = 2, default_value = "minimal", verbatim_doc_comment)]
+        template: Template,
+    },
+
+    /// Create a contract project in an existing folder. It is the same as "new", but fails if the folder already
+    /// contains "Cargo.toml" or "src/lib.rs".
+    #[clap(display_order = 11, verbatim_doc_comment)]
+    Init {
+        /// Absolute/Relative path to the folder. (Default: the current folder)
+        #[clap(display_order = 1, default_value = ".", verbatim_doc_comment)]
+        path: PathBuf,
+
+        /// Package name. (Default: the name of the folder)
+        #[clap(long = "name", display_order = 2, verbatim_doc_comment)]
+        name: Option<String>,
+
+        /// Template of "src/lib.rs". See "new --help" for the possible values.
+        #[clap(long = "template", display_order = 3, default_value = "minimal", verbatim_doc_comment)]
+        template: Template,
+    },
+
+    /// Manage the pchain_compile docker image.
+    #[clap(arg_required_else_help = true, display_order = 12, verbatim_doc_comment)]
+    Image {
+        #[clap(subcommand)]
+        command: ImageCommand,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum CacheCommand {
+    /// Show the cache folder, the number of saved builds and their total size.
+    #[clap(display_order = 1, verbatim_doc_comment)]
+    Info,
+
+    /// Remove saved builds that are not recently used.
+    #[clap(arg_required_else_help = true, display_order = 2, verbatim_doc_comment)]
+    Prune {
+        /// Remove the builds that are not used in this number of days.
+        #[clap(long = "max-age-days", display_order = 1, verbatim_doc_comment)]
+        max_age_days: Option<u64>,
+
+        /// Remove the least recently used builds until the cache is not larger than this size (in bytes).
+        #[clap(long = "max-size", display_order = 2, verbatim_doc_comment)]
+        max_size: Option<u64>,
+    },
+
+    /// Remove all saved builds.
+    #[clap(display_order = 3, verbatim_doc_comment)]
+    Clear,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum ImageCommand {
+    /// Build the docker image locally from the Dockerfile embedded in pchain_compile, instead of pulling
+    /// it from Dockerhub. The components are installed in the versions of the tag. The image is named
+    /// "localhost/pchain_compile_local:<tag>", and building contracts uses it with "--docker-local-image".
+    #[clap(display_order = 1, verbatim_doc_comment)]
+    Build {
+        /// Tag of the docker image. (Default: the tag determined by the version of pchain_compile)
+        #[clap(long = "tag", display_order = 1, verbatim_doc_comment)]
+        tag: Option<String>,
+
+        /// Address of the docker daemon. See "pchain_compile build --help".
+        #[clap(long = "docker-host", display_order = 2, verbatim_doc_comment)]
+        docker_host: Option<String>,
+
+        /// Folder of the TLS certificates to connect to the docker daemon. See "pchain_compile build --help".
+        #[clap(long = "docker-tls-cert-path", display_order = 3, verbatim_doc_comment)]
+        docker_tls_cert_path: Option<PathBuf>,
+
+        /// Version of the Docker Engine API. See "pchain_compile build --help".
+        #[clap(long = "docker-api-version", display_order = 4, verbatim_doc_comment)]
+        docker_api_version: Option<String>,
+
+        /// Timeout (in seconds) of the requests to the docker daemon. (Default: 120)
+        #[clap(long = "docker-timeout", display_order = 5, verbatim_doc_comment)]
+        docker_timeout: Option<u64>,
     },
 }
 
@@ -85,46 +517,127 @@ async fn main() {
             source_path,
             destination_path,
             locked,
+            frozen,
+            features,
+            embed_metadata,
+            embed_abi,
+            float_policy,
+            size_report,
+            max_wasm_size,
+            size_baseline,
+            max_size_growth,
+            no_cache,
+            watch,
+            output_layout,
+            versioned_name,
+            jobs,
             dockerless,
             docker_image_tag,
+            sandbox,
+            sandbox_toolchain,
+            docker_host,
+            docker_tls_cert_path,
+            docker_api_version,
+            docker_timeout,
+            docker_cli,
+            docker_local_image,
+            config_path,
         } => {
-            if source_path.is_empty() {
+            // Settings from the command line override the project configuration file
+            let file_config = match config_path {
+                Some(config_path) => ProjectConfig::from_file(&config_path).map(Some),
+                None => ProjectConfig::find(Path::new(".")),
+            };
+            let file_config = match file_config {
+                Ok(file_config) => file_config.unwrap_or_default(),
+                Err(error) => {
+                    println!("{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+            let project_config = file_config.override_with(ProjectConfig {
+                contracts: (!source_path.is_empty()).then_some(source_path),
+                destination: destination_path,
+                locked,
+                frozen,
+                features: (!features.is_empty()).then_some(features),
+                embed_metadata,
+                embed_abi,
+                float_policy,
+                size_report,
+                max_wasm_size,
+                size_baseline,
+                max_size_growth,
+                no_cache,
+                output_layout,
+                versioned_name,
+                jobs: jobs.map(|jobs| jobs as usize),
+                dockerless,
+                docker_tag: docker_image_tag,
+                sandbox,
+                sandbox_toolchain,
+                docker_host,
+                docker_tls_cert_path,
+                docker_api_version,
+                docker_timeout,
+                docker_cli,
+                docker_local_image,
+            });
+
+            let configs: Vec<_> = match project_config.configs() {
+                Ok(configs) => configs
+                    .into_iter()
+                    .map(|mut config| {
+                        config.build_options.on_warning = Some(print_warning());
+                        config
+                    })
+                    .collect(),
+                Err(error) => {
+                    println!("{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+            if configs.is_empty() {
                 println!("Please provide at least one source!");
                 std::process::exit(-1);
             }
+            let destination_path = project_config.destination.clone();
+            let size_report = project_config.size_report.unwrap_or_default();
+            let jobs = project_config.jobs.unwrap_or_else(pchain_compile::batch::default_jobs);
             println!("Build process started. This could take several minutes for large contracts.");
+            let cancellation_token = cancel_on_ctrl_c();
 
-            let build_options = BuildOptions {
-                locked
-            };
-            
-            let docker_option = if dockerless {
-                DockerOption::Dockerless
-            } else {
-                DockerOption::Docker(DockerConfig {
-                    tag: docker_image_tag,
+            if watch {
+                let dst_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+                let result = pchain_compile::watch(configs, jobs, pchain_compile::watch::DEFAULT_DEBOUNCE, cancellation_token, |config, result| {
+                    match result {
+                        Ok(contract) => {
+                            let size = std::fs::metadata(dst_path.join(contract)).map(|m| m.len()).unwrap_or_default();
+                            println!("Built {contract} ({size} bytes).");
+                        }
+                        Err(error) => println!(
+                            "Failed to build {}:\n{}",
+                            config.source_path.display(),
+                            pchain_compile::watch::diagnostic_summary(error)
+                        ),
+                    }
+                    std::ops::ControlFlow::Continue(())
                 })
-            };
-
-            // Spawn threads to handle each contract code
-            let mut join_handles = vec![];
-            source_path.into_iter().for_each(|source_path| {
-                let config = Config {
-                    source_path,
-                    destination_path: destination_path.clone(),
-                    build_options: build_options.clone(),
-                    docker_option: docker_option.clone(),
-                };
-
-                join_handles.push(tokio::spawn(config.run()));
-            });
-
-            // Join threads to obtain results
-            let mut results = vec![];
-            for handle in join_handles {
-                results.push(handle.await.unwrap());
+                .await;
+                if let Err(error) = result {
+                    println!("{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+                return;
             }
 
+            // Build the contracts in parallel, and report each result as the build finishes
+            let results = pchain_compile::build_all(configs, jobs, cancellation_token.clone(), |config, result| match result {
+                Ok(contract) => println!("Built {contract}."),
+                Err(error) => println!("Failed to build {}: {error}", config.source_path.display()),
+            })
+            .await;
+
             // Display the results
             let (success, fails): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
 
@@ -133,16 +646,368 @@ async fn main() {
                     .clone()
                     .unwrap_or(Path::new(".").to_path_buf());
                 let contracts: Vec<String> = success.into_iter().map(|r| r.ok().unwrap()).collect();
-                println!("Finished compiling. ParallelChain Mainnet smart contract(s) {:?} are saved at ({})", contracts,  dunce::canonicalize(dst_path).unwrap().to_str().unwrap());
+                println!("Finished compiling. ParallelChain Mainnet smart contract(s) {:?} are saved at ({})", contracts,  dunce::canonicalize(&dst_path).unwrap().to_str().unwrap());
+
+                if size_report {
+                    for contract in &contracts {
+                        let report_path = dst_path.join(pchain_compile::size::size_report_file(contract));
+                        match pchain_compile::size::read_size_report(&report_path) {
+                            Ok(report) => println!("\nSize report of {contract}:\n{report}"),
+                            Err(error) => println!("{}\n{}\n", error, error.detail()),
+                        }
+                    }
+                }
             }
 
-            if !fails.is_empty() {
+            let failed = !fails.is_empty();
+            if failed {
                 println!("Compiling fails.");
                 fails.into_iter().for_each(|e| {
                     let error = e.err().unwrap();
                     println!("{}\n{}\n", error, error.detail());
                 });
             }
+
+            if cancellation_token.is_cancelled() {
+                std::process::exit(130);
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        PchainCompile::Verify {
+            source_path,
+            wasm_path,
+            docker_image_tag,
+            features,
+            docker_host,
+            docker_tls_cert_path,
+            docker_api_version,
+            docker_timeout,
+            docker_cli,
+        } => {
+            println!("Verification started. This could take several minutes for large contracts.");
+
+            let report = match pchain_compile::verify_contract(
+                source_path,
+                wasm_path,
+                features,
+                DockerConfig {
+                    tag: docker_image_tag,
+                    host: docker_host,
+                    tls_cert_path: docker_tls_cert_path,
+                    api_version: docker_api_version,
+                    timeout: docker_timeout,
+                    cli: docker_cli,
+                    local_image: false,
+                },
+            )
+            .await
+            {
+                Ok(report) => report,
+                Err(error) => {
+                    println!("Verification fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+
+            if let Some(metadata) = &report.metadata {
+                println!(
+                    "The given binary was built by pchain_compile {} with docker image tag {}.",
+                    metadata.pchain_compile_version,
+                    metadata.docker_image_tag.as_deref().unwrap_or("(none)")
+                );
+            }
+            println!("SHA-256 of the given binary:   {}", report.expected_hash);
+            println!("SHA-256 of the rebuilt binary: {}", report.actual_hash);
+            if report.is_match() {
+                println!("Verified. The binary is reproducibly built from the source code.");
+                return;
+            }
+
+            println!("Mismatch. The binary is different from the one rebuilt from the source code.\n");
+            println!("{:<24}{:>16}{:>16}  Status", "Section", "Given (bytes)", "Rebuilt (bytes)");
+            let size_or_dash = |size: Option<usize>| size.map_or("-".to_string(), |size| size.to_string());
+            for diff in report.section_diffs {
+                let status = match (diff.expected_size, diff.actual_size) {
+                    (Some(_), None) => "missing in rebuilt",
+                    (None, Some(_)) => "missing in given",
+                    _ if diff.identical => "identical",
+                    _ => "different",
+                };
+                println!(
+                    "{:<24}{:>16}{:>16}  {}",
+                    diff.name,
+                    size_or_dash(diff.expected_size),
+                    size_or_dash(diff.actual_size),
+                    status
+                );
+            }
+            std::process::exit(1);
+        }
+        PchainCompile::Inspect { wasm_path, json } => {
+            let inspection = match pchain_compile::inspect_wasm(&wasm_path) {
+                Ok(inspection) => inspection,
+                Err(error) => {
+                    println!("Inspection fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&inspection).unwrap());
+            } else {
+                print!("{inspection}");
+            }
+        }
+        PchainCompile::Validate { wasm_path } => {
+            match pchain_compile::validate_wasm(&wasm_path) {
+                Ok(violations) if violations.is_empty() => {
+                    println!("Validation passed. The contract satisfies the rules of ParallelChain runtime.");
+                }
+                Ok(violations) => {
+                    let error = pchain_compile::error::Error::ContractValidationFailure(violations);
+                    println!("Validation fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+                Err(error) => {
+                    println!("Validation fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            }
+        }
+        PchainCompile::Size { wasm_path, json } => {
+            let report = match pchain_compile::size_report(&wasm_path) {
+                Ok(report) => report,
+                Err(error) => {
+                    println!("Size profiling fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print!("{report}");
+            }
+        }
+        PchainCompile::Compat { old_path, new_path, json } => {
+            let report = match pchain_compile::check_compatibility(&old_path, &new_path) {
+                Ok(report) => report,
+                Err(error) => {
+                    println!("Compatibility check fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else if report.changes.is_empty() {
+                println!("No changes in methods or storage layout.");
+            } else {
+                report.changes.iter().for_each(|change| println!("{change}"));
+            }
+
+            if !report.is_compatible() {
+                std::process::exit(1);
+            }
+        }
+        PchainCompile::Optimize {
+            input_path,
+            output_path,
+            float_policy,
+            size_report,
+            max_wasm_size,
+            size_baseline,
+            max_size_growth,
+        } => {
+            let options = BuildOptions {
+                float_policy,
+                size_report,
+                max_wasm_size,
+                size_baseline: size_baseline.map(|wasm_path| SizeBaseline {
+                    wasm_path,
+                    max_growth_percent: max_size_growth,
+                }),
+                on_warning: Some(print_warning()),
+                ..Default::default()
+            };
+
+            if let Err(error) = pchain_compile::optimize_wasm(&input_path, &output_path, &options) {
+                println!("Optimization fails.\n{}\n{}\n", error, error.detail());
+                std::process::exit(1);
+            }
+            println!("Finished optimizing. Saved {} to {}", input_path.display(), output_path.display());
+
+            if size_report {
+                let wasm_file = output_path.file_name().unwrap().to_string_lossy();
+                let report_path = output_path.with_file_name(pchain_compile::size::size_report_file(&wasm_file));
+                match pchain_compile::size::read_size_report(&report_path) {
+                    Ok(report) => println!("\nSize report of {wasm_file}:\n{report}"),
+                    Err(error) => println!("{}\n{}\n", error, error.detail()),
+                }
+            }
+        }
+        PchainCompile::Run {
+            wasm_path,
+            method,
+            args,
+            abi_path,
+            calling_account,
+            amount,
+            storage_path,
+            json,
+        } => {
+            let result = run_contract(wasm_path, method, args, abi_path, calling_account, amount, storage_path.as_deref());
+            let result = match result {
+                Ok(result) => result,
+                Err(error) => {
+                    println!("Running contract fails.\n{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            };
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&result).unwrap());
+            } else {
+                print!("{result}");
+            }
+        }
+        PchainCompile::Cache { command } => {
+            let result = match command {
+                CacheCommand::Info => pchain_compile::cache::cache_entries().map(|entries| {
+                    println!("Cache folder: {}", pchain_compile::cache::cache_dir().display());
+                    println!(
+                        "{} build(s), {} bytes",
+                        entries.len(),
+                        entries.iter().map(|entry| entry.size).sum::<u64>()
+                    );
+                }),
+                CacheCommand::Prune { max_age_days, max_size } => pchain_compile::cache::prune_cache(
+                    max_age_days.map(|days| std::time::Duration::from_secs(days * 24 * 60 * 60)),
+                    max_size,
+                )
+                .map(|summary| println!("Removed {} build(s), freed {} bytes.", summary.removed, summary.freed)),
+                CacheCommand::Clear => pchain_compile::cache::clear_cache()
+                    .map(|summary| println!("Removed {} build(s), freed {} bytes.", summary.removed, summary.freed)),
+            };
+            if let Err(error) = result {
+                println!("{}\n{}\n", error, error.detail());
+                std::process::exit(1);
+            }
+        }
+        PchainCompile::Image { command } => match command {
+            ImageCommand::Build {
+                tag,
+                docker_host,
+                docker_tls_cert_path,
+                docker_api_version,
+                docker_timeout,
+            } => {
+                println!("Building docker image. This could take several minutes.");
+                let docker_config = DockerConfig {
+                    tag,
+                    host: docker_host,
+                    tls_cert_path: docker_tls_cert_path,
+                    api_version: docker_api_version,
+                    timeout: docker_timeout,
+                    cli: None,
+                    local_image: false,
+                };
+                match pchain_compile::image::build_image(docker_config, |log| print!("{log}")).await {
+                    Ok(image) => println!("Built docker image {image}."),
+                    Err(error) => {
+                        println!("Building docker image fails.\n{}\n{}\n", error, error.detail());
+                        std::process::exit(1);
+                    }
+                }
+            }
+        },
+        PchainCompile::New { path, template } => {
+            match pchain_compile::new_contract(&path, template) {
+                Ok(name) => println!("Created contract \"{name}\" at {}. To build it, run:\n\npchain_compile build --source {}", path.display(), path.display()),
+                Err(error) => {
+                    println!("{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            }
+        }
+        PchainCompile::Init { path, name, template } => {
+            match pchain_compile::init_contract(&path, name.as_deref(), template) {
+                Ok(name) => println!("Created contract \"{name}\" at {}. To build it, run:\n\npchain_compile build --source {}", path.display(), path.display()),
+                Err(error) => {
+                    println!("{}\n{}\n", error, error.detail());
+                    std::process::exit(1);
+                }
+            }
         }
     };
 }
+
+/// Prepares the call from the command arguments, runs the contract and saves the storage if `storage_path` is given.
+fn run_contract(
+    wasm_path: PathBuf,
+    method: String,
+    args: Vec<String>,
+    abi_path: Option<PathBuf>,
+    calling_account: Option<String>,
+    amount: u64,
+    storage_path: Option<&Path>,
+) -> Result<pchain_compile::run::RunResult, Error> {
+    let abi = match abi_path {
+        Some(abi_path) => {
+            let content = std::fs::read(&abi_path)
+                .map_err(|e| Error::ContractRunFailure(format!("Fail to read {}: {e}", abi_path.display())))?;
+            Some(serde_json::from_slice(&content).map_err(|e| {
+                Error::ContractRunFailure(format!("Malformed ABI file {}: {e}", abi_path.display()))
+            })?)
+        }
+        None => None,
+    };
+    let calling_account = match calling_account {
+        Some(address) => pchain_compile::run::encode_argument("[u8;32]", &address)
+            .map_err(|e| Error::ContractRunFailure(format!("Invalid calling account: {e}")))?
+            .try_into()
+            .unwrap(),
+        None => [0; 32],
+    };
+    let storage = match storage_path {
+        Some(storage_path) => pchain_compile::run::load_storage(storage_path)?,
+        None => Default::default(),
+    };
+
+    let call = ContractCall {
+        method,
+        args,
+        abi,
+        calling_account,
+        amount,
+        storage,
+    };
+    let result = pchain_compile::run_contract(&wasm_path, &call)?;
+    if let Some(storage_path) = storage_path {
+        pchain_compile::run::save_storage(storage_path, &result.storage)?;
+    }
+    Ok(result)
+}
+
+/// Returns a token that is cancelled when Ctrl-C is pressed, so that the running builds stop and clean up their docker
+/// containers and temporary folders. Pressing Ctrl-C again exits immediately.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancellation_token = CancellationToken::new();
+    let token = cancellation_token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("Cancelling the building process. Press Ctrl-C again to exit immediately.");
+            token.cancel();
+            let _ = tokio::signal::ctrl_c().await;
+            std::process::exit(130);
+        }
+    });
+    cancellation_token
+}
+
+/// Returns the handler that prints the warnings found in building.
+fn print_warning() -> WarningHandler {
+    std::sync::Arc::new(|warning| eprintln!("Warning: {warning}"))
+}
@@ -14,6 +14,15 @@
 //!    that are using relative paths in dependencies.
 //! 3. Compile the source code in the docker container. The dependencies (if any) are compile first.
 //! 4. After compilation, copy the binary (wasm) from docker container to target destination.
+//! 5. Validate the binary against the rules of ParallelChain runtime (see [crate::validate]).
+//! 6. Generate the ABI of the contract from the source code (see [crate::abi]).
+//!
+//! Before step 2, the build is looked up in the local build cache (see [crate::cache]). If it is cached, the cached
+//! files are copied to the destination instead. A successful build is saved to the cache.
+//!
+//! The output files are saved in the destination folder, or in a folder for each contract in it (see [crate::OutputLayout]).
+//!
+//! Steps 1 to 4 are done by [crate::backend::DockerBackend]. The other steps are the same for all build backends (see [crate::backend]).
 //!
 //! ## Compilation without using Docker
 //!
@@ -24,14 +33,18 @@
 //! **Please note the compiled contracts are not always consistent with the previous compiled ones, because the building process happens in
 //! your local changing environment.**
 
-use bollard::Docker;
-use std::path::Path;
-use std::{collections::HashSet, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use std::fs;
 
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::{BuildBackend, BuildOutput, BuildRequest, DockerBackend};
 use crate::error::Error;
-use crate::{DockerConfig, BuildOptions};
+use crate::{DockerConfig, BuildOptions, BuildWarning, FloatPolicy, OutputLayout};
+
+/// File name of the build log saved in the output folder of a contract with [OutputLayout::PerContract].
+pub const BUILD_LOG_FILE: &str = "build.log";
 
 /// `build_target` takes the path to the cargo manifest file(s), generates an optimized WASM binary(ies) after building
 /// the source code and saves the binary(ies) to the designated destination_path.
@@ -43,15 +56,23 @@ pub async fn build_target(
     source_path: PathBuf,
     destination_path: Option<PathBuf>,
 ) -> Result<String, Error> {
-    build_target_with_docker(source_path, destination_path, BuildOptions::default(), DockerConfig::default()).await
+    build_target_with_backend(
+        &DockerBackend::new(DockerConfig::default())?,
+        source_path,
+        destination_path,
+        BuildOptions::default(),
+        &CancellationToken::new(),
+    )
+    .await
 }
 
-/// Validates inputs and trigger building process that uses docker.
-pub(crate) async fn build_target_with_docker(
+/// Validates inputs and trigger building process that compiles the contract with `backend`.
+pub(crate) async fn build_target_with_backend<B: BuildBackend>(
+    backend: &B,
     source_path: PathBuf,
     destination_path: Option<PathBuf>,
     options: BuildOptions,
-    docker_config: DockerConfig,
+    cancellation_token: &CancellationToken,
 ) -> Result<String, Error> {
     // create destination directory if it does not exist.
     if let Some(dst_path) = &destination_path {
@@ -61,42 +82,85 @@ pub(crate) async fn build_target_with_docker(
     // check validity of source path (and convert relative path to absolute path if applicable)
     let source_path = validated_source_path(source_path)?;
 
-    // check if the manifest file exists on the path supplied.
-    let package_name =
-        crate::manifests::package_name(&source_path).map_err(|_| Error::InvalidSourcePath)?;
-    let wasm_file = format!("{package_name}.wasm").replace('-', "_");
-
-    // check if docker image tag is valid
-    let docker_image_tag = docker_config
-        .tag
-        .unwrap_or(crate::docker::PCHAIN_COMPILE_IMAGE_TAGS[0].to_string());
-    if !crate::docker::PCHAIN_COMPILE_IMAGE_TAGS.contains(&docker_image_tag.as_str()) {
-        return Err(Error::UnkownDockerImageTag(docker_image_tag));
+    // check if the Cargo.lock exists when it is strictly required.
+    if options.frozen && !source_path.join("Cargo.lock").exists() {
+        return Err(Error::LockfileNotFound);
     }
 
-    build_target_in_docker(source_path, destination_path, options, docker_image_tag, wasm_file).await
-}
+    // check if the manifest file exists on the path supplied, and its library target can be built into a contract.
+    let cargo_wasm_file = crate::manifests::wasm_file(&source_path).map_err(|e| match e {
+        Error::ManifestFailure => Error::InvalidSourcePath,
+        e => e,
+    })?;
 
-/// Validates inputs and trigger building process that does not use docker.
-pub(crate) async fn build_target_without_docker(
-    source_path: PathBuf,
-    destination_path: Option<PathBuf>,
-    options: BuildOptions,
-) -> Result<String, Error> {
-    // create destination directory if it does not exist.
-    if let Some(dst_path) = &destination_path {
-        fs::create_dir_all(dst_path).map_err(|_| Error::InvalidDestinationPath)?;
+    // locate the output files according to the output layout.
+    let (destination_path, wasm_file, artifact) =
+        output_location(&source_path, destination_path, &cargo_wasm_file, &options)?;
+
+    // return the cached build if it exists.
+    let cache_key = match options.no_cache {
+        false => backend
+            .toolchain(&source_path)
+            .await
+            .and_then(|toolchain| crate::cache::cache_key(&source_path, &options, &toolchain)),
+        true => None,
+    };
+    if let Some(cache_key) = &cache_key {
+        if restore_from_cache(cache_key, destination_path.clone(), &wasm_file, &options)? {
+            return Ok(artifact);
+        }
     }
 
-    // check validity of source path (and convert relative path to absolute path if applicable)
-    let source_path = validated_source_path(source_path)?;
+    let wasm_file = build_target_by_backend(
+        backend,
+        source_path.clone(),
+        destination_path.clone(),
+        options.clone(),
+        cargo_wasm_file,
+        wasm_file,
+        cancellation_token,
+    )
+    .await?;
 
-    // check if the manifest file exists on the path supplied.
-    let package_name =
-        crate::manifests::package_name(&source_path).map_err(|_| Error::InvalidSourcePath)?;
-    let wasm_file = format!("{package_name}.wasm").replace('-', "_");
+    // save the build to cache. The toolchain can be available only after building, e.g. the digest of the docker
+    // image is available after the image is pulled.
+    let cache_key = match (cache_key, options.no_cache) {
+        (Some(cache_key), _) => Some(cache_key),
+        (None, false) => backend
+            .toolchain(&source_path)
+            .await
+            .and_then(|toolchain| crate::cache::cache_key(&source_path, &options, &toolchain)),
+        (None, true) => None,
+    };
+    if let Some(cache_key) = cache_key {
+        save_to_cache(&cache_key, destination_path, &wasm_file, &options);
+    }
 
-    build_target_by_cargo(source_path, destination_path, options, wasm_file).await
+    Ok(artifact)
+}
+
+/// Returns the folder to save the output files of the contract, the file name of the compiled contract, and the path
+/// to the compiled contract relative to `destination_path`. The folder is created for [OutputLayout::PerContract].
+fn output_location(
+    source_path: &Path,
+    destination_path: Option<PathBuf>,
+    cargo_wasm_file: &str,
+    options: &BuildOptions,
+) -> Result<(Option<PathBuf>, String, String), Error> {
+    let contract = cargo_wasm_file.trim_end_matches(".wasm");
+    let wasm_file = match options.versioned_name {
+        true => format!("{contract}-{}.wasm", crate::manifests::package_version(source_path)?),
+        false => cargo_wasm_file.to_string(),
+    };
+    match options.output_layout {
+        OutputLayout::Flat => Ok((destination_path, wasm_file.clone(), wasm_file)),
+        OutputLayout::PerContract => {
+            let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf()).join(contract);
+            fs::create_dir_all(&output_path).map_err(|_| Error::InvalidDestinationPath)?;
+            let artifact = format!("{contract}/{wasm_file}");
+            Ok((Some(output_path), wasm_file, artifact))
+        }
+    }
 }
 
 fn validated_source_path(source_path: PathBuf) -> Result<PathBuf, Error> {
@@ -106,106 +170,189 @@ fn validated_source_path(source_path: PathBuf) -> Result<PathBuf, Error> {
     Ok(Path::new(&src_absolute_str).to_path_buf())
 }
 
-/// Setup docker environment and build contract in docker container. It manages to pull docker image, start and remove containers.
-async fn build_target_in_docker(
+/// Copies the cached build to the destination folder, and runs the checks on it as in building. It returns
+/// false if the build is not cached.
+fn restore_from_cache(
+    cache_key: &str,
+    destination_path: Option<PathBuf>,
+    wasm_file: &str,
+    options: &BuildOptions,
+) -> Result<bool, Error> {
+    let output_path = destination_path.clone().unwrap_or(Path::new(".").to_path_buf());
+    if !crate::cache::restore(cache_key, &output_path, wasm_file) {
+        return Ok(false);
+    }
+    validate_contract(destination_path.clone(), wasm_file, options)?;
+    check_size_budget(destination_path, wasm_file, options)?;
+    Ok(true)
+}
+
+/// Saves the build in the destination folder to cache.
+fn save_to_cache(cache_key: &str, destination_path: Option<PathBuf>, wasm_file: &str, options: &BuildOptions) {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    crate::cache::store(cache_key, &output_path, wasm_file, options.size_report);
+}
+
+/// Compiles the contract with `backend` in a temporary working folder, and then validates the compiled contract, saves
+/// the size report, checks the size budget, and saves the ABI and the build metadata. The working folder is removed no
+/// matter if build is successful.
+async fn build_target_by_backend<B: BuildBackend>(
+    backend: &B,
     source_path: PathBuf,
     destination_path: Option<PathBuf>,
     options: BuildOptions,
-    docker_image_tag: String,
+    cargo_wasm_file: String,
     wasm_file: String,
+    cancellation_token: &CancellationToken,
 ) -> Result<String, Error> {
-    // Retrieve dependency paths from manifest.
-    let mut dependencies = HashSet::new();
-    crate::manifests::get_dependency_paths(&source_path, &mut dependencies)?;
-
-    // Create container from Parallelchain Lab docker image
-    let container_name = crate::docker::random_container_name();
-    let docker = Docker::connect_with_local_defaults().map_err(|_| Error::DockerDaemonFailure)?;
-    let image_name = crate::docker::pull_image(&docker, &docker_image_tag).await?;
-    crate::docker::start_container(&docker, &container_name, image_name).await?;
-
-    // Compile Contract in docker container
-    let result = compile_contract_in_docker_container(
-        &docker,
-        &container_name,
-        dependencies,
-        source_path,
-        destination_path,
-        options,
-        &wasm_file,
-    )
-    .await;
+    // 1. Create temporary folder as a working directory for the backend. The backend saves its output files in a
+    // folder inside it, because the destination folder can be shared by other builds running in parallel.
+    let working_path = crate::cargo::random_temp_dir_name();
+    let staging_path = working_path.join("output");
+    std::fs::create_dir_all(staging_path.as_path()).map_err(|_| Error::CreateTempDir)?;
 
-    // Remove container no matter if build is successful
-    let _ = crate::docker::remove_container(&docker, &container_name).await;
+    // 2. Compile the source code by the backend
+    let request = BuildRequest {
+        source_path: source_path.clone(),
+        options: options.clone(),
+        cargo_wasm_file,
+        output_path: staging_path.clone(),
+        wasm_file: wasm_file.clone(),
+        working_path: working_path.clone(),
+    };
+    let result = backend.compile(&request, cancellation_token).await;
 
-    result.map(|_| wasm_file)
-}
+    // 3. Copy the output files to the destination folder, validate the contract, save size report from the
+    // intermediate binaries and check the size budget
+    let result = result.and_then(|output| {
+        let cargo_lock = std::fs::read(staging_path.join("Cargo.lock")).ok();
+        copy_output_files(&staging_path, destination_path.clone())?;
+        validate_contract(destination_path.clone(), &wasm_file, &options)?;
+        if options.size_report {
+            save_size_report(&working_path, destination_path.clone(), &wasm_file)?;
+        }
+        check_size_budget(destination_path.clone(), &wasm_file, &options)?;
+        Ok((output, cargo_lock))
+    });
 
-/// Inner process in method [build_target_in_docker] to compile contract in docker container. It does not remove docker container after use.
-async fn compile_contract_in_docker_container(
-    docker: &Docker,
-    container_name: &str,
-    dependencies: impl IntoIterator<Item = String>,
-    source_path: PathBuf,
-    destination_path: Option<PathBuf>,
-    options: BuildOptions,
-    wasm_file: &str,
-) -> Result<(), Error> {
-    // Step 1. create dependency directory and copy source to docker
-    for dependency in dependencies {
-        crate::docker::copy_files(docker, container_name, &dependency).await?;
+    // 4. Remove temporary files after building
+    let _ = std::fs::remove_dir_all(working_path);
+
+    let (output, cargo_lock) = result?;
+
+    save_abi(&source_path, destination_path.clone(), &wasm_file, &options)?;
+
+    if request.needs_environment() {
+        save_build_metadata(
+            &source_path,
+            destination_path,
+            &wasm_file,
+            &options,
+            cargo_lock.as_deref(),
+            output,
+        )?;
     }
 
-    // Step 2: create directory paths inside docker and  copy file to container
-    crate::docker::copy_files(docker, container_name, source_path.to_str().unwrap()).await?;
+    Ok(wasm_file)
+}
 
-    // Step 3: build the source code inside docker
-    let (result_in_docker, build_log) = crate::docker::build_contracts(
-        docker,
-        container_name,
-        source_path,
-        options.locked,
-        wasm_file,
-    )
-    .await?;
+/// Copies the output files saved by the backend in `staging_path` to the destination folder.
+fn copy_output_files(staging_path: &Path, destination_path: Option<PathBuf>) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    let entries = fs::read_dir(staging_path).map_err(|e| Error::BuildFailure(format!("Fail to read the output files: {e}")))?;
+    for entry in entries {
+        let path = entry.map_err(|e| Error::BuildFailure(format!("Fail to read the output files: {e}")))?.path();
+        if let (true, Some(file_name)) = (path.is_file(), path.file_name()) {
+            fs::copy(&path, output_path.join(file_name)).map_err(|_| Error::InvalidDestinationPath)?;
+        }
+    }
+    Ok(())
+}
 
-    // Step 4: copy file from docker to given location
-    crate::docker::copy_files_from(
-        docker,
-        container_name,
-        &result_in_docker,
-        destination_path.clone(),
-        build_log
-    )
-    .await?;
+/// Validates the compiled contract saved in the destination folder against the rules of ParallelChain runtime,
+/// and handles the floating-point instructions in it according to the `float_policy`. The floating-point instructions
+/// are reported as [BuildWarning::FloatInstructions] with [FloatPolicy::Warn].
+pub(crate) fn validate_contract(destination_path: Option<PathBuf>, wasm_file: &str, options: &BuildOptions) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    let module = crate::validate::parse_module(&output_path.join(wasm_file))?;
+
+    let violations = crate::validate::validate_module(&module);
+    if !violations.is_empty() {
+        return Err(Error::ContractValidationFailure(violations));
+    }
 
+    if options.float_policy == FloatPolicy::Allow {
+        return Ok(());
+    }
+    let usages = crate::validate::float_usages(&module);
+    if usages.is_empty() {
+        return Ok(());
+    }
+    if options.float_policy == FloatPolicy::Error {
+        return Err(Error::FloatInstructionsFound(usages));
+    }
+    options.warn(BuildWarning::FloatInstructions {
+        wasm_file: wasm_file.to_string(),
+        usages,
+    });
     Ok(())
 }
 
-/// Setup filesystem and build contract by cargo. It manages to create a temporary workding folder and 
-/// remove it after call.
-async fn build_target_by_cargo(
-    source_path: PathBuf,
-    destination_path: Option<PathBuf>,
-    options: BuildOptions,
-    wasm_file: String,
-) -> Result<String, Error> {
-    // 1. Create temporary folder as a working directory for cargo build
-    let temp_dir = crate::cargo::random_temp_dir_name();
-    std::fs::create_dir_all(temp_dir.as_path()).map_err(|_| Error::CreateTempDir)?;
-
-    // 2. Build the source code locally by cargo build
-    let result = crate::cargo::build_contract(
-        &temp_dir,
-        source_path.as_path(),
-        destination_path,
-        options.locked,
-        &wasm_file,
-    );
+/// Saves the size report of the compiled contract in the destination folder.
+pub(crate) fn save_size_report(intermediates_path: &Path, destination_path: Option<PathBuf>, wasm_file: &str) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    crate::size::save_build_size_report(intermediates_path, &output_path, wasm_file)
+}
+
+/// Checks the size of the compiled contract saved in the destination folder against the size budget.
+pub(crate) fn check_size_budget(destination_path: Option<PathBuf>, wasm_file: &str, options: &BuildOptions) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    crate::size::check_size_budget(&output_path.join(wasm_file), wasm_file, options)
+}
 
-    // 3. Remove temporary files after building
-    let _ = std::fs::remove_dir_all(temp_dir);
+/// Saves the ABI of the contract in the destination folder, and embeds it into the compiled contract if `embed_abi` is
+/// set. If the ABI cannot be generated, it is reported as [BuildWarning::AbiNotGenerated], unless `embed_abi` is set.
+fn save_abi(source_path: &Path, destination_path: Option<PathBuf>, wasm_file: &str, options: &BuildOptions) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    match crate::abi::save_abi(source_path, &output_path, wasm_file, options.embed_abi) {
+        Err(Error::AbiGenerationFailure(reason)) if !options.embed_abi => {
+            options.warn(BuildWarning::AbiNotGenerated {
+                wasm_file: wasm_file.to_string(),
+                reason,
+            });
+            Ok(())
+        }
+        result => result,
+    }
+}
 
-    result.map(|_| wasm_file)
+/// Embeds the build metadata into the compiled contract saved in the destination folder if `embed_metadata` is set.
+/// For [OutputLayout::PerContract], it also saves the build metadata and the build log in the destination folder.
+/// `cargo_lock` is the content of the Cargo.lock saved by the backend, read before it is copied to the destination
+/// folder, which may be overwritten by the other builds.
+fn save_build_metadata(
+    source_path: &Path,
+    destination_path: Option<PathBuf>,
+    wasm_file: &str,
+    options: &BuildOptions,
+    cargo_lock: Option<&[u8]>,
+    output: BuildOutput,
+) -> Result<(), Error> {
+    let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
+    let metadata = crate::metadata::BuildMetadata::new(
+        source_path,
+        cargo_lock,
+        options,
+        output.docker_image,
+        output.rustc_version,
+    )?;
+    if options.embed_metadata {
+        crate::metadata::embed_metadata(&output_path.join(wasm_file), &metadata)?;
+    }
+    if options.output_layout == OutputLayout::PerContract {
+        crate::metadata::save_metadata(&output_path, &metadata)?;
+        std::fs::write(output_path.join(BUILD_LOG_FILE), output.build_log)
+            .map_err(|e| Error::BuildFailure(format!("Fail to save build log: {e}")))?;
+    }
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,275 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the local build cache. A successful build is saved in the cache folder (see [cache_dir]) under
+//! a key computed from everything that determines its output:
+//! - the version of pchain_compile,
+//! - the toolchain, i.e. the digest of the docker image, or the output of `rustc --version` for builds without docker,
+//! - the source code directory and the path dependencies (see [crate::metadata::BuildMetadata::source_hash]),
+//! - the file Cargo.lock in the source code directory, if it exists,
+//! - the build options that change the output files.
+//!
+//! Building again with the same key copies the saved files to the destination instead of building. The checks that do
+//! not change the output (e.g. the size budget) are run again on the copied files.
+//!
+//! The cache is best effort. Failures to read or write the cache never fail a build.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{error::Error, BuildOptions};
+
+/// Environment variable that overrides the cache folder.
+pub const CACHE_DIR_ENV: &str = "PCHAIN_COMPILE_CACHE_DIR";
+
+/// File name of the information about a cache entry, in the folder of the entry.
+const ENTRY_FILE: &str = "entry.json";
+
+/// A saved build in the cache.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub key: String,
+    /// Folder of the saved files.
+    pub path: PathBuf,
+    /// File name of the compiled contract.
+    pub wasm_file: String,
+    /// Total size of the saved files in bytes.
+    pub size: u64,
+    /// Last time the entry was saved or used.
+    pub last_used: SystemTime,
+}
+
+/// Result of removing entries from the cache.
+#[derive(Debug, Clone, Default)]
+pub struct PruneSummary {
+    /// Number of removed entries.
+    pub removed: usize,
+    /// Total size of the removed entries in bytes.
+    pub freed: u64,
+}
+
+/// Content of the file `entry.json` in the folder of an entry.
+#[derive(Serialize, Deserialize)]
+struct EntryInfo {
+    wasm_file: String,
+    /// Seconds since the Unix epoch.
+    last_used: u64,
+}
+
+/// Returns the cache folder. It is `$PCHAIN_COMPILE_CACHE_DIR` if set, otherwise `pchain_compile` in the user's
+/// cache folder, i.e. `$XDG_CACHE_HOME` or `~/.cache` (`%LOCALAPPDATA%` on Windows).
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    let user_cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or(std::env::temp_dir());
+    user_cache_dir.join("pchain_compile")
+}
+
+/// Returns the entries in the cache, from the least recently used.
+pub fn cache_entries() -> Result<Vec<CacheEntry>, Error> {
+    let dir = cache_dir();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let map_err = |e: std::io::Error| Error::CacheFailure(format!("Fail to read {}: {e}", dir.display()));
+    let mut entries = vec![];
+    for entry in std::fs::read_dir(&dir).map_err(map_err)? {
+        let path = entry.map_err(map_err)?.path();
+        // Skip the temporary folders of entries being saved
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if let Some(entry) = read_entry(&path) {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|entry| entry.last_used);
+    Ok(entries)
+}
+
+/// Removes the entries that are not used within `max_age`, then removes the least recently used entries until the
+/// total size of the cache is not more than `max_size` bytes.
+pub fn prune_cache(max_age: Option<Duration>, max_size: Option<u64>) -> Result<PruneSummary, Error> {
+    let now = SystemTime::now();
+    let entries = cache_entries()?;
+    let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut summary = PruneSummary::default();
+    for entry in entries {
+        let expired = max_age.is_some_and(|max_age| {
+            now.duration_since(entry.last_used).unwrap_or_default() > max_age
+        });
+        let oversized = max_size.is_some_and(|max_size| total_size > max_size);
+        if !expired && !oversized {
+            continue;
+        }
+        std::fs::remove_dir_all(&entry.path)
+            .map_err(|e| Error::CacheFailure(format!("Fail to remove {}: {e}", entry.path.display())))?;
+        total_size -= entry.size;
+        summary.removed += 1;
+        summary.freed += entry.size;
+    }
+    Ok(summary)
+}
+
+/// Removes all entries in the cache.
+pub fn clear_cache() -> Result<PruneSummary, Error> {
+    prune_cache(None, Some(0))
+}
+
+/// Computes the cache key of a build. It returns None if the source code or its dependencies cannot be read.
+pub(crate) fn cache_key(source_path: &Path, options: &BuildOptions, toolchain: &str) -> Option<String> {
+    let mut hasher = Sha256::new();
+    let mut update = |label: &str, value: &str| {
+        hasher.update(label.as_bytes());
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    };
+
+    update("pchain_compile", env!("CARGO_PKG_VERSION"));
+    update("toolchain", toolchain);
+    update("source", &crate::metadata::source_tree_hash(source_path).ok()?);
+
+    let mut dependencies = HashSet::new();
+    crate::manifests::get_dependency_paths(source_path, &mut dependencies).ok()?;
+    let mut dependency_hashes = dependencies
+        .iter()
+        .map(|dependency| crate::metadata::source_tree_hash(Path::new(dependency)).ok())
+        .collect::<Option<Vec<_>>>()?;
+    dependency_hashes.sort();
+    for dependency_hash in dependency_hashes {
+        update("dependency", &dependency_hash);
+    }
+
+    let cargo_lock = std::fs::read(source_path.join("Cargo.lock"))
+        .map(|content| crate::wasm::sha256_hex(&content))
+        .unwrap_or_default();
+    update("Cargo.lock", &cargo_lock);
+
+    let mut features = options.features.clone();
+    features.sort();
+    features.dedup();
+    update(
+        "options",
+        &format!(
+            "locked={} frozen={} features={} embed_metadata={} embed_abi={} size_report={} output_layout={:?} versioned_name={}",
+            options.locked,
+            options.frozen,
+            features.join(","),
+            options.embed_metadata,
+            options.embed_abi,
+            options.size_report,
+            options.output_layout,
+            options.versioned_name
+        ),
+    );
+
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// Copies the saved files of the entry to the output folder. It returns false if the entry does not exist.
+pub(crate) fn restore(key: &str, output_path: &Path, wasm_file: &str) -> bool {
+    let entry_path = cache_dir().join(key);
+    match read_entry(&entry_path) {
+        Some(entry) if entry.wasm_file == wasm_file => {}
+        _ => return false,
+    }
+    let restored = entry_files(&entry_path).and_then(|files| {
+        files
+            .iter()
+            .try_for_each(|file| std::fs::copy(entry_path.join(file), output_path.join(file)).map(|_| ()))
+    });
+    if restored.is_err() {
+        return false;
+    }
+    let _ = write_entry_info(&entry_path, wasm_file);
+    true
+}
+
+/// Saves the output files of a build as an entry. The files are the compiled contract and the files saved next to it,
+/// i.e. the Cargo.lock, the ABI, the size report (if `size_report` is set), and the build metadata and the build log
+/// (if they exist, see [crate::OutputLayout::PerContract]).
+pub(crate) fn store(key: &str, output_path: &Path, wasm_file: &str, size_report: bool) {
+    let dir = cache_dir();
+    let entry_path = dir.join(key);
+    if entry_path.exists() {
+        return;
+    }
+
+    // Save the files to a temporary folder first, so that an entry is either complete or absent.
+    let suffix: String = thread_rng().sample_iter(&Alphanumeric).take(5).collect();
+    let temp_path = dir.join(format!(".{key}.{suffix}"));
+    let mut files = vec![
+        wasm_file.to_string(),
+        "Cargo.lock".to_string(),
+        crate::abi::abi_file(wasm_file),
+        crate::metadata::BUILD_METADATA_FILE.to_string(),
+        crate::build::BUILD_LOG_FILE.to_string(),
+    ];
+    if size_report {
+        files.push(crate::size::size_report_file(wasm_file));
+    }
+    let saved = std::fs::create_dir_all(&temp_path).and_then(|_| {
+        files
+            .iter()
+            .filter(|file| output_path.join(file).exists())
+            .try_for_each(|file| std::fs::copy(output_path.join(file), temp_path.join(file)).map(|_| ()))
+    });
+    let saved = saved
+        .and_then(|_| write_entry_info(&temp_path, wasm_file))
+        .and_then(|_| std::fs::rename(&temp_path, &entry_path));
+    if saved.is_err() {
+        let _ = std::fs::remove_dir_all(&temp_path);
+    }
+}
+
+fn read_entry(entry_path: &Path) -> Option<CacheEntry> {
+    let content = std::fs::read(entry_path.join(ENTRY_FILE)).ok()?;
+    let info: EntryInfo = serde_json::from_slice(&content).ok()?;
+    let size = entry_files(entry_path)
+        .ok()?
+        .iter()
+        .filter_map(|file| std::fs::metadata(entry_path.join(file)).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    Some(CacheEntry {
+        key: entry_path.file_name()?.to_string_lossy().to_string(),
+        path: entry_path.to_path_buf(),
+        wasm_file: info.wasm_file,
+        size,
+        last_used: UNIX_EPOCH + Duration::from_secs(info.last_used),
+    })
+}
+
+/// Returns the names of the saved files in the folder of an entry.
+fn entry_files(entry_path: &Path) -> std::io::Result<Vec<String>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(entry_path)? {
+        let file_name = entry?.file_name().to_string_lossy().to_string();
+        if file_name != ENTRY_FILE {
+            files.push(file_name);
+        }
+    }
+    Ok(files)
+}
+
+fn write_entry_info(entry_path: &Path, wasm_file: &str) -> std::io::Result<()> {
+    let info = EntryInfo {
+        wasm_file: wasm_file.to_string(),
+        last_used: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+    };
+    std::fs::write(entry_path.join(ENTRY_FILE), serde_json::to_vec(&info).unwrap())
+}
@@ -8,13 +8,16 @@
 use std::{path::{Path, PathBuf}, io::Write, sync::{Arc, Mutex}};
 
 use cargo::{
-    core::compiler::{CompileKind, CompileTarget},
+    core::{
+        compiler::{CompileKind, CompileTarget},
+        resolver::CliFeatures,
+    },
     ops::CompileOptions,
     util::interning::InternedString,
     Config,
 };
 
-use crate::error::Error;
+use crate::{error::Error, BuildOptions};
 
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 
@@ -25,7 +28,6 @@ pub(crate) fn random_temp_dir_name() -> PathBuf {
             thread_rng()
                 .sample_iter(&Alphanumeric)
                 .take(5)
-                .map(char::from)
                 .collect::<String>(),
         )
         .to_path_buf()
@@ -36,18 +38,26 @@ pub(crate) fn random_temp_dir_name() -> PathBuf {
 /// 2. wasm-opt -Oz <wasm_file> --output temp.wasm
 /// 3. wasm-snip temp.wasm --output temp2.wasm --snip-rust-fmt-code --snip-rust-panicking-code
 /// 4. wasm-opt --dce temp2.wasm --output <wasm_file>
+///
+/// Steps 2 to 4 are implemented in [crate::optimize::post_process]. The cargo output `cargo_wasm_file` is saved as
+/// `wasm_file` in the destination folder. If a size report is requested, the working folder keeps the intermediate
+/// binaries, including a copy of the cargo output and the result of `wasm-opt -Oz -g <wasm_file> --output named.wasm`.
+/// See [crate::size].
+///
+/// It returns the build log if success.
 pub(crate) fn build_contract(
     working_folder: &Path,
     source_path: &Path,
     destination_path: Option<PathBuf>,
-    locked: bool,
+    options: &BuildOptions,
+    cargo_wasm_file: &str,
     wasm_file: &str,
-) -> Result<(), Error> {
+) -> Result<String, Error> {
     let output_path = destination_path.unwrap_or(Path::new(".").to_path_buf());
 
     // 1. cargo build --target wasm32-unknown-unknown --release --quiet
     // Does not set "--locked" if the Cargo.lock file does not exist.
-    let use_cargo_lock = locked && source_path.join("Cargo.lock").exists();
+    let use_cargo_lock = (options.locked || options.frozen) && source_path.join("Cargo.lock").exists();
     let mut config = CargoConfig::new();
     config
         .configure(0, false, None, false, use_cargo_lock, false, &None, &[], &[])
@@ -58,60 +68,54 @@ pub(crate) fn build_contract(
         CompileTarget::new("wasm32-unknown-unknown").unwrap(),
     )];
     compile_configs.build_config.requested_profile = InternedString::new("release");
+    compile_configs.cli_features = CliFeatures::from_command_line(&options.features, false, true)
+        .map_err(|e| Error::BuildFailure(format!("Invalid features:\n\n{:?}\n", e)))?;
     let ws =
         cargo::core::Workspace::new(&source_path.join("Cargo.toml"), &config).map_err(|e| {
             Error::BuildFailure(
                 format!("Error in preparing workspace according to the manifest file in source path:\n\n{:?}\n", e),
             )
         })?;
-    if let Err(_) = cargo::ops::compile(&ws, &compile_configs) {
+    if let Err(e) = cargo::ops::compile(&ws, &compile_configs) {
+        if options.frozen && is_lockfile_outdated(&format!("{:?}", e)) {
+            return Err(Error::LockfileOutdated(format!("{}{:?}\n", config.logs(), e)))
+        }
         return Err(Error::BuildFailureWithLogs(config.logs()))
     }
 
     // Save Cargo.lock to output folder: If option '--locked' is enabled, the Cargo.lock file 
     // is the file provided by user, otherwise, the Cargo.lock file is the one generated during
     // "cargo build".
-    if locked {
-        let _ = std::fs::copy(source_path.join("Cargo.lock"), output_path.join("Cargo.lock"));
-    }
+    let _ = std::fs::copy(source_path.join("Cargo.lock"), output_path.join("Cargo.lock"));
 
-    // 2. wasm-opt -Oz wasm_file --output temp.wasm
-    let temp_wasm = working_folder.join("temp.wasm");
-    wasm_opt::OptimizationOptions::new_optimize_for_size_aggressively()
-        .run(
-            source_path
-                .join("target")
-                .join("wasm32-unknown-unknown")
-                .join("release")
-                .join(wasm_file),
-            &temp_wasm,
-        )
-        .map_err(|e| Error::BuildFailure(format!("Wasm optimization error:\n\n{:?}\n", e)))?;
-
-    // 3. wasm-snip temp.wasm --output temp2.wasm --snip-rust-fmt-code --snip-rust-panicking-code
-    let temp2_wasm = working_folder.join("temp2.wasm");
-    let wasm_snip_options = wasm_snip::Options {
-        snip_rust_fmt_code: true,
-        snip_rust_panicking_code: true,
-        ..Default::default()
-    };
-    let mut module = walrus::ModuleConfig::new()
-        .parse_file(temp_wasm)
-        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
-    wasm_snip::snip(&mut module, wasm_snip_options)
-        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
-    module
-        .emit_wasm_file(&temp2_wasm)
-        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
-
-    // 4. wasm-opt --dce temp2.wasm --output wasm_file
-    let optimized_wasm = output_path.join(wasm_file);
-    wasm_opt::OptimizationOptions::new_optimize_for_size()
-        .add_pass(wasm_opt::Pass::Dce)
-        .run(temp2_wasm, optimized_wasm)
-        .map_err(|e| Error::BuildFailure(format!("Wasm optimization error:\n\n{:?}\n", e)))?;
-
-    Ok(())
+    // 2. - 4. wasm-opt, wasm-snip and wasm-opt --dce
+    let cargo_wasm = source_path
+        .join("target")
+        .join("wasm32-unknown-unknown")
+        .join("release")
+        .join(cargo_wasm_file);
+    crate::optimize::post_process(&cargo_wasm, working_folder, &output_path.join(wasm_file), options.size_report)?;
+
+    Ok(config.logs())
+}
+
+/// Returns the output of `rustc --version` in the local environment. It runs in the source code folder, so that it
+/// reports the toolchain selected by rustup for the source code (e.g. by `rust-toolchain.toml`), as cargo does.
+pub(crate) fn rustc_version(source_path: &Path) -> Option<String> {
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+    std::process::Command::new(rustc)
+        .arg("--version")
+        .current_dir(source_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Checks if the building log reports that Cargo refused to update the Cargo.lock because
+/// "--locked" was passed.
+pub(crate) fn is_lockfile_outdated(build_log: &str) -> bool {
+    build_log.contains("needs to be updated but --locked was passed")
 }
 
 /// Captures the [cargo::util::Config] with custom instantiation.
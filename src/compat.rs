@@ -0,0 +1,190 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of checking the upgrade compatibility between two versions of a contract. The ABIs
+//! (see [crate::abi]) of both versions are compared, and each change is classified as breaking or compatible.
+//!
+//! Methods are matched by name. Removing a method, or changing its argument types, return type or turning
+//! a view into a mutating method is breaking. Arguments are passed by position, so renaming an argument is compatible.
+//!
+//! Storage fields are matched by position, because the storage key of a field is derived from its position.
+//! Retyping or reordering fields is breaking. Renaming a field, appending a field, or removing the last fields
+//! (whose stored values are no longer accessible) is compatible.
+
+use std::{fmt::Display, path::Path};
+
+use serde::Serialize;
+
+use crate::{abi::ContractAbi, error::Error};
+
+/// Result of comparing two versions of a contract.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompatReport {
+    /// Changes from the old version to the new version.
+    pub changes: Vec<Change>,
+}
+
+impl CompatReport {
+    /// Returns true if none of the changes is breaking.
+    pub fn is_compatible(&self) -> bool {
+        self.changes.iter().all(|change| change.kind == ChangeKind::Compatible)
+    }
+}
+
+/// A change from the old version to the new version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// The changed item, e.g. `method hello_from` or `storage field 0 (counter)`.
+    pub item: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChangeKind {
+    /// Existing clients or stored data may not work with the new version.
+    Breaking,
+    /// Existing clients and stored data work with the new version.
+    Compatible,
+}
+
+/// `check_compatibility` compares the contract at `old_path` with the contract source code at `new_path`.
+/// The old version can be a source code directory, an ABI file (`<contract>.abi.json`), or a WebAssembly binary
+/// with the ABI embedded.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` compat --old `old_path` --new `new_path`
+pub fn check_compatibility(old_path: &Path, new_path: &Path) -> Result<CompatReport, Error> {
+    let old = load_abi(old_path)?;
+    let new = crate::abi::generate_abi(new_path)?;
+    Ok(compare_abis(&old, &new))
+}
+
+/// Loads the ABI from a source code directory, an ABI file, or a WebAssembly binary with the ABI embedded.
+fn load_abi(path: &Path) -> Result<ContractAbi, Error> {
+    if path.is_dir() {
+        return crate::abi::generate_abi(path);
+    }
+    let content = std::fs::read(path)
+        .map_err(|e| Error::AbiGenerationFailure(format!("Fail to read {}: {e}", path.display())))?;
+    if path.extension().is_some_and(|extension| extension == "wasm") {
+        return crate::abi::read_abi(&content)?.ok_or(Error::AbiGenerationFailure(format!(
+            "{} does not contain the ABI section.",
+            path.display()
+        )));
+    }
+    serde_json::from_slice(&content)
+        .map_err(|e| Error::AbiGenerationFailure(format!("Malformed ABI file {}: {e}", path.display())))
+}
+
+/// Compares the methods and storage layouts of two ABIs.
+pub fn compare_abis(old: &ContractAbi, new: &ContractAbi) -> CompatReport {
+    let mut changes = vec![];
+    compare_methods(old, new, &mut changes);
+    compare_storage(old, new, &mut changes);
+    CompatReport { changes }
+}
+
+fn compare_methods(old: &ContractAbi, new: &ContractAbi, changes: &mut Vec<Change>) {
+    for old_method in &old.methods {
+        let item = format!("method {}", old_method.name);
+        let Some(new_method) = new.methods.iter().find(|method| method.name == old_method.name) else {
+            changes.push(breaking(item, "removed"));
+            continue;
+        };
+
+        let old_types: Vec<&str> = old_method.args.iter().map(|arg| arg.ty.as_str()).collect();
+        let new_types: Vec<&str> = new_method.args.iter().map(|arg| arg.ty.as_str()).collect();
+        if old_types != new_types {
+            changes.push(breaking(
+                item.clone(),
+                &format!("arguments changed from ({}) to ({})", old_types.join(", "), new_types.join(", ")),
+            ));
+        } else if old_method.args != new_method.args {
+            changes.push(compatible(item.clone(), "arguments renamed"));
+        }
+
+        if old_method.return_type != new_method.return_type {
+            changes.push(breaking(
+                item.clone(),
+                &format!(
+                    "return type changed from {} to {}",
+                    old_method.return_type.as_deref().unwrap_or("()"),
+                    new_method.return_type.as_deref().unwrap_or("()")
+                ),
+            ));
+        }
+
+        match (old_method.is_view, new_method.is_view) {
+            (true, false) => changes.push(breaking(item, "changed from view to mutating")),
+            (false, true) => changes.push(compatible(item, "changed from mutating to view")),
+            _ => {}
+        }
+    }
+
+    for new_method in &new.methods {
+        if !old.methods.iter().any(|method| method.name == new_method.name) {
+            changes.push(compatible(format!("method {}", new_method.name), "added"));
+        }
+    }
+}
+
+fn compare_storage(old: &ContractAbi, new: &ContractAbi, changes: &mut Vec<Change>) {
+    for index in 0..old.storage.len().max(new.storage.len()) {
+        match (old.storage.get(index), new.storage.get(index)) {
+            (Some(old_field), Some(new_field)) => {
+                let item = format!("storage field {index} ({})", old_field.name);
+                let moved = old_field.name != new_field.name
+                    && old.storage.iter().any(|field| field.name == new_field.name);
+                if moved {
+                    changes.push(breaking(item, &format!("reordered, now holds {}", new_field.name)));
+                } else if old_field.ty != new_field.ty {
+                    changes.push(breaking(
+                        item,
+                        &format!("type changed from {} to {}", old_field.ty, new_field.ty),
+                    ));
+                } else if old_field.name != new_field.name {
+                    changes.push(compatible(item, &format!("renamed to {}", new_field.name)));
+                }
+            }
+            (Some(old_field), None) => changes.push(compatible(
+                format!("storage field {index} ({})", old_field.name),
+                "removed, its stored value is no longer accessible",
+            )),
+            (None, Some(new_field)) => changes.push(compatible(
+                format!("storage field {index} ({})", new_field.name),
+                "added",
+            )),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn breaking(item: String, description: &str) -> Change {
+    Change {
+        kind: ChangeKind::Breaking,
+        item,
+        description: description.to_string(),
+    }
+}
+
+fn compatible(item: String, description: &str) -> Change {
+    Change {
+        kind: ChangeKind::Compatible,
+        item,
+        description: description.to_string(),
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ChangeKind::Breaking => "breaking",
+            ChangeKind::Compatible => "compatible",
+        };
+        write!(f, "[{kind}] {}: {}", self.item, self.description)
+    }
+}
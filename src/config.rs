@@ -6,8 +6,11 @@
 //! Configuration of pchain_compile. The struct `Config` specifies parameters being used, and
 //! provides a method `run` that starts the compilation process.
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::{BuildBackend, DockerBackend, DockerCliBackend, DockerlessBackend, SandboxBackend};
 use crate::error::Error;
 
 /// Configuration to compile smart contract.
@@ -28,7 +31,146 @@ pub struct Config {
 pub struct BuildOptions {
     /// Use of the Cargo.lock. It is equivalent to run Cargo build with 
     /// flag "--locked".
-    pub locked: bool
+    pub locked: bool,
+    /// Strict use of the Cargo.lock. It implies `locked`, and the building process fails
+    /// if the file Cargo.lock does not exist or needs to be updated.
+    pub frozen: bool,
+    /// List of features to activate. It is equivalent to run Cargo build with
+    /// flag "--features".
+    pub features: Vec<String>,
+    /// Embed build provenance metadata into the compiled contract as a custom
+    /// section named "pchain.build". See [crate::metadata::BuildMetadata].
+    pub embed_metadata: bool,
+    /// Embed the ABI of the contract into the compiled contract as a custom section
+    /// named "pchain.abi". See [crate::abi::ContractAbi].
+    pub embed_abi: bool,
+    /// How to handle floating-point instructions found in the compiled contract.
+    /// See [crate::validate::find_float_instructions].
+    pub float_policy: FloatPolicy,
+    /// Save a size report of the compiled contract as `<contract>.size.json` in the destination
+    /// folder. See [crate::size::SizeReport].
+    pub size_report: bool,
+    /// Maximum size (in bytes) of the compiled contract. The building process fails if the
+    /// optimized artifact is larger than it.
+    pub max_wasm_size: Option<usize>,
+    /// Baseline artifact to compare the size of the compiled contract with. See [SizeBaseline].
+    pub size_baseline: Option<SizeBaseline>,
+    /// Always build the source code, without returning or saving the build in the local
+    /// build cache. See [crate::cache].
+    pub no_cache: bool,
+    /// Layout of the output files in the destination folder. See [OutputLayout].
+    pub output_layout: OutputLayout,
+    /// Append the version of the package to the file name of the compiled contract, e.g.
+    /// `hello_contract-0.4.0.wasm`. The files saved next to it are named after it.
+    pub versioned_name: bool,
+    /// Called with the warnings found in building, which do not fail the build. The warnings
+    /// are ignored if it is None. (Default)
+    pub on_warning: Option<WarningHandler>,
+}
+
+impl BuildOptions {
+    /// Passes the warning to `on_warning`.
+    pub(crate) fn warn(&self, warning: BuildWarning) {
+        if let Some(on_warning) = &self.on_warning {
+            on_warning(&warning);
+        }
+    }
+}
+
+/// Handler of the warnings found in building. See [BuildOptions::on_warning].
+pub type WarningHandler = Arc<dyn Fn(&BuildWarning) + Send + Sync>;
+
+/// Warning found in building a contract, which does not fail the build.
+#[derive(Clone, Debug)]
+pub enum BuildWarning {
+    /// Floating-point instructions are found in the compiled contract `wasm_file`, with [FloatPolicy::Warn].
+    FloatInstructions {
+        wasm_file: String,
+        usages: Vec<crate::validate::FloatUsage>,
+    },
+    /// The ABI of the compiled contract `wasm_file` cannot be generated from the source code, so it is not saved.
+    /// It fails the build instead if [BuildOptions::embed_abi] is set.
+    AbiNotGenerated { wasm_file: String, reason: String },
+}
+
+impl std::fmt::Display for BuildWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildWarning::FloatInstructions { wasm_file, usages } => {
+                write!(f, "floating-point instructions are found in {wasm_file}:")?;
+                for usage in usages {
+                    write!(f, "\n- {usage}")?;
+                }
+                Ok(())
+            }
+            BuildWarning::AbiNotGenerated { wasm_file, reason } => {
+                write!(f, "the ABI of {wasm_file} is not generated: {reason}")
+            }
+        }
+    }
+}
+
+/// Layout of the output files in the destination folder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// Save the output files of all contracts in the destination folder. (Default)
+    #[default]
+    Flat,
+    /// Save the output files of each contract in its own folder `<destination>/<contract>/`, together with
+    /// the build metadata `build.json` (see [crate::metadata::BuildMetadata]) and the build log `build.log`.
+    PerContract,
+}
+
+impl std::str::FromStr for OutputLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Self::Flat),
+            "per-contract" => Ok(Self::PerContract),
+            _ => Err(format!("unknown output layout \"{s}\", expected one of: flat, per-contract")),
+        }
+    }
+}
+
+/// Baseline artifact for limiting the growth of the size of the compiled contract.
+#[derive(Clone, Debug)]
+pub struct SizeBaseline {
+    /// Path to the baseline WebAssembly binary. If it is a folder, the binary with the same
+    /// file name as the compiled contract in the folder is used.
+    pub wasm_path: PathBuf,
+    /// The building process fails if the compiled contract is larger than the baseline by
+    /// more than this percentage.
+    pub max_growth_percent: f64,
+}
+
+/// Policy on floating-point instructions in the compiled contract. Floating-point operations
+/// are non-deterministic across platforms, so contracts using them are a risk to consensus.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FloatPolicy {
+    /// Ignore floating-point instructions.
+    Allow,
+    /// Report a warning that lists the functions using floating-point instructions to [BuildOptions::on_warning].
+    /// (Default)
+    #[default]
+    Warn,
+    /// Fail the building process if any floating-point instruction is found.
+    Error,
+}
+
+impl std::str::FromStr for FloatPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("unknown float policy \"{s}\", expected one of: allow, warn, error")),
+        }
+    }
 }
 
 /// Compilation option regards to docker.
@@ -38,6 +180,8 @@ pub enum DockerOption {
     Docker(DockerConfig),
     /// Compile contract without using Docker.
     Dockerless,
+    /// Compile contract in a Linux user-namespace sandbox, without using Docker.
+    Sandbox(SandboxConfig),
 }
 
 impl Default for DockerOption {
@@ -46,28 +190,88 @@ impl Default for DockerOption {
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct DockerConfig {
     /// Docker Image tag.
     pub tag: Option<String>,
+    /// Address of the docker daemon, either a unix socket (e.g. `unix:///run/user/1000/podman/podman.sock`) or
+    /// an HTTP address (e.g. `tcp://127.0.0.1:2375`). If None, the environment variable `DOCKER_HOST` is used.
+    /// Without it, the default docker socket is used, or the Podman socket if the docker socket does not exist. On
+    /// Windows, the named pipe of the docker daemon is used.
+    pub host: Option<String>,
+    /// Folder of the TLS certificates `ca.pem`, `cert.pem` and `key.pem`, to connect to the docker daemon at an
+    /// HTTP address over TLS. It cannot be used with a unix socket. If None, TLS is enabled by the environment
+    /// variable `DOCKER_TLS_VERIFY`, with the certificates in `DOCKER_CERT_PATH` (Default: `~/.docker`).
+    pub tls_cert_path: Option<PathBuf>,
+    /// Version of the Docker Engine API, e.g. `1.41`. If None, it is negotiated with the docker daemon.
+    pub api_version: Option<String>,
+    /// Timeout (in seconds) of the requests to the docker daemon. (Default: 120)
+    pub timeout: Option<u64>,
+    /// Container CLI binary (e.g. `docker` or `podman`) to run the containers with, instead of connecting to the API
+    /// socket of the docker daemon. The CLI uses its own connection settings, so `host`, `tls_cert_path`, `api_version`
+    /// and `timeout` are ignored. See [crate::backend::DockerCliBackend].
+    pub cli: Option<String>,
+    /// Use the docker image built locally by [crate::image::build_image] instead of pulling the image from DockerHub.
+    pub local_image: bool,
+}
+
+/// Configuration of building in a Linux user-namespace sandbox created by bubblewrap. The sandbox has no network, and
+/// mounts the source code and the toolchain read-only. See [crate::backend::SandboxBackend].
+#[derive(Clone, Debug, Default)]
+pub struct SandboxConfig {
+    /// Rust toolchain installed by rustup, e.g. `1.71.0`. The target `wasm32-unknown-unknown` must be added to it.
+    /// If None, the toolchain selected by rustup for the source code is used.
+    pub toolchain: Option<String>,
+    /// Path to the bubblewrap binary. (Default: `bwrap`)
+    pub bwrap: Option<String>,
 }
 
 impl Config {
     pub async fn run(self) -> Result<String, Error> {
-        match self.docker_option {
+        self.run_cancellable(CancellationToken::new()).await
+    }
+
+    /// Runs the compilation process until it finishes or `cancellation_token` is cancelled. On cancellation, it
+    /// returns [Error::BuildCancelled] after the docker container and the temporary folders are removed. Building
+    /// without docker returns immediately, and the running cargo build, which cannot be interrupted, finishes in the
+    /// background.
+    pub async fn run_cancellable(self, cancellation_token: CancellationToken) -> Result<String, Error> {
+        if cancellation_token.is_cancelled() {
+            return Err(Error::BuildCancelled);
+        }
+        match self.docker_option.clone() {
+            DockerOption::Docker(docker_config) if docker_config.cli.is_some() => {
+                let backend = DockerCliBackend::new(docker_config)?;
+                self.run_with_backend(&backend, cancellation_token).await
+            }
             DockerOption::Docker(docker_config) => {
-                crate::build::build_target_with_docker(
-                    self.source_path,
-                    self.destination_path,
-                    self.build_options,
-                    docker_config,
-                )
-                .await
+                let backend = DockerBackend::new(docker_config)?;
+                self.run_with_backend(&backend, cancellation_token).await
             }
-            DockerOption::Dockerless => {
-                crate::build::build_target_without_docker(self.source_path, self.destination_path, self.build_options)
-                    .await
+            DockerOption::Dockerless => self.run_with_backend(&DockerlessBackend, cancellation_token).await,
+            DockerOption::Sandbox(sandbox_config) => {
+                self.run_with_backend(&SandboxBackend::new(sandbox_config), cancellation_token).await
             }
         }
     }
+
+    /// Runs the compilation process with `backend`, instead of the backend selected by `docker_option`. See
+    /// [crate::backend].
+    pub async fn run_with_backend<B: BuildBackend>(
+        self,
+        backend: &B,
+        cancellation_token: CancellationToken,
+    ) -> Result<String, Error> {
+        if cancellation_token.is_cancelled() {
+            return Err(Error::BuildCancelled);
+        }
+        crate::build::build_target_with_backend(
+            backend,
+            self.source_path,
+            self.destination_path,
+            self.build_options,
+            &cancellation_token,
+        )
+        .await
+    }
 }
@@ -7,6 +7,7 @@
 //! copying files to container and executing commands inside docker.
 
 use std::{
+    collections::HashMap,
     io::{Read, Write},
     ops::Not,
     path::{Path, PathBuf}, time::Duration,
@@ -18,38 +19,150 @@ use bollard::{
         StartContainerOptions, UploadToContainerOptions,
     },
     exec::{CreateExecOptions, StartExecOptions},
-    image::CreateImageOptions,
+    image::{BuildImageOptions, CreateImageOptions},
     service::HostConfig,
-    Docker,
+    ClientVersion, Docker,
 };
-use futures_util::TryStreamExt;
+use futures_util::{StreamExt, TryStreamExt};
 use tar::Archive;
 
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use std::fs::File;
 
-use crate::error::Error;
+use crate::{error::Error, image::ImageVersions, BuildOptions, DockerConfig};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 
-/// List of docker image tags that can be used. The first (0-indexed) is the default one. 
-pub(crate) const PCHAIN_COMPILE_IMAGE_TAGS: [&str; 3] = [env!("CARGO_PKG_VERSION"), "0.4.2", "mainnet01"];
+/// List of docker image tags that can be used, with the versions of the components installed in them. The first
+/// (0-indexed) is the default one. 
+pub(crate) const PCHAIN_COMPILE_IMAGE_TAGS: [(&str, ImageVersions); 3] = [
+    (env!("CARGO_PKG_VERSION"), ImageVersions { rustc: "1.77.1", wasm_snip: "0.4.0", binaryen: "114" }),
+    ("0.4.2", ImageVersions { rustc: "1.71.0", wasm_snip: "0.4.0", binaryen: "114" }),
+    ("mainnet01", ImageVersions { rustc: "1.66.1", wasm_snip: "0.4.0", binaryen: "109" }),
+];
 /// The repo name in Parallelchain Lab Dockerhub: https://hub.docker.com/r/parallelchainlab/pchain_compile
 pub(crate) const PCHAIN_COMPILE_IMAGE: &str = "parallelchainlab/pchain_compile";
-const DOCKER_EXEC_TIME_LIMIT: u64 = 15; // secs. It is a time limit to normal docker execution (except cargo build).
+/// Folder in the docker container that keeps the output files of the building process.
+pub(crate) const OUTPUT_FOLDER: &str = "/result";
+/// Folder in the docker container that keeps the intermediate binaries for size report.
+pub(crate) const SIZE_REPORT_FOLDER: &str = "/size_report";
+pub(crate) const DOCKER_EXEC_TIME_LIMIT: u64 = 15; // secs. It is a time limit to normal docker execution (except cargo build).
+const DOCKER_API_TIMEOUT: u64 = 120; // secs. It is the default timeout of the requests to docker daemon.
+/// The default docker socket.
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+/// The address of the docker daemon if no socket is found, i.e. the docker socket on Unix, or the named pipe of the
+/// docker daemon on Windows.
+#[cfg(unix)]
+const DEFAULT_DOCKER_HOST: &str = "unix:///var/run/docker.sock";
+#[cfg(windows)]
+const DEFAULT_DOCKER_HOST: &str = "npipe:////./pipe/docker_engine";
+/// The socket of rootful Podman. The socket of rootless Podman is `podman/podman.sock` in `XDG_RUNTIME_DIR`.
+const PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+
+/// Connects to the docker daemon according to the connection settings in `docker_config`. If the API version is not
+/// set, it is negotiated with the daemon, so that daemons supporting older versions (e.g. Podman) can be used.
+pub(crate) async fn connect(docker_config: &DockerConfig) -> Result<Docker, Error> {
+    let env = |name: &str| std::env::var(name).ok();
+    let host = docker_host_with(docker_config, env, Path::new(DOCKER_SOCKET));
+    let timeout = docker_config.timeout.unwrap_or(DOCKER_API_TIMEOUT);
+    let client_version = match &docker_config.api_version {
+        Some(api_version) => parse_api_version(api_version)?,
+        None => *bollard::API_DEFAULT_VERSION,
+    };
+    let is_http = ["tcp://", "http://", "https://"].iter().any(|scheme| host.starts_with(scheme));
+    let docker = match (tls_cert_path_with(docker_config, env), is_http) {
+        (Some(cert_path), true) => Docker::connect_with_ssl(
+            &host,
+            &cert_path.join("key.pem"),
+            &cert_path.join("cert.pem"),
+            &cert_path.join("ca.pem"),
+            timeout,
+            &client_version,
+        ),
+        (None, true) if host.starts_with("https://") => {
+            return Err(Error::InvalidDockerConnection(format!(
+                "Connecting to {host} over TLS requires the folder of the TLS certificates (--docker-tls-cert-path)."
+            )))
+        }
+        (None, true) => Docker::connect_with_http(&host, timeout, &client_version),
+        (_, false) if docker_config.tls_cert_path.is_some() => {
+            return Err(Error::InvalidDockerConnection(format!(
+                "The TLS certificates (--docker-tls-cert-path) cannot be used to connect to {host}, which is not an HTTP address."
+            )))
+        }
+        (_, false) => Docker::connect_with_socket(&host, timeout, &client_version),
+    }
+    .map_err(|e| Error::InvalidDockerConnection(format!("Fail to connect to {host}: {e}")))?;
+
+    match docker_config.api_version {
+        Some(_) => Ok(docker),
+        None => docker.negotiate_version().await.map_err(|_| Error::DockerDaemonFailure),
+    }
+}
+
+/// Returns the address of the docker daemon, reading the environment variables by `env`. `docker_socket` is the
+/// default docker socket. See [DockerConfig::host].
+fn docker_host_with(docker_config: &DockerConfig, env: impl Fn(&str) -> Option<String>, docker_socket: &Path) -> String {
+    if let Some(host) = docker_config.host.clone().or(env("DOCKER_HOST")) {
+        return host;
+    }
+    let rootless_podman_socket =
+        env("XDG_RUNTIME_DIR").map(|runtime_dir| Path::new(&runtime_dir).join("podman").join("podman.sock"));
+    [Some(docker_socket.to_path_buf()), rootless_podman_socket, Some(PathBuf::from(PODMAN_SOCKET))]
+        .into_iter()
+        .flatten()
+        .find(|socket| socket.exists())
+        .map(|socket| format!("unix://{}", socket.display()))
+        .unwrap_or(DEFAULT_DOCKER_HOST.to_string())
+}
+
+/// Returns the folder of the TLS certificates, reading the environment variables by `env`. Without the setting in
+/// `docker_config`, TLS is enabled by a non-empty `DOCKER_TLS_VERIFY` as in the docker CLI, with the certificates
+/// in `DOCKER_CERT_PATH` or `~/.docker`. See [DockerConfig::tls_cert_path].
+fn tls_cert_path_with(docker_config: &DockerConfig, env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    if docker_config.tls_cert_path.is_some() {
+        return docker_config.tls_cert_path.clone();
+    }
+    env("DOCKER_TLS_VERIFY").filter(|tls_verify| !tls_verify.is_empty())?;
+    env("DOCKER_CERT_PATH")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").or_else(|| env("USERPROFILE")).map(|home| Path::new(&home).join(".docker")))
+}
+
+/// Parses the version of the Docker Engine API in the form of `<major>.<minor>`, e.g. `1.41`.
+pub(crate) fn parse_api_version(api_version: &str) -> Result<ClientVersion, Error> {
+    api_version
+        .split_once('.')
+        .and_then(|(major, minor)| Some(ClientVersion {
+            major_version: major.parse().ok()?,
+            minor_version: minor.parse().ok()?,
+        }))
+        .ok_or(Error::InvalidDockerConnection(format!(
+            "Invalid API version \"{api_version}\", expected the form of <major>.<minor>, e.g. 1.41"
+        )))
+}
 
 /// Generate a random Docker container name
 pub fn random_container_name() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
         .take(5)
-        .map(char::from)
         .collect()
 }
 
-/// Pull docker image from ParallelChain Lab DockerHub. Returns the name of docker image.
-pub async fn pull_image(docker: &Docker, tag: &str) -> Result<String, Error> {
+/// Pull docker image from ParallelChain Lab DockerHub. Returns the name of docker image. If `local_image` is true,
+/// the image built locally by [crate::image::build_image] is used instead, and it fails if the image does not exist.
+pub async fn pull_image(docker: &Docker, tag: &str, local_image: bool) -> Result<String, Error> {
+    if local_image {
+        let image = crate::image::local_image_name(tag);
+        return match docker.inspect_image(&image).await {
+            Ok(_) => Ok(image),
+            Err(_) => Err(Error::LocalImageNotFound(image)),
+        };
+    }
+
     let from_image = format!("{PCHAIN_COMPILE_IMAGE}:{tag}");
+
     let create_image_infos = &docker
         .create_image(
             Some(CreateImageOptions {
@@ -70,6 +183,42 @@ pub async fn pull_image(docker: &Docker, tag: &str) -> Result<String, Error> {
     Ok(from_image)
 }
 
+/// Builds the docker image `image` from the build context `context` (a tar archive containing the Dockerfile) with
+/// the build arguments. `on_log` is called with the output of each building step. Returns the building log.
+pub async fn build_image<F: FnMut(&str)>(
+    docker: &Docker,
+    image: &str,
+    context: Vec<u8>,
+    build_args: HashMap<String, String>,
+    mut on_log: F,
+) -> Result<String, Error> {
+    let mut build_infos = docker.build_image(
+        BuildImageOptions {
+            dockerfile: "Dockerfile".to_string(),
+            t: image.to_string(),
+            rm: true,
+            forcerm: true,
+            buildargs: build_args,
+            ..Default::default()
+        },
+        None,
+        Some(context.into()),
+    );
+
+    let mut build_log = String::new();
+    while let Some(build_info) = build_infos.next().await {
+        let build_info = build_info.map_err(|e| Error::ImageBuildFailure(format!("{build_log}{e}")))?;
+        if let Some(error) = build_info.error {
+            return Err(Error::ImageBuildFailure(format!("{build_log}{error}")));
+        }
+        if let Some(stream) = build_info.stream {
+            on_log(&stream);
+            build_log.push_str(&stream);
+        }
+    }
+    Ok(build_log)
+}
+
 /// Starts a containter with the Image pulled from ParallelChain Lab DockerHub
 pub async fn start_container(
     docker: &Docker,
@@ -112,12 +261,7 @@ pub async fn copy_files(
     container_name: &str,
     source_path: &str,
 ) -> Result<(), Error> {
-    let save_to_path = source_path
-        .replace(':', "")
-        .replace('\\', "/")
-        .replace(' ', "_")
-        .trim_start_matches('/')
-        .to_string(); // Remove the starting "/" for linux file path format.
+    let save_to_path = container_path(source_path);
 
     let src_path = Path::new(source_path).to_path_buf();
     let dst_path = Path::new(
@@ -130,19 +274,24 @@ pub async fn copy_files(
     )
     .to_path_buf();
 
-    create_tar_gz(src_path, &save_to_path, &dst_path).map_err(|_| Error::DockerDaemonFailure)?;
+    if create_tar_gz(src_path, &save_to_path, &dst_path).is_err() {
+        let _ = std::fs::remove_file(&dst_path);
+        return Err(Error::DockerDaemonFailure);
+    }
 
     // Read Content
-    let file_content = File::open(dst_path.clone())
-        .map(|mut file| {
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).unwrap();
-            contents
-        })
-        .map_err(|_| Error::DockerDaemonFailure)?;
+    let file_content = File::open(dst_path.clone()).map(|mut file| {
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        contents
+    });
+
+    // Remove file before uploading, so that it is not left behind if the upload is cancelled
+    let _ = std::fs::remove_file(&dst_path); // remove the compressed file .tar.gz
+    let file_content = file_content.map_err(|_| Error::DockerDaemonFailure)?;
 
     // Save to docker container
-    let result = docker
+    docker
         .upload_to_container(
             container_name,
             Some(UploadToContainerOptions {
@@ -151,12 +300,8 @@ pub async fn copy_files(
             }),
             file_content.into(),
         )
-        .await;
-
-    // Remove file
-    let _ = std::fs::remove_file(&dst_path); // remove the compressed file .tar.gz
-
-    result.map_err(|_| Error::DockerDaemonFailure)
+        .await
+        .map_err(|_| Error::DockerDaemonFailure)
 }
 
 /// Copy files from docker container to a specified output path. The output path is None, current path becomes the output path.
@@ -178,7 +323,17 @@ pub async fn copy_files_from(
         .await
         .map_err(|e| Error::BuildFailure(e.to_string()))?
         .concat();
-    let files_content = files_from_tar_gz(compressed_data)?;
+    save_files_from_tar(compressed_data, specified_output_path, build_log)
+}
+
+/// Saves the files in the tar archive downloaded from a container to the output path. It fails with the build log if
+/// there is no file in the archive.
+pub(crate) fn save_files_from_tar(
+    tar_bytes: Vec<u8>,
+    specified_output_path: Option<PathBuf>,
+    build_log: String,
+) -> Result<(), Error> {
+    let files_content = files_from_tar_gz(tar_bytes)?;
 
     if files_content.is_empty() {
         return Err(Error::BuildFailureWithLogs(build_log));
@@ -200,27 +355,74 @@ pub async fn copy_files_from(
 }
 
 /// Build contract by executing commands in docker container, including `Cargo`, `wasm-opt` and `wasm-snip`.
+/// The cargo output `cargo_wasm_file` is saved as `wasm_file` in the output folder.
 /// Return the output folder path and the build logs if success.
 pub async fn build_contracts(
     docker: &Docker,
     container_name: &str,
     source_path: PathBuf,
-    locked: bool,
+    options: &BuildOptions,
+    cargo_wasm_file: &str,
     wasm_file: &str,
 ) -> Result<(String, String), Error> {
-    let source_path_str = source_path.to_str().unwrap()
-        .replace(':', "")
-        .replace('\\', "/")
-        .replace(' ', "_");
-    let working_folder_code = format!("/{source_path_str}").to_string();
-    let working_folder_build =
-        format!("/{source_path_str}/target/wasm32-unknown-unknown/release").to_string();
-    let output_folder = "/result";
-    let output_file = format!("{output_folder}/{wasm_file}").to_string();
+    let commands = build_commands(&source_path, options, cargo_wasm_file, wasm_file);
+
+    let (working_dir, cmd) = &commands.cargo_build;
+    let build_log = execute(
+        docker,
+        container_name,
+        Some(working_dir),
+        cmd.iter().map(String::as_str).collect(),
+        true,
+        None
+    )
+    .await
+    .map_err(|e| Error::BuildFailure(e.to_string()))?;
+
+    if options.frozen && crate::cargo::is_lockfile_outdated(&build_log) {
+        return Err(Error::LockfileOutdated(build_log));
+    }
+
+    for (working_dir, cmd) in &commands.post_process {
+        execute(
+            docker,
+            container_name,
+            Some(working_dir),
+            cmd.iter().map(String::as_str).collect(),
+            false,
+            Some(DOCKER_EXEC_TIME_LIMIT)
+        )
+        .await
+        .map_err(|e| Error::BuildFailure(e.to_string()))?;
+    }
+
+    Ok((OUTPUT_FOLDER.to_string(), build_log))
+}
+
+/// Commands to build a contract in a container, with the working folders to execute them in.
+pub(crate) struct BuildCommands {
+    /// Cargo build. Its output is the build log.
+    pub cargo_build: (String, Vec<String>),
+    /// `wasm-opt` and `wasm-snip`, and moving the output files to [OUTPUT_FOLDER] (and [SIZE_REPORT_FOLDER]).
+    pub post_process: Vec<(String, Vec<String>)>,
+}
+
+/// Returns the commands to build the contract copied from `source_path` in a container. The cargo output
+/// `cargo_wasm_file` is saved as `wasm_file` in [OUTPUT_FOLDER].
+pub(crate) fn build_commands(
+    source_path: &Path,
+    options: &BuildOptions,
+    cargo_wasm_file: &str,
+    wasm_file: &str,
+) -> BuildCommands {
+    let source_path_str = container_path(source_path.to_str().unwrap());
+    let working_folder_code = format!("/{source_path_str}");
+    let working_folder_build = format!("/{source_path_str}/target/wasm32-unknown-unknown/release");
+    let output_file = format!("{OUTPUT_FOLDER}/{wasm_file}");
 
     // Does not set "--locked" if the Cargo.lock file does not exist.
-    let use_cargo_lock = locked && source_path.join("Cargo.lock").exists();
-    let cmd_cargo_build = if use_cargo_lock {
+    let use_cargo_lock = (options.locked || options.frozen) && source_path.join("Cargo.lock").exists();
+    let mut cmd_cargo_build = if use_cargo_lock {
         vec![
             "cargo",
             "build",
@@ -238,17 +440,10 @@ pub async fn build_contracts(
             "--release",
         ]
     };
-
-    let build_log = execute(
-        docker,
-        container_name,
-        Some(&working_folder_code),
-        cmd_cargo_build,
-        true,
-        None
-    )
-    .await
-    .map_err(|e| Error::BuildFailure(e.to_string()))?;
+    let features = options.features.join(",");
+    if !features.is_empty() {
+        cmd_cargo_build.extend(["--features", &features]);
+    }
 
     let mut cmds = vec![
         (
@@ -260,7 +455,7 @@ pub async fn build_contracts(
             vec![
                 "/root/bin/wasm-opt",
                 "-Oz",
-                wasm_file,
+                cargo_wasm_file,
                 "--output",
                 "temp.wasm",
             ],
@@ -286,37 +481,95 @@ pub async fn build_contracts(
                 "optimized.wasm",
             ],
         ),
-        (&working_folder_build, vec!["mkdir", "-p", output_folder]),
+        (&working_folder_build, vec!["mkdir", "-p", OUTPUT_FOLDER]),
         (
             &working_folder_build,
             vec!["mv", "optimized.wasm", &output_file],
         ),
+        // Save Cargo.lock to output folder
+        (
+            &working_folder_code,
+            vec!["mv", "Cargo.lock", OUTPUT_FOLDER]
+        ),
     ];
 
-    // Save Cargo.lock to output folder if applicable
-    if locked {
-        cmds.push(
+    // Keep the intermediate binaries for size report, including an optimized binary with name section.
+    // The cargo output is kept with the name of the compiled contract.
+    let size_report_cargo_wasm = format!("{SIZE_REPORT_FOLDER}/{wasm_file}");
+    if options.size_report {
+        cmds.extend([
             (
-                &working_folder_code,
-                vec!["mv", "Cargo.lock", output_folder]
-            )
-        );
+                &working_folder_build,
+                vec![
+                    "/root/bin/wasm-opt",
+                    "-Oz",
+                    "-g",
+                    cargo_wasm_file,
+                    "--output",
+                    crate::size::NAMED_WASM,
+                ],
+            ),
+            (&working_folder_build, vec!["mkdir", "-p", SIZE_REPORT_FOLDER]),
+            (
+                &working_folder_build,
+                vec!["cp", cargo_wasm_file, &size_report_cargo_wasm],
+            ),
+            (
+                &working_folder_build,
+                vec![
+                    "cp",
+                    crate::size::OPTIMIZED_WASM,
+                    crate::size::SNIPPED_WASM,
+                    crate::size::NAMED_WASM,
+                    SIZE_REPORT_FOLDER,
+                ],
+            ),
+        ]);
     }
 
-    for (working_dir, cmd) in cmds {
-        execute(
-            docker,
-            container_name,
-            Some(working_dir),
-            cmd,
-            false,
-            Some(DOCKER_EXEC_TIME_LIMIT)
-        )
-        .await
-        .map_err(|e| Error::BuildFailure(e.to_string()))?;
+    let to_owned = |(working_dir, cmd): (&String, Vec<&str>)| {
+        (working_dir.clone(), cmd.into_iter().map(str::to_string).collect())
+    };
+    BuildCommands {
+        cargo_build: to_owned((&working_folder_code, cmd_cargo_build)),
+        post_process: cmds.into_iter().map(to_owned).collect(),
     }
+}
+
+/// Returns the path in the container that the files at `source_path` are copied to.
+pub(crate) fn container_path(source_path: &str) -> String {
+    source_path
+        .replace(':', "")
+        .replace('\\', "/")
+        .replace(' ', "_")
+        .trim_start_matches('/')
+        .to_string() // Remove the starting "/" for linux file path format.
+}
 
-    Ok((output_folder.to_string(), build_log))
+/// Returns the digest of the docker image (e.g. `parallelchainlab/pchain_compile@sha256:...`), if available. The image
+/// built locally has no digest, so its ID (e.g. `sha256:...`) is returned instead.
+pub async fn image_digest(docker: &Docker, image: &str) -> Option<String> {
+    let inspect = docker.inspect_image(image).await.ok()?;
+    inspect
+        .repo_digests
+        .and_then(|digests| digests.into_iter().next())
+        .or(inspect.id)
+}
+
+/// Returns the output of `rustc --version` in the docker container, if available.
+pub async fn rustc_version(docker: &Docker, container_name: &str) -> Option<String> {
+    execute(
+        docker,
+        container_name,
+        None,
+        vec!["rustc", "--version"],
+        true,
+        Some(DOCKER_EXEC_TIME_LIMIT),
+    )
+    .await
+    .ok()
+    .map(|output| output.trim().to_string())
+    .filter(|output| !output.is_empty())
 }
 
 /// Force stop and remove a container
@@ -442,10 +695,80 @@ async fn execute(
                 }
             }
 
-            return Ok(log_outputs)
+            Ok(log_outputs)
         },
         bollard::exec::StartExecResults::Detached => {
-            return Err(Error::BuildFailure("Execution Result Not Attached".to_string()));
+            Err(Error::BuildFailure("Execution Result Not Attached".to_string()))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::DockerConfig;
+
+    #[test]
+    fn docker_host() {
+        let runtime_dir = std::env::temp_dir().join("pchain_compile_docker_host");
+        let podman_socket = runtime_dir.join("podman").join("podman.sock");
+        let docker_socket = runtime_dir.join("docker.sock");
+        std::fs::create_dir_all(podman_socket.parent().unwrap()).unwrap();
+        std::fs::write(&podman_socket, "").unwrap();
+        let runtime_dir_env = runtime_dir.to_str().unwrap().to_string();
+        let env = |docker_host: Option<&str>| {
+            let runtime_dir_env = runtime_dir_env.clone();
+            let docker_host = docker_host.map(str::to_string);
+            move |name: &str| match name {
+                "XDG_RUNTIME_DIR" => Some(runtime_dir_env.clone()),
+                "DOCKER_HOST" => docker_host.clone(),
+                _ => None,
+            }
+        };
+        let default_config = DockerConfig::default();
+        let host_config = DockerConfig {
+            host: Some("tcp://127.0.0.1:2375".to_string()),
+            ..Default::default()
+        };
+
+        // The socket of rootless Podman is used if the docker socket does not exist.
+        let podman_host = super::docker_host_with(&default_config, env(None), &docker_socket);
+        std::fs::write(&docker_socket, "").unwrap();
+        let docker_host = super::docker_host_with(&default_config, env(None), &docker_socket);
+        let env_host = super::docker_host_with(&default_config, env(Some("unix:///tmp/docker.sock")), &docker_socket);
+        let config_host = super::docker_host_with(&host_config, env(Some("unix:///tmp/docker.sock")), &docker_socket);
+        let missing_sockets = super::docker_host_with(&default_config, |_| None, Path::new("/nonexistent/docker.sock"));
+        let _ = std::fs::remove_dir_all(&runtime_dir);
+
+        assert_eq!(podman_host, format!("unix://{}", podman_socket.display()));
+        assert_eq!(docker_host, format!("unix://{}", docker_socket.display()));
+        assert_eq!(env_host, "unix:///tmp/docker.sock");
+        assert_eq!(config_host, "tcp://127.0.0.1:2375");
+        if !Path::new(super::PODMAN_SOCKET).exists() {
+            assert_eq!(missing_sockets, super::DEFAULT_DOCKER_HOST);
+        }
+    }
+
+    #[test]
+    fn tls_cert_path() {
+        let env = |tls_verify: &'static str, cert_path: Option<&'static str>| {
+            move |name: &str| match name {
+                "DOCKER_TLS_VERIFY" => Some(tls_verify.to_string()),
+                "DOCKER_CERT_PATH" => cert_path.map(str::to_string),
+                "HOME" => Some("/home/user".to_string()),
+                _ => None,
+            }
+        };
+        let default_config = DockerConfig::default();
+        let cert_path_config = DockerConfig {
+            tls_cert_path: Some(PathBuf::from("/certs")),
+            ..Default::default()
+        };
+
+        assert_eq!(super::tls_cert_path_with(&default_config, env("", Some("/env_certs"))), None);
+        assert_eq!(super::tls_cert_path_with(&default_config, env("1", Some("/env_certs"))), Some(PathBuf::from("/env_certs")));
+        assert_eq!(super::tls_cert_path_with(&default_config, env("1", None)), Some(PathBuf::from("/home/user/.docker")));
+        assert_eq!(super::tls_cert_path_with(&cert_path_config, env("1", Some("/env_certs"))), Some(PathBuf::from("/certs")));
+    }
+}
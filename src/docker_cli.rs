@@ -0,0 +1,210 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implements the operations with containers for building contract through a container CLI binary (e.g. `docker`
+//! or `podman`), for environments where the API socket of the daemon is not reachable. The container lifecycle
+//! (pull, create, cp, exec and rm) and the commands executed in the container are the same as in [crate::docker].
+
+use std::{path::PathBuf, process::Stdio, time::Duration};
+
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{
+    docker::{BuildCommands, DOCKER_EXEC_TIME_LIMIT, PCHAIN_COMPILE_IMAGE},
+    error::Error,
+    BuildOptions,
+};
+
+/// Pull docker image from ParallelChain Lab DockerHub. Returns the name of docker image. The name is fully qualified,
+/// so that it is not ambiguous to Podman. If `local_image` is true, the image built locally by
+/// [crate::image::build_image] is used instead, and it fails if the image does not exist.
+pub async fn pull_image(cli: &str, tag: &str, local_image: bool) -> Result<String, Error> {
+    if local_image {
+        let image = crate::image::local_image_name(tag);
+        return match run(cli, &["image", "inspect", &image], None).await {
+            Ok(_) => Ok(image),
+            Err(_) => Err(Error::LocalImageNotFound(image)),
+        };
+    }
+
+    let image = image_name(tag);
+    run(cli, &["pull", &image], None).await?;
+    Ok(image)
+}
+
+/// Returns the fully qualified name of the docker image of the tag.
+pub fn image_name(tag: &str) -> String {
+    format!("docker.io/{PCHAIN_COMPILE_IMAGE}:{tag}")
+}
+
+/// Creates and starts a container with the Image pulled from ParallelChain Lab DockerHub
+pub async fn start_container(cli: &str, container_name: &str, image: &str) -> Result<(), Error> {
+    run(
+        cli,
+        &["create", "--interactive", "--tty", "--privileged", "--name", container_name, image],
+        None,
+    )
+    .await?;
+    run(cli, &["start", container_name], None).await?;
+    Ok(())
+}
+
+/// Copy Files from source path to docker container. The files are streamed to `cp` as a tar archive.
+pub async fn copy_files(cli: &str, container_name: &str, source_path: &str) -> Result<(), Error> {
+    let save_to_path = crate::docker::container_path(source_path);
+    let mut tar = tar::Builder::new(Vec::new());
+    tar.append_dir_all(&save_to_path, source_path)
+        .map_err(|e| Error::DockerCliFailure(format!("Fail to archive {source_path}: {e}")))?;
+    let archive = tar
+        .into_inner()
+        .map_err(|e| Error::DockerCliFailure(format!("Fail to archive {source_path}: {e}")))?;
+
+    run(cli, &["cp", "-", &format!("{container_name}:/")], Some(archive)).await?;
+    Ok(())
+}
+
+/// Copy files from docker container to a specified output path. The output path is None, current path becomes the output path.
+pub async fn copy_files_from(
+    cli: &str,
+    container_name: &str,
+    container_path: &str,
+    specified_output_path: Option<PathBuf>,
+    build_log: String,
+) -> Result<(), Error> {
+    let archive = run(cli, &["cp", &format!("{container_name}:{container_path}"), "-"], None)
+        .await
+        .map_err(|e| match e {
+            Error::DockerCliFailure(msg) => Error::BuildFailure(msg),
+            e => e,
+        })?;
+    crate::docker::save_files_from_tar(archive, specified_output_path, build_log)
+}
+
+/// Build contract by executing commands in docker container, including `Cargo`, `wasm-opt` and `wasm-snip`.
+/// The cargo output `cargo_wasm_file` is saved as `wasm_file` in the output folder.
+/// Return the output folder path and the build logs if success.
+pub async fn build_contracts(
+    cli: &str,
+    container_name: &str,
+    source_path: PathBuf,
+    options: &BuildOptions,
+    cargo_wasm_file: &str,
+    wasm_file: &str,
+) -> Result<(String, String), Error> {
+    let BuildCommands { cargo_build, post_process } =
+        crate::docker::build_commands(&source_path, options, cargo_wasm_file, wasm_file);
+
+    let (working_dir, cmd) = &cargo_build;
+    let build_log = execute(cli, container_name, Some(working_dir), cmd, None).await?;
+
+    if options.frozen && crate::cargo::is_lockfile_outdated(&build_log) {
+        return Err(Error::LockfileOutdated(build_log));
+    }
+
+    for (working_dir, cmd) in &post_process {
+        execute(cli, container_name, Some(working_dir), cmd, Some(DOCKER_EXEC_TIME_LIMIT)).await?;
+    }
+
+    Ok((crate::docker::OUTPUT_FOLDER.to_string(), build_log))
+}
+
+/// Returns the digest of the docker image (e.g. `parallelchainlab/pchain_compile@sha256:...`), if available. The image
+/// built locally has no digest, so its ID is returned instead.
+pub async fn image_digest(cli: &str, image: &str) -> Option<String> {
+    let format = "{{if .RepoDigests}}{{index .RepoDigests 0}}{{else}}{{.Id}}{{end}}";
+    let output = run(cli, &["image", "inspect", "--format", format, image], None)
+        .await
+        .ok()?;
+    let digest = String::from_utf8_lossy(&output).trim().to_string();
+    (!digest.is_empty()).then_some(digest)
+}
+
+/// Returns the output of `rustc --version` in the docker container, if available.
+pub async fn rustc_version(cli: &str, container_name: &str) -> Option<String> {
+    let cmd = ["rustc".to_string(), "--version".to_string()];
+    execute(cli, container_name, None, &cmd, Some(DOCKER_EXEC_TIME_LIMIT))
+        .await
+        .ok()
+        .map(|output| output.trim().to_string())
+        .filter(|output| !output.is_empty())
+}
+
+/// Force stop and remove a container
+pub async fn remove_container(cli: &str, container_name: &str) -> Result<(), Error> {
+    run(cli, &["rm", "--force", "--volumes", container_name], None)
+        .await
+        .map_err(|_| Error::ArtifactRemovalFailure)?;
+    Ok(())
+}
+
+/// Executes a command in the container, and returns its output (stderr followed by stdout). As with [crate::docker],
+/// the exit code of the command is not checked, because a failed build is found by its missing output files.
+async fn execute(
+    cli: &str,
+    container_name: &str,
+    working_dir: Option<&str>,
+    cmd: &[String],
+    timeout_secs: Option<u64>,
+) -> Result<String, Error> {
+    let mut args = vec!["exec"];
+    if let Some(working_dir) = working_dir {
+        args.extend(["--workdir", working_dir]);
+    }
+    args.push(container_name);
+    args.extend(cmd.iter().map(String::as_str));
+
+    let output = command(cli, &args, false).output();
+    let output = match timeout_secs {
+        Some(timeout) => tokio::time::timeout(Duration::from_secs(timeout), output)
+            .await
+            .map_err(|_| Error::BuildTimeout)?,
+        None => output.await,
+    }
+    .map_err(|e| Error::DockerCliFailure(format!("Fail to run {cli}: {e}")))?;
+
+    Ok(format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    ))
+}
+
+/// Runs the CLI with `args`, writing `stdin` to its standard input. It returns the standard output, or fails with the
+/// standard error if the CLI exits with an error.
+async fn run(cli: &str, args: &[&str], stdin: Option<Vec<u8>>) -> Result<Vec<u8>, Error> {
+    let mut child = command(cli, args, stdin.is_some())
+        .spawn()
+        .map_err(|e| Error::DockerCliFailure(format!("Fail to run {cli}: {e}")))?;
+    if let (Some(content), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin
+            .write_all(&content)
+            .await
+            .map_err(|e| Error::DockerCliFailure(format!("Fail to write to {cli}: {e}")))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| Error::DockerCliFailure(format!("Fail to run {cli}: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::DockerCliFailure(format!(
+            "\"{cli} {}\" failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Returns the command to run the CLI. The process is killed if the command is dropped, e.g. on cancellation.
+fn command(cli: &str, args: &[&str], with_stdin: bool) -> Command {
+    let mut command = Command::new(cli);
+    command
+        .args(args)
+        .stdin(if with_stdin { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
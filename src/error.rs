@@ -19,6 +19,9 @@ pub enum Error {
     #[error("The building process took too long.")]
     BuildTimeout,
 
+    #[error("The building process was cancelled.")]
+    BuildCancelled,
+
     #[error("Docker daemon service did not respond.")]
     DockerDaemonFailure,
 
@@ -31,6 +34,9 @@ pub enum Error {
     #[error("Source code path not valid.")]
     InvalidSourcePath,
 
+    #[error("Library target not valid.")]
+    InvalidLibraryTarget(String),
+
     #[error("Destination path not valid.")]
     InvalidDestinationPath,
 
@@ -42,6 +48,63 @@ pub enum Error {
 
     #[error("Unknown docker image tag")]
     UnkownDockerImageTag(String),
+
+    #[error("Fail to build the docker image.")]
+    ImageBuildFailure(String),
+
+    #[error("Local docker image not found.")]
+    LocalImageNotFound(String),
+
+    #[error("Invalid settings of the connection to docker daemon.")]
+    InvalidDockerConnection(String),
+
+    #[error("Docker CLI did not respond as expected.")]
+    DockerCliFailure(String),
+
+    #[error("Fail to build in the sandbox.")]
+    SandboxFailure(String),
+
+    #[error("Cargo.lock not found.")]
+    LockfileNotFound,
+
+    #[error("Cargo.lock needs to be updated.")]
+    LockfileOutdated(String),
+
+    #[error("Wasm file not valid.")]
+    InvalidWasmFile(String),
+
+    #[error("The settings conflict with the build metadata of the binary.")]
+    BuildMetadataConflict(String),
+
+    #[error("The contract does not satisfy the rules of ParallelChain runtime.")]
+    ContractValidationFailure(Vec<crate::validate::Violation>),
+
+    #[error("The contract contains floating-point instructions.")]
+    FloatInstructionsFound(Vec<crate::validate::FloatUsage>),
+
+    #[error("The contract exceeds the size budget.")]
+    WasmSizeExceeded { size: usize, max_size: usize },
+
+    #[error("The contract grows more than allowed from the baseline.")]
+    WasmSizeGrowthExceeded { size: usize, baseline_size: usize, max_growth_percent: f64 },
+
+    #[error("Fails to generate the ABI of the contract.")]
+    AbiGenerationFailure(String),
+
+    #[error("Fails to run the contract.")]
+    ContractRunFailure(String),
+
+    #[error("Fails to access the build cache.")]
+    CacheFailure(String),
+
+    #[error("Fails to watch the source code.")]
+    WatchFailure(String),
+
+    #[error("Fails to create the contract project.")]
+    ScaffoldFailure(String),
+
+    #[error("Fails to read the project configuration.")]
+    ProjectConfigFailure(String),
 }
 
 impl Error {
@@ -50,14 +113,35 @@ impl Error {
             Error::ArtifactRemovalFailure => "The compilation was successful, but pchain-compile failed to stop its Docker containers. Please remove them manually.".to_string(), 
             Error::BuildFailure(e) => format!("\nDetails: {e}\nPlease rectify the errors and build your source code again."),
             Error::BuildFailureWithLogs(log) => format!("There maybe some problems in the source code.\nBuilding log is as follows:\n\n{log}\n"),
-            Error::BuildTimeout => format!("The time used in the building process is abnormal. It is possible that the contract code is extraordinarily  large, or there is something wrong in your building environment (e.g. docker)."),
-            Error::DockerDaemonFailure => "Failed to compile.\nDetails: Docker Daemon Failure. Check if Docker is running on your machine and confirm read/write access privileges.".to_string(),
+            Error::BuildTimeout => "The time used in the building process is abnormal. It is possible that the contract code is extraordinarily  large, or there is something wrong in your building environment (e.g. docker).".to_string(),
+            Error::BuildCancelled => "\nDetails: The docker containers and the temporary folders created in the building process were removed.".to_string(),
+            Error::DockerDaemonFailure => "Failed to compile.\nDetails: Docker Daemon Failure. Check if Docker is running on your machine and confirm read/write access privileges. To use another daemon (e.g. Podman), set its address by the environment variable DOCKER_HOST or the option \"--docker-host\".".to_string(),
             Error::ManifestFailure => "Failed to compile.\nDetails: Manifest File Not Found. Check if the manifest file exists on the source code path.".to_string(),
             Error::InvalidSourcePath => "Failed to compile.\nDetails: Source Code Path Not Valid. Check if you have provided the correct path to your source code directory and confirm write access privileges.".to_string(),
+            Error::InvalidLibraryTarget(e) => format!("Failed to compile.\nDetails: {e}\nA contract is built from the library target of the crate. Please add the following section to \"Cargo.toml\":\n\n[lib]\ncrate-type = [\"cdylib\"]\n"),
             Error::InvalidDestinationPath => "\nDetails: Destination Path Not Valid. Check if you have provided the correct path to save your optimized WASM binary and confirm write access privileges.".to_string(),
             Error::InvalidDependencyPath => "\nDetails: Dependency Paths Specified Within Smart Contract Crate Not Valid. Check if you have provided the correct path to the dependencies on your source".to_string(),
             Error::CreateTempDir => "\nDetails: The compilation process requires creating a temporary folder in your machine. Please check if the program has write permission to create folder.".to_string(),
             Error::UnkownDockerImageTag(tag) => format!("\nDetails: The docker image tag ({tag}) is not recognised. Please choose tag from dockerhub https://hub.docker.com/r/parallelchainlab/pchain_compile"),
+            Error::ImageBuildFailure(log) => format!("\nDetails: Building log is as follows:\n\n{log}\n"),
+            Error::LocalImageNotFound(image) => format!("\nDetails: The docker image {image} does not exist. Build it by \"pchain_compile image build\" with the same tag, or build the contract without \"--docker-local-image\" to pull the image from DockerHub."),
+            Error::InvalidDockerConnection(msg) => format!("\nDetails: {msg}"),
+            Error::SandboxFailure(msg) => format!("Failed to compile.\nDetails: {msg}\nCheck if bubblewrap (bwrap) is installed and unprivileged user namespaces are enabled."),
+            Error::DockerCliFailure(msg) => format!("Failed to compile.\nDetails: {msg}\nCheck if the container CLI (e.g. docker or podman) is installed and can run containers under current user."),
+            Error::LockfileNotFound => "Failed to compile.\nDetails: Cargo.lock Not Found. Building with strict lockfile enforcement requires the file Cargo.lock on the source code path. Run \"cargo generate-lockfile\" to create it.".to_string(),
+            Error::LockfileOutdated(log) => format!("\nDetails: The Cargo.lock on the source code path is out of date with the manifest file. Please update the Cargo.lock and build your source code again.\nBuilding log is as follows:\n\n{log}\n"),
+            Error::InvalidWasmFile(e) => format!("\nDetails: {e}\nCheck if you have provided the correct path to a WebAssembly binary file."),
+            Error::BuildMetadataConflict(e) => format!("\nDetails: {e}\nOmit the option to rebuild with the setting recorded in the binary."),
+            Error::ContractValidationFailure(violations) => format!("\nDetails: The contract would be rejected when it is deployed. Violations are as follows:\n\n{}\n", violations.iter().map(|v| format!("- {v}")).collect::<Vec<_>>().join("\n")),
+            Error::FloatInstructionsFound(usages) => format!("\nDetails: Floating-point operations are non-deterministic and may cause the contract to be rejected. The instructions are used in the following functions:\n\n{}\n", usages.iter().map(|u| format!("- {u}")).collect::<Vec<_>>().join("\n")),
+            Error::WasmSizeExceeded { size, max_size } => format!("\nDetails: The contract is {size} bytes, which exceeds the maximum size of {max_size} bytes. Please reduce the size of the contract, or raise the size budget."),
+            Error::WasmSizeGrowthExceeded { size, baseline_size, max_growth_percent } => format!("\nDetails: The contract is {size} bytes, which is {:.2}% larger than the baseline of {baseline_size} bytes. The maximum allowed growth is {max_growth_percent}%.", (*size as f64 - *baseline_size as f64) * 100.0 / *baseline_size as f64),
+            Error::AbiGenerationFailure(e) => format!("\nDetails: {e}\nThe ABI is generated by parsing the source code, starting from the library root and following the \"mod\" declarations."),
+            Error::ContractRunFailure(e) => format!("\nDetails: {e}"),
+            Error::CacheFailure(e) => format!("\nDetails: {e}\nCheck if the program has write permission to the cache folder {}.", crate::cache::cache_dir().display()),
+            Error::WatchFailure(e) => format!("\nDetails: {e}\nCheck if the source code path and the dependency paths exist, and the system limit of watched files is not reached."),
+            Error::ScaffoldFailure(e) => format!("\nDetails: {e}"),
+            Error::ProjectConfigFailure(e) => format!("\nDetails: {e}\nCheck the settings in \"{}\" or the table [package.metadata.pchain] in \"Cargo.toml\".", crate::project::PROJECT_CONFIG_FILE),
         }
     }
 }
@@ -0,0 +1,80 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of building the `pchain_compile` docker image locally, for environments that cannot pull it from
+//! DockerHub, or to audit how the image is made. The image is built through the docker daemon from the Dockerfile
+//! embedded in `pchain_compile`, with the versions of the components of the tag. It is registered under a name
+//! different from the image on DockerHub ([LOCAL_IMAGE]), and building contracts uses it only if
+//! [crate::DockerConfig::local_image] is set, so that it never replaces the official image silently.
+
+use std::collections::HashMap;
+
+use crate::{error::Error, DockerConfig};
+
+/// The Dockerfile of the `pchain_compile` docker image. The versions of the components are set by build arguments.
+pub const DOCKERFILE: &str = include_str!("../docker_image/Dockerfile");
+
+/// Repository of the docker image built by [build_image]. The registry is `localhost`, so that the name is the same in
+/// Docker and Podman, and the image cannot be mistaken for one on DockerHub.
+pub const LOCAL_IMAGE: &str = "localhost/pchain_compile_local";
+
+/// Versions of the components installed in a tag of the docker image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageVersions {
+    /// Version of the Rust toolchain, i.e. the tag of the base image `rust`.
+    pub rustc: &'static str,
+    /// Version of the crate `wasm-snip`.
+    pub wasm_snip: &'static str,
+    /// Release version of Binaryen, which provides `wasm-opt`.
+    pub binaryen: &'static str,
+}
+
+/// Returns the versions of the components of the docker image tag, or None if the tag is unknown.
+pub fn image_versions(tag: &str) -> Option<ImageVersions> {
+    crate::docker::PCHAIN_COMPILE_IMAGE_TAGS
+        .iter()
+        .find(|(image_tag, _)| *image_tag == tag)
+        .map(|(_, versions)| *versions)
+}
+
+/// `build_image` builds the docker image of the tag in `docker_config` (or the default tag) from [DOCKERFILE], through
+/// the docker daemon in `docker_config`. `on_log` is called with the output of each building step. It returns the
+/// name of the image, e.g. `localhost/pchain_compile_local:0.4.3`, which is used to build contracts instead of the
+/// image pulled from DockerHub if [DockerConfig::local_image] is set.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` image build --tag `tag`
+pub async fn build_image<F: FnMut(&str)>(docker_config: DockerConfig, on_log: F) -> Result<String, Error> {
+    let tag = crate::backend::docker_image_tag(&docker_config)?;
+    let versions = image_versions(&tag).ok_or(Error::UnkownDockerImageTag(tag.clone()))?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(DOCKERFILE.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    let mut context = tar::Builder::new(Vec::new());
+    context
+        .append_data(&mut header, "Dockerfile", DOCKERFILE.as_bytes())
+        .and_then(|_| context.finish())
+        .map_err(|e| Error::ImageBuildFailure(e.to_string()))?;
+    let context = context.into_inner().map_err(|e| Error::ImageBuildFailure(e.to_string()))?;
+
+    let build_args = HashMap::from([
+        ("RUST_VERSION".to_string(), versions.rustc.to_string()),
+        ("WASM_SNIP_VERSION".to_string(), versions.wasm_snip.to_string()),
+        ("BINARYEN_VERSION".to_string(), versions.binaryen.to_string()),
+    ]);
+
+    let image = local_image_name(&tag);
+    let docker = crate::docker::connect(&docker_config).await?;
+    crate::docker::build_image(&docker, &image, context, build_args, on_log).await?;
+    Ok(image)
+}
+
+/// Returns the name of the docker image of the tag built by [build_image].
+pub fn local_image_name(tag: &str) -> String {
+    format!("{LOCAL_IMAGE}:{tag}")
+}
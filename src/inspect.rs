@@ -0,0 +1,282 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of inspecting a compiled contract. It lists the imports, exports, memory and table limits,
+//! data segments and custom sections of a WebAssembly binary, as well as the build metadata embedded in it
+//! (see [crate::metadata]).
+
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+use walrus::{ExportItem, FunctionId, ImportKind, Module};
+
+use crate::{error::Error, metadata::BuildMetadata};
+
+/// Summary of the contents of a WebAssembly binary.
+#[derive(Debug, Clone, Serialize)]
+pub struct WasmInspection {
+    /// Size of the binary in bytes.
+    pub size: usize,
+    /// Imports grouped by the name of the host module (e.g. `env`).
+    pub imports: BTreeMap<String, Vec<ImportInfo>>,
+    /// Exports in the order they are defined.
+    pub exports: Vec<ExportInfo>,
+    /// Limits of the linear memories.
+    pub memories: Vec<LimitsInfo>,
+    /// Limits of the tables.
+    pub tables: Vec<LimitsInfo>,
+    /// Data segments in the order they are defined.
+    pub data_segments: Vec<DataSegmentInfo>,
+    /// Custom sections in the order they appear in the binary.
+    pub custom_sections: Vec<CustomSectionInfo>,
+    /// Build metadata embedded in the binary, if any.
+    pub metadata: Option<BuildMetadata>,
+}
+
+/// An imported item.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportInfo {
+    pub name: String,
+    /// One of "function", "table", "memory" or "global".
+    pub kind: String,
+    /// Signature of a function (e.g. `(i32, i32) -> i64`), or value type of a global.
+    pub signature: Option<String>,
+}
+
+/// An exported item.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportInfo {
+    pub name: String,
+    /// One of "function", "table", "memory" or "global".
+    pub kind: String,
+    /// Signature of a function (e.g. `(i32, i32) -> i64`), or value type of a global.
+    pub signature: Option<String>,
+}
+
+/// Limits of a linear memory (in pages of 64 KiB) or a table (in elements).
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitsInfo {
+    pub initial: u32,
+    pub maximum: Option<u32>,
+    /// Whether the item is imported instead of defined in the module.
+    pub imported: bool,
+}
+
+/// A data segment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataSegmentInfo {
+    /// Size of the data in bytes.
+    pub size: usize,
+    /// Absolute address in the linear memory for active segments. None for passive segments, or
+    /// active segments placed relative to a global.
+    pub offset: Option<u32>,
+}
+
+/// A custom section.
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomSectionInfo {
+    pub name: String,
+    /// Size of the section content in bytes.
+    pub size: usize,
+}
+
+/// `inspect_wasm` reads the WebAssembly binary at `wasm_path` and summarizes its contents.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` inspect `wasm_path`
+pub fn inspect_wasm(wasm_path: &Path) -> Result<WasmInspection, Error> {
+    let wasm = std::fs::read(wasm_path)
+        .map_err(|e| Error::InvalidWasmFile(format!("Fail to read {}: {e}", wasm_path.display())))?;
+    inspect_bytes(&wasm)
+}
+
+/// Summarizes the contents of a WebAssembly binary. See [inspect_wasm].
+pub(crate) fn inspect_bytes(wasm: &[u8]) -> Result<WasmInspection, Error> {
+    let module = walrus::ModuleConfig::new()
+        .parse(wasm)
+        .map_err(|e| Error::InvalidWasmFile(format!("{:?}", e)))?;
+
+    let mut imports: BTreeMap<String, Vec<ImportInfo>> = BTreeMap::new();
+    for import in module.imports.iter() {
+        let (kind, signature) = match import.kind {
+            ImportKind::Function(id) => ("function", Some(function_signature(&module, id))),
+            ImportKind::Table(_) => ("table", None),
+            ImportKind::Memory(_) => ("memory", None),
+            ImportKind::Global(id) => ("global", Some(module.globals.get(id).ty.to_string())),
+        };
+        imports.entry(import.module.clone()).or_default().push(ImportInfo {
+            name: import.name.clone(),
+            kind: kind.to_string(),
+            signature,
+        });
+    }
+
+    let exports = module
+        .exports
+        .iter()
+        .map(|export| {
+            let (kind, signature) = match export.item {
+                ExportItem::Function(id) => ("function", Some(function_signature(&module, id))),
+                ExportItem::Table(_) => ("table", None),
+                ExportItem::Memory(_) => ("memory", None),
+                ExportItem::Global(id) => ("global", Some(module.globals.get(id).ty.to_string())),
+            };
+            ExportInfo {
+                name: export.name.clone(),
+                kind: kind.to_string(),
+                signature,
+            }
+        })
+        .collect();
+
+    let memories = module
+        .memories
+        .iter()
+        .map(|memory| LimitsInfo {
+            initial: memory.initial,
+            maximum: memory.maximum,
+            imported: memory.import.is_some(),
+        })
+        .collect();
+
+    let tables = module
+        .tables
+        .iter()
+        .map(|table| LimitsInfo {
+            initial: table.initial,
+            maximum: table.maximum,
+            imported: table.import.is_some(),
+        })
+        .collect();
+
+    let data_segments = module
+        .data
+        .iter()
+        .map(|data| DataSegmentInfo {
+            size: data.value.len(),
+            offset: match &data.kind {
+                walrus::DataKind::Active(walrus::ActiveData {
+                    location: walrus::ActiveDataLocation::Absolute(offset),
+                    ..
+                }) => Some(*offset),
+                _ => None,
+            },
+        })
+        .collect();
+
+    let mut custom_sections = vec![];
+    for section in crate::wasm::sections(wasm)? {
+        if section.id == 0 {
+            custom_sections.push(CustomSectionInfo {
+                size: crate::wasm::custom_section_content(&section)?.len(),
+                name: section.name,
+            });
+        }
+    }
+
+    Ok(WasmInspection {
+        size: wasm.len(),
+        imports,
+        exports,
+        memories,
+        tables,
+        data_segments,
+        custom_sections,
+        metadata: crate::metadata::read_metadata(wasm)?,
+    })
+}
+
+/// Returns the signature of a function in the form of `(i32, i32) -> i64`.
+pub(crate) fn function_signature(module: &Module, id: FunctionId) -> String {
+    let ty = module.types.get(module.funcs.get(id).ty());
+    let join = |types: &[walrus::ValType]| {
+        types
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match ty.results() {
+        [] => format!("({})", join(ty.params())),
+        [result] => format!("({}) -> {}", join(ty.params()), result),
+        results => format!("({}) -> ({})", join(ty.params()), join(results)),
+    }
+}
+
+/// Formats an imported or exported item, e.g. `function set(i32, i32, i32, i32)` or `global g: i32`.
+fn item(kind: &str, name: &str, signature: &Option<String>) -> String {
+    match signature {
+        Some(signature) if kind == "function" => format!("{kind} {name}{signature}"),
+        Some(signature) => format!("{kind} {name}: {signature}"),
+        None => format!("{kind} {name}"),
+    }
+}
+
+impl Display for WasmInspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Size: {} bytes", self.size)?;
+
+        writeln!(f, "\nImports:")?;
+        for (module, imports) in &self.imports {
+            writeln!(f, "  {module}")?;
+            for import in imports {
+                writeln!(f, "    {}", item(&import.kind, &import.name, &import.signature))?;
+            }
+        }
+
+        writeln!(f, "\nExports:")?;
+        for export in &self.exports {
+            writeln!(f, "  {}", item(&export.kind, &export.name, &export.signature))?;
+        }
+
+        let limits = |limits: &LimitsInfo| {
+            format!(
+                "initial {}, maximum {}{}",
+                limits.initial,
+                limits.maximum.map_or("none".to_string(), |max| max.to_string()),
+                if limits.imported { " (imported)" } else { "" }
+            )
+        };
+        writeln!(f, "\nMemories (pages):")?;
+        for memory in &self.memories {
+            writeln!(f, "  {}", limits(memory))?;
+        }
+        writeln!(f, "\nTables (elements):")?;
+        for table in &self.tables {
+            writeln!(f, "  {}", limits(table))?;
+        }
+
+        writeln!(
+            f,
+            "\nData segments: {} bytes in total",
+            self.data_segments.iter().map(|data| data.size).sum::<usize>()
+        )?;
+        for data in &self.data_segments {
+            match data.offset {
+                Some(offset) => writeln!(f, "  {} bytes at offset {}", data.size, offset)?,
+                None => writeln!(f, "  {} bytes", data.size)?,
+            }
+        }
+
+        writeln!(f, "\nCustom sections:")?;
+        for section in &self.custom_sections {
+            writeln!(f, "  {} ({} bytes)", section.name, section.size)?;
+        }
+
+        if let Some(metadata) = &self.metadata {
+            let or_none = |value: &Option<String>| value.clone().unwrap_or("(none)".to_string());
+            writeln!(f, "\nBuild metadata:")?;
+            writeln!(f, "  pchain_compile version: {}", metadata.pchain_compile_version)?;
+            writeln!(f, "  docker image tag: {}", or_none(&metadata.docker_image_tag))?;
+            writeln!(f, "  docker image digest: {}", or_none(&metadata.docker_image_digest))?;
+            writeln!(f, "  rustc version: {}", or_none(&metadata.rustc_version))?;
+            writeln!(f, "  source hash: {}", metadata.source_hash)?;
+            writeln!(f, "  Cargo.lock hash: {}", or_none(&metadata.cargo_lock_hash))?;
+            writeln!(f, "  features: {}", metadata.features.join(", "))?;
+        }
+        Ok(())
+    }
+}
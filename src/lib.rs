@@ -23,17 +23,84 @@
 //! .run()
 //! .await;
 //! ```
+//!
+//! # Example - Run with a build backend
+//! ```no_run
+//! # use std::path::Path;
+//! # async fn example(wasm: Vec<u8>) -> Result<(), pchain_compile::error::Error> {
+//! let backend = pchain_compile::backend::FakeBackend::new(wasm);
+//! let result = pchain_compile::Config {
+//!     source_path: Path::new("/home/user/contract").to_path_buf(),
+//!     ..Default::default()
+//! }
+//! .run_with_backend(&backend, pchain_compile::CancellationToken::new())
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod abi;
+
+pub mod backend;
+pub use backend::BuildBackend;
+
+pub mod batch;
+pub use batch::build_all;
+
+pub mod cache;
 
 pub(crate) mod cargo;
 
+pub mod compat;
+pub use compat::check_compatibility;
+
 pub mod config;
 pub use config::*;
 
+pub use tokio_util::sync::CancellationToken;
+
 pub(crate) mod docker;
 
+pub(crate) mod docker_cli;
+
 pub mod error;
 
 pub(crate) mod manifests;
 
 pub mod build;
 pub use build::build_target;
+
+pub mod image;
+
+pub mod inspect;
+pub use inspect::inspect_wasm;
+
+pub mod metadata;
+
+pub mod optimize;
+pub use optimize::optimize_wasm;
+
+pub mod project;
+pub use project::ProjectConfig;
+
+pub mod run;
+pub use run::run_contract;
+
+pub(crate) mod sandbox;
+
+pub mod scaffold;
+pub use scaffold::{init_contract, new_contract};
+
+pub mod size;
+pub use size::size_report;
+
+pub mod validate;
+pub use validate::validate_wasm;
+
+pub mod verify;
+pub use verify::verify_contract;
+
+pub mod watch;
+pub use watch::watch;
+
+pub(crate) mod wasm;
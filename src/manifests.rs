@@ -41,10 +41,49 @@ pub fn get_dependency_paths(
     Ok(())
 }
 
-/// Return package name from manifest file
-pub fn package_name(current_dir: &Path) -> Result<String, Error> {
+/// Returns the file name of the compiled contract from the library target in the manifest file, i.e. the name of
+/// the library, or the package name with "-" replaced by "_" if it is not set. The library target must exist and
+/// have the crate type "cdylib", otherwise Cargo does not produce a wasm file.
+pub fn wasm_file(current_dir: &Path) -> Result<String, Error> {
+    // The manifest is not completed from the file system, which would replace the crate type and the name of
+    // a library target that does not set its path.
+    let content = std::fs::read(current_dir.join("Cargo.toml")).map_err(|_| Error::ManifestFailure)?;
+    let manifest = Manifest::from_slice(&content).map_err(|_| Error::ManifestFailure)?;
+    let package = manifest.package.ok_or(Error::InvalidLibraryTarget(
+        "The manifest file does not contain the section [package].".to_string(),
+    ))?;
+    let lib = manifest.lib.unwrap_or_default();
+
+    let lib_path = lib.path.unwrap_or("src/lib.rs".to_string());
+    if !current_dir.join(&lib_path).is_file() {
+        return Err(Error::InvalidLibraryTarget(format!(
+            "The library root \"{lib_path}\" is not found."
+        )));
+    }
+    let crate_type = lib.crate_type.unwrap_or_default();
+    if !crate_type.iter().any(|crate_type| crate_type == "cdylib") {
+        return Err(Error::InvalidLibraryTarget(format!(
+            "The crate type of the library is {crate_type:?}, which does not contain \"cdylib\"."
+        )));
+    }
+
+    let lib_name = lib.name.unwrap_or(package.name.replace('-', "_"));
+    Ok(format!("{lib_name}.wasm"))
+}
+
+/// Returns the version of the package from the manifest file.
+pub fn package_version(current_dir: &Path) -> Result<String, Error> {
+    Manifest::from_path(current_dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|f| f.package)
+        .map(|package| package.version)
+        .ok_or(Error::ManifestFailure)
+}
+
+/// Returns the path to the library root (e.g. "src/lib.rs") relative to the manifest file.
+pub fn lib_path(current_dir: &Path) -> Result<String, Error> {
     Manifest::from_path(current_dir.join("Cargo.toml"))
-        .map(|f| (f.package.as_ref().unwrap()).name.to_string())
+        .map(|f| f.lib.and_then(|lib| lib.path).unwrap_or("src/lib.rs".to_string()))
         .map_err(|_| Error::ManifestFailure)
 }
 
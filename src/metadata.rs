@@ -0,0 +1,156 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Defines the build provenance metadata that can be embedded into the compiled contract as a WebAssembly
+//! custom section named `pchain.build`.
+//!
+//! The custom section is appended to the end of the module after all post-processing, so the other sections
+//! (including the code section) are byte-for-byte identical to a build without the metadata. The metadata
+//! itself contains no timestamps or absolute paths, so that rebuilding the same source in the same environment
+//! produces the same binary.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{error::Error, BuildOptions};
+
+/// Name of the custom section that stores the build metadata.
+pub const BUILD_METADATA_SECTION: &str = "pchain.build";
+
+/// File name of the build metadata saved in the output folder of a contract with
+/// [crate::OutputLayout::PerContract].
+pub const BUILD_METADATA_FILE: &str = "build.json";
+
+/// Provenance information about how a contract was built.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildMetadata {
+    /// Version of pchain_compile that built the contract.
+    pub pchain_compile_version: String,
+    /// Tag of the docker image. None if the contract was built without docker.
+    pub docker_image_tag: Option<String>,
+    /// Digest of the docker image (e.g. `parallelchainlab/pchain_compile@sha256:...`), if available.
+    pub docker_image_digest: Option<String>,
+    /// Output of `rustc --version` in the building environment, if available.
+    pub rustc_version: Option<String>,
+    /// SHA-256 (hex) of the source code directory. See [source_tree_hash].
+    pub source_hash: String,
+    /// SHA-256 (hex) of the Cargo.lock used or generated in the building process, if available.
+    pub cargo_lock_hash: Option<String>,
+    /// Cargo features enabled in the build, in sorted order.
+    pub features: Vec<String>,
+}
+
+impl BuildMetadata {
+    /// Creates the metadata of the build of the contract at `source_path`. `cargo_lock` is the content of the
+    /// Cargo.lock used or generated in the build.
+    pub(crate) fn new(
+        source_path: &Path,
+        cargo_lock: Option<&[u8]>,
+        options: &BuildOptions,
+        docker_image: Option<(String, Option<String>)>,
+        rustc_version: Option<String>,
+    ) -> Result<Self, Error> {
+        let (docker_image_tag, docker_image_digest) = match docker_image {
+            Some((tag, digest)) => (Some(tag), digest),
+            None => (None, None),
+        };
+        let mut features = options.features.clone();
+        features.sort();
+        features.dedup();
+
+        Ok(Self {
+            pchain_compile_version: env!("CARGO_PKG_VERSION").to_string(),
+            docker_image_tag,
+            docker_image_digest,
+            rustc_version,
+            source_hash: source_tree_hash(source_path)?,
+            cargo_lock_hash: cargo_lock.map(crate::wasm::sha256_hex),
+            features,
+        })
+    }
+}
+
+/// Appends the build metadata as a custom section to the WebAssembly binary at `wasm_path`.
+pub(crate) fn embed_metadata(wasm_path: &Path, metadata: &BuildMetadata) -> Result<(), Error> {
+    let map_err = |e: String| Error::BuildFailure(format!("Fail to embed build metadata: {e}"));
+
+    let mut wasm = std::fs::read(wasm_path).map_err(|e| map_err(e.to_string()))?;
+    let payload = serde_json::to_vec(metadata).map_err(|e| map_err(e.to_string()))?;
+    crate::wasm::append_custom_section(&mut wasm, BUILD_METADATA_SECTION, &payload);
+    std::fs::write(wasm_path, wasm).map_err(|e| map_err(e.to_string()))
+}
+
+/// Saves the build metadata as [BUILD_METADATA_FILE] in the folder at `output_path`.
+pub(crate) fn save_metadata(output_path: &Path, metadata: &BuildMetadata) -> Result<(), Error> {
+    let map_err = |e: String| Error::BuildFailure(format!("Fail to save build metadata: {e}"));
+
+    let content = serde_json::to_vec_pretty(metadata).map_err(|e| map_err(e.to_string()))?;
+    std::fs::write(output_path.join(BUILD_METADATA_FILE), content).map_err(|e| map_err(e.to_string()))
+}
+
+/// Returns the build metadata embedded in the WebAssembly binary, or None if it does not exist.
+pub fn read_metadata(wasm: &[u8]) -> Result<Option<BuildMetadata>, Error> {
+    for section in crate::wasm::sections(wasm)? {
+        if section.id == 0 && section.name == BUILD_METADATA_SECTION {
+            let payload = crate::wasm::custom_section_content(&section)?;
+            return serde_json::from_slice(payload)
+                .map(Some)
+                .map_err(|e| Error::InvalidWasmFile(format!("Malformed build metadata: {e}")));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the WebAssembly binary without the build metadata section. The result is the binary
+/// as it was before the metadata was embedded.
+pub fn strip_metadata(wasm: &[u8]) -> Result<Vec<u8>, Error> {
+    crate::wasm::remove_custom_section(wasm, BUILD_METADATA_SECTION)
+}
+
+/// Computes a SHA-256 (hex) over the files in the source code directory. Folders named `target` at
+/// the top level and files or folders whose names start with `.` are skipped. Files are hashed in sorted order
+/// of their relative paths (with `/` as separator), so the result does not depend on where the directory is located.
+pub(crate) fn source_tree_hash(source_path: &Path) -> Result<String, Error> {
+    let mut files = vec![];
+    collect_files(source_path, source_path, &mut files)
+        .map_err(|e| Error::BuildFailure(format!("Fail to read source code: {e}")))?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for relative_path in files {
+        let content = std::fs::read(source_path.join(&relative_path))
+            .map_err(|e| Error::BuildFailure(format!("Fail to read source code: {e}")))?;
+        hasher.update(relative_path.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') || (dir == root && file_name == "target") {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative_path = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(relative_path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the post-processing of a WebAssembly binary built by cargo. It is the part of the building
+//! process after `cargo build`, i.e. optimize by `wasm-opt`, then snip by `wasm-snip`, then remove the dead code
+//! by `wasm-opt` again. It can be applied to a binary built outside of pchain_compile, without a Cargo project.
+
+use std::path::Path;
+
+use crate::{error::Error, BuildOptions};
+
+/// `optimize_wasm` post-processes the WebAssembly binary at `input_path` (e.g. the output of cargo build in
+/// "target/wasm32-unknown-unknown/release") and saves the result to `output_path`.
+///
+/// The options applicable to a binary are applied as in building, i.e. `float_policy`, `size_report`, `max_wasm_size`
+/// and `size_baseline`. The size report is saved next to the result. The other options require the source code and
+/// are ignored.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` optimize `input_path` --output `output_path`
+pub fn optimize_wasm(input_path: &Path, output_path: &Path, options: &BuildOptions) -> Result<(), Error> {
+    if !input_path.is_file() {
+        return Err(Error::InvalidWasmFile(format!("{} is not a file.", input_path.display())));
+    }
+    let wasm_file = output_path
+        .file_name()
+        .ok_or(Error::InvalidDestinationPath)?
+        .to_string_lossy()
+        .to_string();
+    let destination_path = match output_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+    std::fs::create_dir_all(&destination_path).map_err(|_| Error::InvalidDestinationPath)?;
+
+    // Create temporary folder as a working directory for the intermediate binaries
+    let temp_dir = crate::cargo::random_temp_dir_name();
+    std::fs::create_dir_all(temp_dir.as_path()).map_err(|_| Error::CreateTempDir)?;
+
+    let destination_path = Some(destination_path);
+    let result = post_process(input_path, &temp_dir, output_path, options.size_report).and_then(|_| {
+        crate::build::validate_contract(destination_path.clone(), &wasm_file, options)?;
+        if options.size_report {
+            crate::build::save_size_report(&temp_dir, destination_path.clone(), &wasm_file)?;
+        }
+        crate::build::check_size_budget(destination_path.clone(), &wasm_file, options)
+    });
+
+    // Remove temporary files after post-processing
+    let _ = std::fs::remove_dir_all(temp_dir);
+
+    result
+}
+
+/// Equivalent to run following commands:
+/// 1. wasm-opt -Oz <input_wasm> --output temp.wasm
+/// 2. wasm-snip temp.wasm --output temp2.wasm --snip-rust-fmt-code --snip-rust-panicking-code
+/// 3. wasm-opt --dce temp2.wasm --output <output_wasm>
+///
+/// If `keep_intermediates` is set, the working folder keeps the intermediate binaries for size report, including a copy
+/// of the input (named as the output) and the result of `wasm-opt -Oz -g <input_wasm> --output named.wasm`. See [crate::size].
+pub(crate) fn post_process(
+    input_wasm: &Path,
+    working_folder: &Path,
+    output_wasm: &Path,
+    keep_intermediates: bool,
+) -> Result<(), Error> {
+    // Keep the input and an optimized binary with name section for size report, before the output may overwrite the input
+    if keep_intermediates {
+        let input_copy = working_folder.join(output_wasm.file_name().unwrap());
+        std::fs::copy(input_wasm, input_copy)
+            .map_err(|e| Error::BuildFailure(format!("Fail to keep the cargo output:\n\n{:?}\n", e)))?;
+        wasm_opt::OptimizationOptions::new_optimize_for_size_aggressively()
+            .debug_info(true)
+            .run(input_wasm, working_folder.join(crate::size::NAMED_WASM))
+            .map_err(|e| Error::BuildFailure(format!("Wasm optimization error:\n\n{:?}\n", e)))?;
+    }
+
+    // 1. wasm-opt -Oz input_wasm --output temp.wasm
+    let temp_wasm = working_folder.join(crate::size::OPTIMIZED_WASM);
+    wasm_opt::OptimizationOptions::new_optimize_for_size_aggressively()
+        .run(input_wasm, &temp_wasm)
+        .map_err(|e| Error::BuildFailure(format!("Wasm optimization error:\n\n{:?}\n", e)))?;
+
+    // 2. wasm-snip temp.wasm --output temp2.wasm --snip-rust-fmt-code --snip-rust-panicking-code
+    let temp2_wasm = working_folder.join(crate::size::SNIPPED_WASM);
+    let wasm_snip_options = wasm_snip::Options {
+        snip_rust_fmt_code: true,
+        snip_rust_panicking_code: true,
+        ..Default::default()
+    };
+    let mut module = walrus::ModuleConfig::new()
+        .parse_file(temp_wasm)
+        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
+    wasm_snip::snip(&mut module, wasm_snip_options)
+        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
+    module
+        .emit_wasm_file(&temp2_wasm)
+        .map_err(|e| Error::BuildFailure(format!("Wasm snip error:\n\n{:?}\n", e)))?;
+
+    // 3. wasm-opt --dce temp2.wasm --output output_wasm
+    wasm_opt::OptimizationOptions::new_optimize_for_size()
+        .add_pass(wasm_opt::Pass::Dce)
+        .run(temp2_wasm, output_wasm)
+        .map_err(|e| Error::BuildFailure(format!("Wasm optimization error:\n\n{:?}\n", e)))?;
+
+    Ok(())
+}
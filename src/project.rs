@@ -0,0 +1,262 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the project configuration file, which stores the build settings that would otherwise be
+//! passed on every invocation. The settings are read from either:
+//! - the file `pchain_compile.toml` ([PROJECT_CONFIG_FILE]), or
+//! - the table `[package.metadata.pchain]` in `Cargo.toml`, in which case the package itself is the
+//!   default contract.
+//!
+//! Example of `pchain_compile.toml`:
+//! ```toml
+//! contracts = ["contracts/token", "contracts/exchange"]
+//! destination = "artifacts"
+//! locked = true
+//! docker-tag = "0.4.3"
+//! features = ["mainnet"]
+//! float-policy = "error"
+//! max-wasm-size = 500000
+//! ```
+//!
+//! Relative paths are resolved against the folder containing the file. A [ProjectConfig] is expanded to one
+//! [Config] per contract by [ProjectConfig::configs]. Settings from the command line are applied on top of the
+//! file by [ProjectConfig::override_with].
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    config::{BuildOptions, Config, DockerConfig, DockerOption, FloatPolicy, OutputLayout, SandboxConfig, SizeBaseline},
+    error::Error,
+};
+
+/// File name of the project configuration file.
+pub const PROJECT_CONFIG_FILE: &str = "pchain_compile.toml";
+
+/// Build settings of a project. A field that is None is not set, and the default of [Config] applies.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Paths to the source code folders of the contracts.
+    pub contracts: Option<Vec<PathBuf>>,
+    /// Path to the destination folder shared by the contracts.
+    pub destination: Option<PathBuf>,
+    /// See [BuildOptions::locked].
+    pub locked: Option<bool>,
+    /// See [BuildOptions::frozen].
+    pub frozen: Option<bool>,
+    /// See [BuildOptions::features].
+    pub features: Option<Vec<String>>,
+    /// See [BuildOptions::embed_metadata].
+    pub embed_metadata: Option<bool>,
+    /// See [BuildOptions::embed_abi].
+    pub embed_abi: Option<bool>,
+    /// See [BuildOptions::float_policy].
+    pub float_policy: Option<FloatPolicy>,
+    /// See [BuildOptions::size_report].
+    pub size_report: Option<bool>,
+    /// See [BuildOptions::max_wasm_size].
+    pub max_wasm_size: Option<usize>,
+    /// See [SizeBaseline::wasm_path].
+    pub size_baseline: Option<PathBuf>,
+    /// See [SizeBaseline::max_growth_percent]. It requires `size_baseline`.
+    pub max_size_growth: Option<f64>,
+    /// See [BuildOptions::no_cache].
+    pub no_cache: Option<bool>,
+    /// See [BuildOptions::output_layout].
+    pub output_layout: Option<OutputLayout>,
+    /// See [BuildOptions::versioned_name].
+    pub versioned_name: Option<bool>,
+    /// Maximum number of builds running in parallel. See [crate::batch::build_all].
+    pub jobs: Option<usize>,
+    /// Build without using docker. See [DockerOption::Dockerless].
+    pub dockerless: Option<bool>,
+    /// Tag of the docker image. See [DockerConfig::tag].
+    pub docker_tag: Option<String>,
+    /// Build in a Linux user-namespace sandbox. See [DockerOption::Sandbox].
+    pub sandbox: Option<bool>,
+    /// See [SandboxConfig::toolchain].
+    pub sandbox_toolchain: Option<String>,
+    /// Address of the docker daemon. See [DockerConfig::host].
+    pub docker_host: Option<String>,
+    /// See [DockerConfig::tls_cert_path].
+    pub docker_tls_cert_path: Option<PathBuf>,
+    /// See [DockerConfig::api_version].
+    pub docker_api_version: Option<String>,
+    /// See [DockerConfig::timeout].
+    pub docker_timeout: Option<u64>,
+    /// See [DockerConfig::cli].
+    pub docker_cli: Option<String>,
+    /// See [DockerConfig::local_image].
+    pub docker_local_image: Option<bool>,
+}
+
+impl ProjectConfig {
+    /// Reads the project configuration from a file. If the file name is `Cargo.toml`, the configuration is the
+    /// table `[package.metadata.pchain]`, and the contracts default to the package. Relative paths are resolved
+    /// against the folder containing the file.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::ProjectConfigFailure(format!("Fail to read {}: {e}", path.display())))?;
+        let parse_error = |e: toml::de::Error| Error::ProjectConfigFailure(format!("Fail to parse {}: {e}", path.display()));
+
+        let mut project_config = if path.file_name().is_some_and(|name| name == "Cargo.toml") {
+            let manifest: toml::Value = toml::from_str(&content).map_err(parse_error)?;
+            let mut project_config: ProjectConfig = pchain_metadata(&manifest)
+                .cloned()
+                .map(|pchain| pchain.try_into().map_err(parse_error))
+                .transpose()?
+                .unwrap_or_default();
+            project_config.contracts.get_or_insert(vec![PathBuf::from(".")]);
+            project_config
+        } else {
+            toml::from_str(&content).map_err(parse_error)?
+        };
+
+        let base_path = path.parent().unwrap_or(Path::new("."));
+        let resolve = |relative: &mut PathBuf| *relative = base_path.join(&*relative);
+        project_config.contracts.iter_mut().flatten().for_each(resolve);
+        project_config.destination.iter_mut().for_each(resolve);
+        project_config.size_baseline.iter_mut().for_each(resolve);
+        project_config.docker_tls_cert_path.iter_mut().for_each(resolve);
+        Ok(project_config)
+    }
+
+    /// Finds the project configuration in a folder. It reads `pchain_compile.toml` if it exists, or otherwise
+    /// `Cargo.toml` if it contains the table `[package.metadata.pchain]`. Returns None if neither is found.
+    pub fn find(folder: &Path) -> Result<Option<Self>, Error> {
+        let file_path = folder.join(PROJECT_CONFIG_FILE);
+        if file_path.is_file() {
+            return Self::from_file(&file_path).map(Some);
+        }
+
+        let manifest_path = folder.join("Cargo.toml");
+        let has_pchain_metadata = std::fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+            .is_some_and(|manifest| pchain_metadata(&manifest).is_some());
+        match has_pchain_metadata {
+            true => Self::from_file(&manifest_path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Returns the configuration in which the fields set in `other` replace the fields in `self`.
+    pub fn override_with(self, other: ProjectConfig) -> ProjectConfig {
+        // The settings of the build environment are replaced together, so that a docker setting cancels
+        // `dockerless` and `sandbox`, and `dockerless` or `sandbox` cancels the docker settings.
+        let other_docker = other.docker_tag.is_some()
+            || other.docker_host.is_some()
+            || other.docker_tls_cert_path.is_some()
+            || other.docker_api_version.is_some()
+            || other.docker_timeout.is_some()
+            || other.docker_cli.is_some()
+            || other.docker_local_image.is_some();
+        let other_not_docker = other.dockerless.is_some() || other.sandbox.is_some();
+        let (dockerless, sandbox) = match other_docker || other_not_docker {
+            true => (other.dockerless, other.sandbox),
+            false => (self.dockerless, self.sandbox),
+        };
+        ProjectConfig {
+            contracts: other.contracts.or(self.contracts),
+            destination: other.destination.or(self.destination),
+            locked: other.locked.or(self.locked),
+            frozen: other.frozen.or(self.frozen),
+            features: other.features.or(self.features),
+            embed_metadata: other.embed_metadata.or(self.embed_metadata),
+            embed_abi: other.embed_abi.or(self.embed_abi),
+            float_policy: other.float_policy.or(self.float_policy),
+            size_report: other.size_report.or(self.size_report),
+            max_wasm_size: other.max_wasm_size.or(self.max_wasm_size),
+            size_baseline: other.size_baseline.or(self.size_baseline),
+            max_size_growth: other.max_size_growth.or(self.max_size_growth),
+            no_cache: other.no_cache.or(self.no_cache),
+            output_layout: other.output_layout.or(self.output_layout),
+            versioned_name: other.versioned_name.or(self.versioned_name),
+            jobs: other.jobs.or(self.jobs),
+            dockerless,
+            docker_tag: other.docker_tag.or(self.docker_tag.filter(|_| !other_not_docker)),
+            sandbox,
+            sandbox_toolchain: other.sandbox_toolchain.or(self.sandbox_toolchain),
+            docker_host: other.docker_host.or(self.docker_host.filter(|_| !other_not_docker)),
+            docker_tls_cert_path: other.docker_tls_cert_path.or(self.docker_tls_cert_path.filter(|_| !other_not_docker)),
+            docker_api_version: other.docker_api_version.or(self.docker_api_version.filter(|_| !other_not_docker)),
+            docker_timeout: other.docker_timeout.or(self.docker_timeout.filter(|_| !other_not_docker)),
+            docker_cli: other.docker_cli.or(self.docker_cli.filter(|_| !other_not_docker)),
+            docker_local_image: other.docker_local_image.or(self.docker_local_image.filter(|_| !other_not_docker)),
+        }
+    }
+
+    /// Returns the build options of the contracts.
+    pub fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            locked: self.locked.unwrap_or_default(),
+            frozen: self.frozen.unwrap_or_default(),
+            features: self.features.clone().unwrap_or_default(),
+            embed_metadata: self.embed_metadata.unwrap_or_default(),
+            embed_abi: self.embed_abi.unwrap_or_default(),
+            float_policy: self.float_policy.unwrap_or_default(),
+            size_report: self.size_report.unwrap_or_default(),
+            max_wasm_size: self.max_wasm_size,
+            size_baseline: self.size_baseline.clone().map(|wasm_path| SizeBaseline {
+                wasm_path,
+                max_growth_percent: self.max_size_growth.unwrap_or_default(),
+            }),
+            no_cache: self.no_cache.unwrap_or_default(),
+            output_layout: self.output_layout.unwrap_or_default(),
+            versioned_name: self.versioned_name.unwrap_or_default(),
+            on_warning: None,
+        }
+    }
+
+    /// Returns the docker option of the contracts.
+    pub fn docker_option(&self) -> DockerOption {
+        match (self.dockerless.unwrap_or_default(), self.sandbox.unwrap_or_default()) {
+            (true, _) => DockerOption::Dockerless,
+            (false, true) => DockerOption::Sandbox(SandboxConfig {
+                toolchain: self.sandbox_toolchain.clone(),
+                ..Default::default()
+            }),
+            (false, false) => DockerOption::Docker(DockerConfig {
+                tag: self.docker_tag.clone(),
+                host: self.docker_host.clone(),
+                tls_cert_path: self.docker_tls_cert_path.clone(),
+                api_version: self.docker_api_version.clone(),
+                timeout: self.docker_timeout,
+                cli: self.docker_cli.clone(),
+                local_image: self.docker_local_image.unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Returns one [Config] for each contract. It fails if `max_size_growth` is set without `size_baseline`, because
+    /// it would be ignored.
+    pub fn configs(&self) -> Result<Vec<Config>, Error> {
+        if self.max_size_growth.is_some() && self.size_baseline.is_none() {
+            return Err(Error::ProjectConfigFailure(
+                "max-size-growth is set without size-baseline.".to_string(),
+            ));
+        }
+        let build_options = self.build_options();
+        let docker_option = self.docker_option();
+        Ok(self
+            .contracts
+            .iter()
+            .flatten()
+            .map(|source_path| Config {
+                source_path: source_path.clone(),
+                destination_path: self.destination.clone(),
+                build_options: build_options.clone(),
+                docker_option: docker_option.clone(),
+            })
+            .collect())
+    }
+}
+
+/// Returns the table `[package.metadata.pchain]` of a manifest.
+fn pchain_metadata(manifest: &toml::Value) -> Option<&toml::Value> {
+    manifest.get("package")?.get("metadata")?.get("pchain")
+}
@@ -0,0 +1,737 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of running a contract method locally. The contract is executed by the embedded WebAssembly
+//! interpreter `wasmi`, with the host functions of ParallelChain runtime stubbed in memory:
+//! - The contract storage is an in-memory map, which can be preloaded and is returned after the call.
+//! - The call context (method, arguments, calling account and amount) is taken from [ContractCall]. The block and
+//!   transaction fields, the balance and the current account are zeros.
+//! - Logs and the return value are collected into [RunResult].
+//! - Internal calls, transfers, deposit commands and cryptographic functions other than `sha256` are not supported,
+//!   and trap the execution when they are called.
+//!
+//! Arguments are given in text form and serialized in borsh according to the argument types in the ABI
+//! (see [crate::abi]). The return value is deserialized according to the return type in the same way.
+
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use wasmi::{core::Trap, Caller, Engine, Extern, Linker, Module, Store};
+
+use crate::{abi::ContractAbi, error::Error};
+
+/// Name of the host module that contracts import the host functions from.
+const HOST_MODULE: &str = "env";
+
+/// Number of bytes of a value shown in the text format of the result.
+const VALUE_BYTES_SHOWN: usize = 32;
+
+/// Input of a local run of a contract method.
+#[derive(Debug, Clone, Default)]
+pub struct ContractCall {
+    /// Name of the method marked with `#[call]`.
+    pub method: String,
+    /// Arguments in text form, one for each argument of the method. See [encode_argument].
+    pub args: Vec<String>,
+    /// ABI of the contract. If None, the ABI embedded in the contract, or the ABI file `<contract>.abi.json`
+    /// next to the contract is used.
+    pub abi: Option<ContractAbi>,
+    pub calling_account: [u8; 32],
+    /// Amount of tokens transferred with the call.
+    pub amount: u64,
+    /// Contract storage before the call.
+    pub storage: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Result of a local run of a contract method.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub logs: Vec<LogEntry>,
+    /// None if the method returns nothing.
+    pub return_value: Option<ReturnValue>,
+    /// Changed storage entries in the order of keys.
+    pub storage_changes: Vec<StorageChange>,
+    /// Contract storage after the call.
+    #[serde(skip)]
+    pub storage: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// A log emitted by the contract. Topic and value are decoded as UTF-8 lossily.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub topic: String,
+    pub value: String,
+}
+
+/// Return value of the contract method.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReturnValue {
+    /// Borsh-serialized value in hex.
+    pub bytes: String,
+    /// Value in JSON, or None if the return type is unknown or not supported.
+    pub decoded: Option<Value>,
+}
+
+/// A storage entry changed by the call.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageChange {
+    /// Key in hex.
+    pub key: String,
+    /// Name of the field of the contract struct that the key belongs to, if any.
+    pub field: Option<String>,
+    /// Value before the call in hex. None if the key did not exist.
+    pub old_value: Option<String>,
+    /// Value after the call in hex.
+    pub new_value: String,
+}
+
+/// `run_contract` calls a method of the contract at `wasm_path` in a local sandbox, and returns the logs, the return
+/// value and the storage changes.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` run `wasm_path` --method `method` --args `args`
+pub fn run_contract(wasm_path: &Path, call: &ContractCall) -> Result<RunResult, Error> {
+    let wasm = std::fs::read(wasm_path)
+        .map_err(|e| Error::InvalidWasmFile(format!("Fail to read {}: {e}", wasm_path.display())))?;
+    let abi = match &call.abi {
+        Some(abi) => Some(abi.clone()),
+        None => find_abi(wasm_path, &wasm)?,
+    };
+    let method = abi.as_ref().and_then(|abi| abi.methods.iter().find(|method| method.name == call.method));
+
+    // Serialize the arguments according to the ABI
+    let arguments = match (&abi, method) {
+        (Some(_), None) => {
+            return Err(Error::ContractRunFailure(format!("Method {} is not found in the ABI.", call.method)))
+        }
+        (Some(_), Some(method)) => {
+            if method.args.len() != call.args.len() {
+                return Err(Error::ContractRunFailure(format!(
+                    "Method {} takes {} argument(s) but {} are given.",
+                    call.method,
+                    method.args.len(),
+                    call.args.len()
+                )));
+            }
+            method
+                .args
+                .iter()
+                .zip(&call.args)
+                .map(|(arg, text)| {
+                    encode_argument(&arg.ty, text)
+                        .map_err(|e| Error::ContractRunFailure(format!("Invalid argument {}: {e}", arg.name)))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        (None, _) => call
+            .args
+            .iter()
+            .map(|text| {
+                decode_hex(text).ok_or(Error::ContractRunFailure(format!(
+                    "Argument {text} is not in hex. Without the ABI, arguments must be borsh-serialized in hex."
+                )))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    // Execute the entrypoint
+    let mut state = execute(&wasm, HostState {
+        method: call.method.as_bytes().to_vec(),
+        arguments: serialize_arguments(&arguments),
+        calling_account: call.calling_account,
+        amount: call.amount,
+        storage: call.storage.clone(),
+        logs: vec![],
+        return_value: None,
+    })?;
+
+    let return_value = state.return_value.take().map(|bytes| ReturnValue {
+        bytes: encode_hex(&bytes),
+        decoded: method
+            .and_then(|method| method.return_type.as_deref())
+            .and_then(|return_type| decode_value(return_type, &bytes)),
+    });
+    let storage_changes = state
+        .storage
+        .iter()
+        .filter(|(key, value)| call.storage.get(*key) != Some(*value))
+        .map(|(key, value)| StorageChange {
+            key: encode_hex(key),
+            field: match (&abi, key.as_slice()) {
+                (Some(abi), [index]) => abi.storage.get(*index as usize).map(|field| field.name.clone()),
+                _ => None,
+            },
+            old_value: call.storage.get(key).map(|value| encode_hex(value)),
+            new_value: encode_hex(value),
+        })
+        .collect();
+
+    Ok(RunResult {
+        logs: state.logs,
+        return_value,
+        storage_changes,
+        storage: state.storage,
+    })
+}
+
+/// Reads a storage file saved by [save_storage]. It returns an empty storage if the file does not exist.
+pub fn load_storage(path: &Path) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, Error> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let map_err = |e: String| Error::ContractRunFailure(format!("Fail to load storage from {}: {e}", path.display()));
+    let content = std::fs::read(path).map_err(|e| map_err(e.to_string()))?;
+    let entries: BTreeMap<String, String> = serde_json::from_slice(&content).map_err(|e| map_err(e.to_string()))?;
+    entries
+        .iter()
+        .map(|(key, value)| match (decode_hex(key), decode_hex(value)) {
+            (Some(key), Some(value)) => Ok((key, value)),
+            _ => Err(map_err(format!("{key}: {value} is not in hex"))),
+        })
+        .collect()
+}
+
+/// Saves the storage as a JSON object that maps keys to values, both in hex.
+pub fn save_storage(path: &Path, storage: &BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), Error> {
+    let entries: BTreeMap<String, String> =
+        storage.iter().map(|(key, value)| (encode_hex(key), encode_hex(value))).collect();
+    let content = serde_json::to_vec_pretty(&entries).unwrap();
+    std::fs::write(path, content)
+        .map_err(|e| Error::ContractRunFailure(format!("Fail to save storage to {}: {e}", path.display())))
+}
+
+/// Returns the ABI embedded in the contract, or saved as `<contract>.abi.json` next to the contract.
+fn find_abi(wasm_path: &Path, wasm: &[u8]) -> Result<Option<ContractAbi>, Error> {
+    if let Some(abi) = crate::abi::read_abi(wasm)? {
+        return Ok(Some(abi));
+    }
+    let wasm_file = wasm_path.file_name().unwrap_or_default().to_string_lossy();
+    let abi_path = wasm_path.with_file_name(crate::abi::abi_file(&wasm_file));
+    match std::fs::read(&abi_path) {
+        Ok(content) => serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|e| Error::ContractRunFailure(format!("Malformed ABI file {}: {e}", abi_path.display()))),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Serializes the arguments as `Vec<Vec<u8>>` in borsh, which is the format that contracts parse the arguments from.
+fn serialize_arguments(arguments: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = (arguments.len() as u32).to_le_bytes().to_vec();
+    for argument in arguments {
+        bytes.extend((argument.len() as u32).to_le_bytes());
+        bytes.extend(argument);
+    }
+    bytes
+}
+
+/* Host environment */
+
+/// In-memory state of the host functions during a call.
+struct HostState {
+    method: Vec<u8>,
+    arguments: Vec<u8>,
+    calling_account: [u8; 32],
+    amount: u64,
+    storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    logs: Vec<LogEntry>,
+    return_value: Option<Vec<u8>>,
+}
+
+/// Instantiates the contract with the host functions and calls its entrypoint. It returns the state after the call.
+fn execute(wasm: &[u8], state: HostState) -> Result<HostState, Error> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).map_err(|e| Error::InvalidWasmFile(e.to_string()))?;
+    let mut store = Store::new(&engine, state);
+    let mut linker = <Linker<HostState>>::new(&engine);
+    define_host_functions(&mut linker).map_err(|e| Error::ContractRunFailure(e.to_string()))?;
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(|e| Error::ContractRunFailure(format!("Fail to instantiate the contract: {e}")))?;
+    let entrypoint = instance
+        .get_typed_func::<(), ()>(&store, "entrypoint")
+        .map_err(|e| Error::ContractRunFailure(format!("Fail to find the entrypoint: {e}")))?;
+    if let Err(e) = entrypoint.call(&mut store, ()) {
+        let logs = &store.data().logs;
+        let logs = if logs.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "\nLogs before the failure:\n{}",
+                logs.iter().map(|log| format!("- {log}")).collect::<Vec<_>>().join("\n")
+            )
+        };
+        return Err(Error::ContractRunFailure(format!("The execution traps: {e}{logs}")));
+    }
+    Ok(store.into_data())
+}
+
+fn define_host_functions(linker: &mut Linker<HostState>) -> Result<(), wasmi::errors::LinkerError> {
+    // Account State Accessors
+    linker.func_wrap(HOST_MODULE, "set", |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32| -> Result<(), Trap> {
+        let key = read_bytes(&caller, key_ptr, key_len)?;
+        let value = read_bytes(&caller, value_ptr, value_len)?;
+        caller.data_mut().storage.insert(key, value);
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "get", |mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, value_ptr_ptr: i32| -> Result<i64, Trap> {
+        let key = read_bytes(&caller, key_ptr, key_len)?;
+        match caller.data().storage.get(&key).cloned() {
+            Some(value) => {
+                write_to_guest(&mut caller, &value, value_ptr_ptr)?;
+                Ok(value.len() as i64)
+            }
+            None => Ok(-1),
+        }
+    })?;
+    linker.func_wrap(HOST_MODULE, "get_network_storage", |_: i32, _: i32, _: i32| -> i64 { -1 })?;
+    linker.func_wrap(HOST_MODULE, "balance", || -> i64 { 0 })?;
+
+    // Block Field Getters
+    linker.func_wrap(HOST_MODULE, "block_height", || -> i64 { 0 })?;
+    linker.func_wrap(HOST_MODULE, "block_timestamp", || -> i32 { 0 })?;
+    linker.func_wrap(HOST_MODULE, "prev_block_hash", |mut caller: Caller<'_, HostState>, hash_ptr_ptr: i32| {
+        write_to_guest(&mut caller, &[0; 32], hash_ptr_ptr)
+    })?;
+
+    // Call Context Getters
+    linker.func_wrap(HOST_MODULE, "calling_account", |mut caller: Caller<'_, HostState>, address_ptr_ptr: i32| {
+        let address = caller.data().calling_account;
+        write_to_guest(&mut caller, &address, address_ptr_ptr)
+    })?;
+    linker.func_wrap(HOST_MODULE, "current_account", |mut caller: Caller<'_, HostState>, address_ptr_ptr: i32| {
+        write_to_guest(&mut caller, &[0; 32], address_ptr_ptr)
+    })?;
+    linker.func_wrap(HOST_MODULE, "method", |mut caller: Caller<'_, HostState>, method_ptr_ptr: i32| -> Result<i32, Trap> {
+        let method = caller.data().method.clone();
+        write_to_guest(&mut caller, &method, method_ptr_ptr)?;
+        Ok(method.len() as i32)
+    })?;
+    linker.func_wrap(HOST_MODULE, "arguments", |mut caller: Caller<'_, HostState>, arguments_ptr_ptr: i32| -> Result<i32, Trap> {
+        let arguments = caller.data().arguments.clone();
+        write_to_guest(&mut caller, &arguments, arguments_ptr_ptr)?;
+        Ok(arguments.len() as i32)
+    })?;
+    linker.func_wrap(HOST_MODULE, "amount", |caller: Caller<'_, HostState>| -> i64 { caller.data().amount as i64 })?;
+    linker.func_wrap(HOST_MODULE, "is_internal_call", || -> i32 { 0 })?;
+    linker.func_wrap(HOST_MODULE, "transaction_hash", |mut caller: Caller<'_, HostState>, hash_ptr_ptr: i32| {
+        write_to_guest(&mut caller, &[0; 32], hash_ptr_ptr)
+    })?;
+
+    // Internal Call Triggers
+    linker.func_wrap(HOST_MODULE, "call", |_: i32, _: i32, _: i32| -> Result<i32, Trap> { Err(unsupported("call")) })?;
+    linker.func_wrap(HOST_MODULE, "return_value", |mut caller: Caller<'_, HostState>, value_ptr: i32, value_len: i32| -> Result<(), Trap> {
+        let value = read_bytes(&caller, value_ptr, value_len)?;
+        caller.data_mut().return_value = Some(value);
+        Ok(())
+    })?;
+    linker.func_wrap(HOST_MODULE, "transfer", |_: i32| -> Result<(), Trap> { Err(unsupported("transfer")) })?;
+
+    // Network Command Triggers
+    for name in [
+        "defer_create_deposit",
+        "defer_set_deposit_settings",
+        "defer_topup_deposit",
+        "defer_withdraw_deposit",
+        "defer_stake_deposit",
+        "defer_unstake_deposit",
+    ] {
+        linker.func_wrap(HOST_MODULE, name, move |_: i32, _: i32| -> Result<(), Trap> { Err(unsupported(name)) })?;
+    }
+
+    // Logging
+    linker.func_wrap(HOST_MODULE, "_log", |mut caller: Caller<'_, HostState>, log_ptr: i32, log_len: i32| -> Result<(), Trap> {
+        let log = read_bytes(&caller, log_ptr, log_len)?;
+        let log = parse_log(&log).ok_or(Trap::new("Malformed log"))?;
+        caller.data_mut().logs.push(log);
+        Ok(())
+    })?;
+
+    // Cryptographic operations
+    linker.func_wrap(HOST_MODULE, "sha256", |mut caller: Caller<'_, HostState>, msg_ptr: i32, msg_len: i32, digest_ptr_ptr: i32| -> Result<(), Trap> {
+        let message = read_bytes(&caller, msg_ptr, msg_len)?;
+        write_to_guest(&mut caller, &Sha256::digest(message), digest_ptr_ptr)
+    })?;
+    linker.func_wrap(HOST_MODULE, "keccak256", |_: i32, _: i32, _: i32| -> Result<(), Trap> { Err(unsupported("keccak256")) })?;
+    linker.func_wrap(HOST_MODULE, "ripemd", |_: i32, _: i32, _: i32| -> Result<(), Trap> { Err(unsupported("ripemd")) })?;
+    linker.func_wrap(HOST_MODULE, "verify_ed25519_signature", |_: i32, _: i32, _: i32, _: i32| -> Result<i32, Trap> {
+        Err(unsupported("verify_ed25519_signature"))
+    })?;
+    Ok(())
+}
+
+fn unsupported(name: &str) -> Trap {
+    Trap::new(format!("Host function {name} is not supported in local run"))
+}
+
+/// Reads bytes from the linear memory of the contract. The range is checked against the size of the memory before
+/// allocating the buffer, because the length is given by the contract.
+fn read_bytes(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or(Trap::new("Memory is not exported"))?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    match ptr.checked_add(len) {
+        Some(end) if end <= memory.data(caller).len() => {}
+        _ => return Err(Trap::new(format!("Out of bounds memory access: {len} bytes at {ptr}"))),
+    }
+    let mut bytes = vec![0; len];
+    memory
+        .read(caller, ptr, &mut bytes)
+        .map_err(|e| Trap::new(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Writes bytes to a memory allocated by the exported function `alloc` of the contract, and writes the address of
+/// the bytes to `ptr_ptr`.
+fn write_to_guest(caller: &mut Caller<'_, HostState>, bytes: &[u8], ptr_ptr: i32) -> Result<(), Trap> {
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or(Trap::new("Function alloc is not exported"))?
+        .typed::<i32, i32>(&*caller)
+        .map_err(|e| Trap::new(e.to_string()))?;
+    let ptr = alloc.call(&mut *caller, bytes.len() as i32)?;
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or(Trap::new("Memory is not exported"))?;
+    memory
+        .write(&mut *caller, ptr as u32 as usize, bytes)
+        .and_then(|_| memory.write(&mut *caller, ptr_ptr as u32 as usize, &ptr.to_le_bytes()))
+        .map_err(|e| Trap::new(e.to_string()))
+}
+
+/// Parses a log serialized in borsh, i.e. the topic and the value as `Vec<u8>`.
+fn parse_log(bytes: &[u8]) -> Option<LogEntry> {
+    let mut bytes = bytes;
+    let topic = take_bytes(&mut bytes)?;
+    let value = take_bytes(&mut bytes)?;
+    Some(LogEntry {
+        topic: String::from_utf8_lossy(&topic).to_string(),
+        value: String::from_utf8_lossy(&value).to_string(),
+    })
+}
+
+/// Takes a borsh-serialized `Vec<u8>` from the front of the bytes.
+fn take_bytes(bytes: &mut &[u8]) -> Option<Vec<u8>> {
+    let len = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()) as usize;
+    take(bytes, len).map(|content| content.to_vec())
+}
+
+/// Takes `len` bytes from the front of the bytes.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+/* Borsh serialization by type names */
+
+/// A type as written in the ABI, for the types that can be serialized from text.
+#[derive(Debug, PartialEq)]
+enum AbiType {
+    Bool,
+    Int { bytes: usize, signed: bool },
+    String,
+    Vec(Box<AbiType>),
+    Array(Box<AbiType>, usize),
+    Option(Box<AbiType>),
+    Tuple(Vec<AbiType>),
+}
+
+impl AbiType {
+    /// Parses a type as written in the ABI, e.g. `Vec<u8>` or `[u8;32]`. It returns None if the type is not supported.
+    fn parse(ty: &str) -> Option<Self> {
+        let ty = ty.trim().trim_start_matches('&');
+        if let Some(inner) = ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+            return split_top_level(inner, ',')
+                .into_iter()
+                .filter(|element| !element.trim().is_empty())
+                .map(AbiType::parse)
+                .collect::<Option<Vec<_>>>()
+                .map(AbiType::Tuple);
+        }
+        if let Some(inner) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            return match split_top_level(inner, ';').as_slice() {
+                [element, len] => Some(AbiType::Array(
+                    Box::new(AbiType::parse(element)?),
+                    len.trim().parse().ok()?,
+                )),
+                _ => None,
+            };
+        }
+
+        let (path, generics) = match ty.find('<') {
+            Some(index) => (&ty[..index], split_top_level(ty[index + 1..].strip_suffix('>')?, ',')),
+            None => (ty, vec![]),
+        };
+        let name = path.rsplit("::").next().unwrap();
+        match (name, generics.as_slice()) {
+            ("bool", []) => Some(AbiType::Bool),
+            ("String" | "str", []) => Some(AbiType::String),
+            ("PublicAddress" | "Sha256Hash", []) => Some(AbiType::Array(Box::new(AbiType::u8()), 32)),
+            ("Vec", [element]) => Some(AbiType::Vec(Box::new(AbiType::parse(element)?))),
+            ("Option", [element]) => Some(AbiType::Option(Box::new(AbiType::parse(element)?))),
+            (name, []) => {
+                let signed = name.starts_with('i');
+                let bits: usize = name.strip_prefix(['u', 'i'])?.parse().ok()?;
+                [8, 16, 32, 64, 128].contains(&bits).then_some(AbiType::Int { bytes: bits / 8, signed })
+            }
+            _ => None,
+        }
+    }
+
+    fn u8() -> Self {
+        AbiType::Int { bytes: 1, signed: false }
+    }
+
+    fn encode(&self, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+        match (self, value) {
+            (AbiType::Bool, Value::Bool(value)) => out.push(*value as u8),
+            (AbiType::Int { bytes, signed }, Value::Number(_) | Value::String(_)) => {
+                let text = match value {
+                    Value::String(text) => text.clone(),
+                    _ => value.to_string(),
+                };
+                let encoded = if *signed {
+                    let number: i128 = text.parse().map_err(|_| format!("{text} is not an integer"))?;
+                    let bits = *bytes as u32 * 8;
+                    if bits < 128 && (number >= 1 << (bits - 1) || number < -(1 << (bits - 1))) {
+                        return Err(format!("{text} is out of the range of i{bits}"));
+                    }
+                    number.to_le_bytes()
+                } else {
+                    let number: u128 = text.parse().map_err(|_| format!("{text} is not an unsigned integer"))?;
+                    let bits = *bytes as u32 * 8;
+                    if bits < 128 && number >> bits != 0 {
+                        return Err(format!("{text} is out of the range of u{bits}"));
+                    }
+                    number.to_le_bytes()
+                };
+                out.extend(&encoded[..*bytes]);
+            }
+            (AbiType::String, Value::String(value)) => {
+                out.extend((value.len() as u32).to_le_bytes());
+                out.extend(value.as_bytes());
+            }
+            (AbiType::String, value) => AbiType::String.encode(&Value::String(value.to_string()), out)?,
+            (AbiType::Vec(element), Value::String(hex)) if **element == AbiType::u8() => {
+                let bytes = decode_hex(hex).ok_or(format!("{hex} is not in hex"))?;
+                out.extend((bytes.len() as u32).to_le_bytes());
+                out.extend(bytes);
+            }
+            (AbiType::Vec(element), Value::Array(values)) => {
+                out.extend((values.len() as u32).to_le_bytes());
+                for value in values {
+                    element.encode(value, out)?;
+                }
+            }
+            (AbiType::Array(element, len), Value::String(hex)) if **element == AbiType::u8() => {
+                let bytes = decode_hex(hex).ok_or(format!("{hex} is not in hex"))?;
+                if bytes.len() != *len {
+                    return Err(format!("{hex} is not {len} bytes"));
+                }
+                out.extend(bytes);
+            }
+            (AbiType::Array(element, len), Value::Array(values)) => {
+                if values.len() != *len {
+                    return Err(format!("{value} does not have {len} elements"));
+                }
+                for value in values {
+                    element.encode(value, out)?;
+                }
+            }
+            (AbiType::Option(_), Value::Null) => out.push(0),
+            (AbiType::Option(element), value) => {
+                out.push(1);
+                element.encode(value, out)?;
+            }
+            (AbiType::Tuple(elements), Value::Array(values)) if elements.len() == values.len() => {
+                for (element, value) in elements.iter().zip(values) {
+                    element.encode(value, out)?;
+                }
+            }
+            (ty, value) => return Err(format!("{value} cannot be serialized as {ty:?}")),
+        }
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &mut &[u8]) -> Option<Value> {
+        Some(match self {
+            AbiType::Bool => match take(bytes, 1)? {
+                [0] => Value::Bool(false),
+                [1] => Value::Bool(true),
+                _ => return None,
+            },
+            AbiType::Int { bytes: len, signed } => {
+                let le_bytes = take(bytes, *len)?;
+                let fill = if *signed && le_bytes[len - 1] >= 0x80 { 0xff } else { 0 };
+                let mut buffer = [fill; 16];
+                buffer[..*len].copy_from_slice(le_bytes);
+                match (*len, *signed) {
+                    (16, true) => Value::String(i128::from_le_bytes(buffer).to_string()),
+                    (16, false) => Value::String(u128::from_le_bytes(buffer).to_string()),
+                    (_, true) => Value::from(i128::from_le_bytes(buffer) as i64),
+                    (_, false) => Value::from(u128::from_le_bytes(buffer) as u64),
+                }
+            }
+            AbiType::String => Value::String(String::from_utf8(take_bytes(bytes)?).ok()?),
+            AbiType::Vec(element) if **element == AbiType::u8() => Value::String(encode_hex(&take_bytes(bytes)?)),
+            AbiType::Vec(element) => {
+                let len = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+                Value::Array((0..len).map(|_| element.decode(bytes)).collect::<Option<_>>()?)
+            }
+            AbiType::Array(element, len) if **element == AbiType::u8() => Value::String(encode_hex(take(bytes, *len)?)),
+            AbiType::Array(element, len) => {
+                Value::Array((0..*len).map(|_| element.decode(bytes)).collect::<Option<_>>()?)
+            }
+            AbiType::Option(element) => match take(bytes, 1)? {
+                [0] => Value::Null,
+                [1] => element.decode(bytes)?,
+                _ => return None,
+            },
+            AbiType::Tuple(elements) => {
+                Value::Array(elements.iter().map(|element| element.decode(bytes)).collect::<Option<_>>()?)
+            }
+        })
+    }
+}
+
+/// Serializes an argument given in text form according to its type in the ABI:
+/// - Integers, booleans and strings are written as is, e.g. `42`, `true` or `Alice`.
+/// - `Vec<u8>` and byte arrays (including `PublicAddress`) are written in hex, e.g. `0x0102`.
+/// - Other vectors, arrays and tuples are written as JSON arrays, e.g. `[1, 2]`. `Option` is written as `null` or the value.
+///
+/// Arguments of other types can be written as their borsh serialization in hex.
+pub fn encode_argument(ty: &str, text: &str) -> Result<Vec<u8>, String> {
+    let Some(abi_type) = AbiType::parse(ty) else {
+        return decode_hex(text).ok_or(format!(
+            "Type {ty} is not supported. Please provide the borsh-serialized argument in hex."
+        ));
+    };
+    let value = match abi_type {
+        AbiType::String => Value::String(text.to_string()),
+        _ => serde_json::from_str(text).unwrap_or(Value::String(text.to_string())),
+    };
+    let mut bytes = vec![];
+    abi_type.encode(&value, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Deserializes a borsh-serialized value into JSON according to its type in the ABI. It returns None if the type is
+/// not supported or the bytes do not match the type.
+fn decode_value(ty: &str, bytes: &[u8]) -> Option<Value> {
+    let mut bytes = bytes;
+    let value = AbiType::parse(ty)?.decode(&mut bytes)?;
+    bytes.is_empty().then_some(value)
+}
+
+/// Splits the text by the separator, ignoring the separators inside brackets.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>())
+}
+
+/// Decodes hex with or without the prefix `0x`.
+#[allow(clippy::manual_is_multiple_of)] // usize::is_multiple_of requires Rust 1.87
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let hex = text.strip_prefix("0x").unwrap_or(text);
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Shows a value in hex, truncated if it is long.
+fn short_hex(hex: &str) -> String {
+    let bytes = hex.len().saturating_sub(2) / 2;
+    if bytes <= VALUE_BYTES_SHOWN {
+        hex.to_string()
+    } else {
+        format!("{}... ({bytes} bytes)", &hex[..2 + VALUE_BYTES_SHOWN * 2])
+    }
+}
+
+impl Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.topic, self.value)
+    }
+}
+
+impl Display for StorageChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Show the key as text if it is printable, e.g. the keys set by `pchain_sdk::storage::set`.
+        let key = decode_hex(&self.key)
+            .and_then(|key| String::from_utf8(key).ok())
+            .filter(|key| !key.is_empty() && key.chars().all(|c| c.is_ascii_graphic() || c == ' '))
+            .map_or(self.key.clone(), |key| format!("\"{key}\""));
+        match &self.field {
+            Some(field) => write!(f, "{key} (field {field}): ")?,
+            None => write!(f, "{key}: ")?,
+        }
+        match &self.old_value {
+            Some(old_value) => write!(f, "{} -> {}", short_hex(old_value), short_hex(&self.new_value)),
+            None => write!(f, "(new) {}", short_hex(&self.new_value)),
+        }
+    }
+}
+
+impl Display for RunResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Logs:")?;
+        if self.logs.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for log in &self.logs {
+            writeln!(f, "- {log}")?;
+        }
+
+        match &self.return_value {
+            Some(ReturnValue { bytes, decoded: Some(decoded) }) => writeln!(f, "Return value: {decoded} ({})", short_hex(bytes))?,
+            Some(ReturnValue { bytes, decoded: None }) => writeln!(f, "Return value: {}", short_hex(bytes))?,
+            None => writeln!(f, "Return value: (none)")?,
+        }
+
+        writeln!(f, "Storage changes:")?;
+        if self.storage_changes.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for change in &self.storage_changes {
+            writeln!(f, "- {change}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implements the compilation process of smart contract in a Linux user-namespace sandbox created by
+//! [bubblewrap](https://github.com/containers/bubblewrap), as an alternative to docker without a daemon.
+//!
+//! The flow of the compilation process is as follows:
+//! 1. Check if bubblewrap can be run, then copy the source code and its path dependencies to the working folder, and
+//!    fetch the dependencies by `cargo fetch` on the copy outside the sandbox. It generates the Cargo.lock in the copy
+//!    if it does not exist, so the source code is never modified.
+//! 2. Build the copy by `cargo build --offline` in the sandbox. The sandbox has no network, the copy and the toolchain
+//!    are mounted read-only, and `/tmp`, the cargo home and the target folder are private to the build.
+//! 3. Optimize and snip the cargo output outside the sandbox, as in building without docker (see [crate::optimize]).
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use tokio::process::Command;
+
+use crate::{error::Error, BuildOptions, SandboxConfig};
+
+/// System folders mounted read-only in the sandbox, if they exist.
+const SYSTEM_FOLDERS: [&str; 6] = ["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc"];
+
+/// Builds the contract at `source_path` in the sandbox. The cargo output `cargo_wasm_file` is optimized and saved as
+/// `wasm_file` in the output folder. The working folder keeps the target folder and the intermediate binaries for
+/// size report. See [crate::cargo::build_contract].
+///
+/// It returns the build log if success.
+pub(crate) async fn build_contract(
+    sandbox_config: &SandboxConfig,
+    working_folder: &Path,
+    source_path: &Path,
+    output_path: &Path,
+    options: &BuildOptions,
+    cargo_wasm_file: &str,
+    wasm_file: &str,
+) -> Result<String, Error> {
+    // Does not set "--locked" if the Cargo.lock file does not exist.
+    let use_cargo_lock = (options.locked || options.frozen) && source_path.join("Cargo.lock").exists();
+    let lockfile_failure = |build_log: String| match options.frozen && crate::cargo::is_lockfile_outdated(&build_log) {
+        true => Error::LockfileOutdated(build_log),
+        false => Error::BuildFailureWithLogs(build_log),
+    };
+
+    // Check if bubblewrap can be run before fetching the dependencies.
+    let bwrap_binary = sandbox_config.bwrap.as_deref().unwrap_or("bwrap");
+    let mut bwrap_version = Command::new(bwrap_binary);
+    bwrap_version.arg("--version");
+    if !output(bwrap_version).await?.0 {
+        return Err(Error::SandboxFailure(format!("Fail to run {bwrap_binary}.")));
+    }
+
+    // Copy the source code and the path dependencies, keeping their relative locations.
+    let mut dependencies = HashSet::new();
+    crate::manifests::get_dependency_paths(source_path, &mut dependencies)?;
+    let copy_folder = working_folder.join("source");
+    let copy = |path: &Path| {
+        let copied_path = copy_folder.join(crate::docker::container_path(path.to_str().unwrap()));
+        copy_source(path, &copied_path)
+            .map(|_| copied_path)
+            .map_err(|e| Error::SandboxFailure(format!("Fail to copy {}: {e}", path.display())))
+    };
+    let copied_dependencies = dependencies
+        .into_iter()
+        .map(|path| copy(Path::new(&path)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let source_path = copy(source_path)?;
+
+    // 1. cargo fetch, which needs network
+    let manifest_path = source_path.join("Cargo.toml");
+    let mut cmd_cargo_fetch = vec!["fetch", "--manifest-path", manifest_path.to_str().unwrap()];
+    if use_cargo_lock {
+        cmd_cargo_fetch.push("--locked");
+    }
+    let mut cargo_fetch = toolchain_command(sandbox_config, "cargo", &source_path);
+    cargo_fetch.args(&cmd_cargo_fetch);
+    let (success, fetch_log) = output(cargo_fetch).await?;
+    if !success {
+        return Err(lockfile_failure(fetch_log));
+    }
+
+    // 2. cargo build --offline --target wasm32-unknown-unknown --release, in the sandbox
+    let target_folder = working_folder.join("target");
+    let mut bwrap = sandbox_command(sandbox_config, working_folder, &source_path, copied_dependencies, &target_folder)?;
+    bwrap.args(["cargo", "build", "--offline", "--target", "wasm32-unknown-unknown", "--release"]);
+    if use_cargo_lock {
+        bwrap.arg("--locked");
+    }
+    let features = options.features.join(",");
+    if !features.is_empty() {
+        bwrap.args(["--features", &features]);
+    }
+    let (success, build_log) = output(bwrap).await?;
+    if !success {
+        return Err(lockfile_failure(build_log));
+    }
+
+    // Save Cargo.lock to output folder.
+    let _ = std::fs::copy(source_path.join("Cargo.lock"), output_path.join("Cargo.lock"));
+
+    // 3. wasm-opt, wasm-snip and wasm-opt --dce
+    let cargo_wasm = target_folder
+        .join("wasm32-unknown-unknown")
+        .join("release")
+        .join(cargo_wasm_file);
+    crate::optimize::post_process(&cargo_wasm, working_folder, &output_path.join(wasm_file), options.size_report)?;
+
+    Ok(build_log)
+}
+
+/// Returns the output of `rustc --version` of the toolchain used in the sandbox to build the source code. The toolchain
+/// can be selected by the file `rust-toolchain.toml` in the source code folder.
+pub(crate) async fn rustc_version(sandbox_config: &SandboxConfig, source_path: &Path) -> Option<String> {
+    let mut rustc = toolchain_command(sandbox_config, "rustc", source_path);
+    rustc.arg("--version");
+    rustc
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the command to run bubblewrap. The sandbox has its own namespaces (including network), and is killed
+/// together with pchain_compile. The cargo home in the sandbox is a private folder, in which the crates fetched
+/// to the cargo home of the current user are mounted read-only.
+fn sandbox_command(
+    sandbox_config: &SandboxConfig,
+    working_folder: &Path,
+    source_path: &Path,
+    dependencies: impl IntoIterator<Item = PathBuf>,
+    target_folder: &Path,
+) -> Result<Command, Error> {
+    let cargo_home = cargo_home().ok_or(Error::SandboxFailure("Cargo home is not found.".to_string()))?;
+    let rustup_home = rustup_home().ok_or(Error::SandboxFailure("Rustup home is not found.".to_string()))?;
+    let sandbox_cargo_home = working_folder.join("cargo_home");
+    std::fs::create_dir_all(&sandbox_cargo_home).map_err(|_| Error::CreateTempDir)?;
+
+    let mut bwrap = Command::new(sandbox_config.bwrap.as_deref().unwrap_or("bwrap"));
+    bwrap.args(["--unshare-all", "--die-with-parent", "--new-session"]);
+    for folder in SYSTEM_FOLDERS {
+        bwrap.args(["--ro-bind-try", folder, folder]);
+    }
+    bwrap.args(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+    bwrap.arg("--ro-bind-try").args([&rustup_home, &rustup_home]);
+    bwrap.arg("--bind").args([working_folder, working_folder]);
+    for folder in ["bin", "registry", "git"] {
+        bwrap.arg("--ro-bind-try").args([cargo_home.join(folder), sandbox_cargo_home.join(folder)]);
+    }
+    for path in dependencies.into_iter().chain([source_path.to_path_buf()]) {
+        bwrap.arg("--ro-bind").args([&path, &path]);
+    }
+
+    let path = std::env::join_paths([sandbox_cargo_home.join("bin"), PathBuf::from("/usr/bin"), PathBuf::from("/bin")])
+        .map_err(|e| Error::SandboxFailure(e.to_string()))?;
+    bwrap.arg("--setenv").arg("PATH").arg(path);
+    bwrap.args(["--setenv", "HOME", "/tmp"]);
+    bwrap.arg("--setenv").arg("CARGO_HOME").arg(&sandbox_cargo_home);
+    bwrap.arg("--setenv").arg("RUSTUP_HOME").arg(&rustup_home);
+    bwrap.arg("--setenv").arg("CARGO_TARGET_DIR").arg(target_folder);
+    if let Some(toolchain) = &sandbox_config.toolchain {
+        bwrap.args(["--setenv", "RUSTUP_TOOLCHAIN", toolchain]);
+    }
+    bwrap.arg("--chdir").arg(source_path);
+    bwrap.arg("--");
+    Ok(bwrap)
+}
+
+/// Returns the command to run a binary of the toolchain (e.g. `cargo`) outside the sandbox. It runs in `current_dir`,
+/// so that rustup selects the same toolchain as the build in the sandbox.
+fn toolchain_command(sandbox_config: &SandboxConfig, program: &str, current_dir: &Path) -> Command {
+    let mut command = Command::new(program);
+    command.current_dir(current_dir);
+    if let Some(toolchain) = &sandbox_config.toolchain {
+        command.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+    command
+}
+
+/// Runs the command, and returns if it succeeds and its output (stderr followed by stdout). The process is killed
+/// if the returned future is dropped, e.g. on cancellation.
+async fn output(mut command: Command) -> Result<(bool, String), Error> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let output = command
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::SandboxFailure(format!("Fail to run {program}: {e}")))?;
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    Ok((output.status.success(), log))
+}
+
+/// Copies the folder `from` to `to`. The target folder of a cargo package is skipped.
+fn copy_source(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    let is_package = from.join("Cargo.toml").exists();
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if std::fs::metadata(&path)?.is_dir() {
+            if !(is_package && entry.file_name() == "target") {
+                copy_source(&path, &to.join(entry.file_name()))?;
+            }
+        } else {
+            std::fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or(std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")))
+}
+
+fn rustup_home() -> Option<PathBuf> {
+    std::env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or(std::env::var_os("HOME").map(|home| Path::new(&home).join(".rustup")))
+}
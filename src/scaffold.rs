@@ -0,0 +1,246 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the project scaffolding, which creates a contract project that is ready to be built. The project
+//! contains:
+//! - `Cargo.toml` with `crate-type = ["cdylib"]`, the dependency `pchain-sdk` (see [PCHAIN_SDK_VERSION]), and a
+//!   release profile that optimizes for size,
+//! - `src/lib.rs` generated from a [Template],
+//! - `.gitignore` that ignores the folder `target`.
+
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Version of `pchain-sdk` in the generated manifest. It is the version that the contracts built by the docker image
+/// of this version of pchain_compile are tested with.
+pub const PCHAIN_SDK_VERSION: &str = "0.4.2";
+
+/// Template of the file `src/lib.rs` of a new contract.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Template {
+    /// A contract with one method that writes a log and returns a value. (Default)
+    #[default]
+    Minimal,
+    /// A contract with a storage field, and methods to read and update it.
+    Storage,
+    /// A contract that calls a method of another contract.
+    CrossContractCall,
+}
+
+impl std::str::FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "storage" => Ok(Self::Storage),
+            "cross-contract-call" => Ok(Self::CrossContractCall),
+            _ => Err(format!(
+                "unknown template \"{s}\", expected one of: minimal, storage, cross-contract-call"
+            )),
+        }
+    }
+}
+
+/// `new_contract` creates a contract project in a new folder. The name of the folder is used as the package name.
+/// It returns the package name.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` new `path` --template `template`
+pub fn new_contract(path: &Path, template: Template) -> Result<String, Error> {
+    if path.exists() {
+        return Err(Error::ScaffoldFailure(format!(
+            "Destination {} already exists.",
+            path.display()
+        )));
+    }
+    let name = package_name_of(path)?;
+    check_package_name(&name)?;
+    std::fs::create_dir_all(path)
+        .map_err(|e| Error::ScaffoldFailure(format!("Fail to create {}: {e}", path.display())))?;
+    write_project(path, &name, template)?;
+    Ok(name)
+}
+
+/// `init_contract` creates a contract project in an existing folder. The package name is `name`, or the name of
+/// the folder if it is None. It fails without writing any file if the folder already contains `Cargo.toml` or
+/// `src/lib.rs`. It returns the package name.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` init `path` --name `name` --template `template`
+pub fn init_contract(path: &Path, name: Option<&str>, template: Template) -> Result<String, Error> {
+    if !path.is_dir() {
+        return Err(Error::ScaffoldFailure(format!(
+            "{} is not a folder.",
+            path.display()
+        )));
+    }
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => package_name_of(path)?,
+    };
+    check_package_name(&name)?;
+    for file in ["Cargo.toml", "src/lib.rs"] {
+        if path.join(file).exists() {
+            return Err(Error::ScaffoldFailure(format!(
+                "{} already exists in {}.",
+                file,
+                path.display()
+            )));
+        }
+    }
+    write_project(path, &name, template)?;
+    Ok(name)
+}
+
+fn write_project(path: &Path, name: &str, template: Template) -> Result<(), Error> {
+    let write = |file: &str, content: &str| {
+        let file_path = path.join(file);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file_path, content)
+    };
+    let written = write("Cargo.toml", &manifest(name))
+        .and_then(|_| write("src/lib.rs", &library(name, template)));
+    // Keep the existing .gitignore of the folder
+    let written = written.and_then(|_| match path.join(".gitignore").exists() {
+        true => Ok(()),
+        false => write(".gitignore", "/target\n"),
+    });
+    written.map_err(|e| Error::ScaffoldFailure(format!("Fail to write to {}: {e}", path.display())))
+}
+
+/// Returns the package name from the name of the folder. A relative path like "." is resolved first.
+fn package_name_of(path: &Path) -> Result<String, Error> {
+    let path = dunce::canonicalize(path).unwrap_or(path.to_path_buf());
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or(Error::ScaffoldFailure(format!(
+            "Fail to get the package name from {}.",
+            path.display()
+        )))
+}
+
+/// Checks if the name can be used as a package name and the library name, i.e. it starts with a letter and contains
+/// only letters, digits, `-` and `_`.
+fn check_package_name(name: &str) -> Result<(), Error> {
+    let starts_with_letter = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+    let valid_chars = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !starts_with_letter || !valid_chars {
+        return Err(Error::ScaffoldFailure(format!(
+            "\"{name}\" is not a valid package name. It should start with a letter and contain only letters, digits, \"-\" and \"_\"."
+        )));
+    }
+    Ok(())
+}
+
+fn manifest(name: &str) -> String {
+    format!(
+        r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+pchain-sdk = "{PCHAIN_SDK_VERSION}"
+
+# Optimize the contract for size. Smaller contracts cost less gas to deploy.
+[profile.release]
+opt-level = "z"
+lto = true
+codegen-units = 1
+panic = "abort"
+"#
+    )
+}
+
+fn library(name: &str, template: Template) -> String {
+    let struct_name = struct_name(name);
+    match template {
+        Template::Minimal => format!(
+            r#"use pchain_sdk::{{call, contract, contract_methods}};
+
+#[contract]
+struct {struct_name} {{}}
+
+#[contract_methods]
+impl {struct_name} {{
+    #[call]
+    fn hello(name: String) -> String {{
+        pchain_sdk::log("hello".as_bytes(), name.as_bytes());
+        format!("Hello, {{name}}!")
+    }}
+}}
+"#
+        ),
+        Template::Storage => format!(
+            r#"use pchain_sdk::{{call, contract, contract_methods}};
+
+#[contract]
+struct {struct_name} {{
+    count: u64,
+}}
+
+#[contract_methods]
+impl {struct_name} {{
+    #[call]
+    fn count(&self) -> u64 {{
+        self.count
+    }}
+
+    #[call]
+    fn increment(&mut self, amount: u64) -> u64 {{
+        self.count = self.count.saturating_add(amount);
+        pchain_sdk::log("increment".as_bytes(), self.count.to_string().as_bytes());
+        self.count
+    }}
+
+    #[call]
+    fn reset(&mut self) {{
+        self.count = 0;
+    }}
+}}
+"#
+        ),
+        Template::CrossContractCall => format!(
+            r#"use pchain_sdk::{{call, contract, contract_methods, method::ContractMethodInputBuilder}};
+
+#[contract]
+struct {struct_name} {{}}
+
+#[contract_methods]
+impl {struct_name} {{
+    /// Calls the method "hello" of the contract at `contract_address` with `name`, and returns its result.
+    #[call]
+    fn call_hello(contract_address: [u8; 32], name: String) -> String {{
+        let arguments = ContractMethodInputBuilder::new().add(name).to_call_arguments();
+        pchain_sdk::call::<String>(contract_address, "hello", arguments, 0).unwrap_or_default()
+    }}
+}}
+"#
+        ),
+    }
+}
+
+/// Converts the package name to a struct name in upper camel case, e.g. `my-contract` to `MyContract`.
+fn struct_name(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
@@ -0,0 +1,300 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the size profiler. It reports the largest functions, their sizes grouped by crate,
+//! the data segments, and how many bytes each post-processing step removed.
+//!
+//! `wasm-opt` drops the name section, so the functions in the final module cannot be named. When the report
+//! is generated during a build (see [crate::BuildOptions::size_report]), the cargo output is additionally
+//! optimized with `wasm-opt -Oz -g`, which keeps the name section. The functions are profiled from this
+//! named module, which is the module before `wasm-snip` as far as code is concerned.
+
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, inspect::DataSegmentInfo, BuildOptions};
+
+/// File name of the module optimized with the name section kept, in the folder of intermediate binaries.
+pub(crate) const NAMED_WASM: &str = "named.wasm";
+/// File name of the module after `wasm-opt -Oz`, in the folder of intermediate binaries.
+pub(crate) const OPTIMIZED_WASM: &str = "temp.wasm";
+/// File name of the module after `wasm-snip`, in the folder of intermediate binaries.
+pub(crate) const SNIPPED_WASM: &str = "temp2.wasm";
+
+/// Number of functions shown in the text format of the report.
+const LARGEST_FUNCTIONS_SHOWN: usize = 20;
+
+/// Size profile of a WebAssembly binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeReport {
+    /// Size of the binary in bytes.
+    pub total_size: usize,
+    /// Total size of the function bodies in bytes.
+    pub code_size: usize,
+    /// Total size of the data segments in bytes.
+    pub data_size: usize,
+    /// Sizes of the binary after each building step. None if the report is not generated during a build.
+    pub stages: Option<StageSizes>,
+    /// Functions in descending order of size.
+    pub functions: Vec<FunctionSize>,
+    /// Sizes of the functions grouped by crate, in descending order of size.
+    pub crates: Vec<CrateSize>,
+    /// Data segments in the order they are defined.
+    pub data_segments: Vec<DataSegmentInfo>,
+}
+
+/// Sizes of the binary (in bytes) after each building step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageSizes {
+    /// Output of cargo build.
+    pub cargo_build: usize,
+    /// After `wasm-opt -Oz`.
+    pub wasm_opt: usize,
+    /// After `wasm-snip`.
+    pub wasm_snip: usize,
+    /// After `wasm-opt --dce`, i.e. the final binary.
+    pub final_size: usize,
+    /// Bytes removed by both runs of `wasm-opt`.
+    pub removed_by_wasm_opt: usize,
+    /// Bytes removed by `wasm-snip`. It includes the custom sections (e.g. DWARF debug information) that are
+    /// dropped when the module is rewritten in this step.
+    pub removed_by_wasm_snip: usize,
+}
+
+/// Size of a function body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionSize {
+    /// Demangled name from the name section, or `func[<index>]` if the name is not available.
+    pub name: String,
+    /// Crate that the function belongs to. See [crate_of].
+    pub crate_name: String,
+    /// Size of the function body in bytes.
+    pub size: usize,
+}
+
+/// Total size of the functions of a crate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateSize {
+    pub name: String,
+    /// Total size of the function bodies in bytes.
+    pub size: usize,
+    /// Number of functions.
+    pub functions: usize,
+}
+
+/// `size_report` reads the WebAssembly binary at `wasm_path` and profiles its size. Functions are named only
+/// if the binary contains the name section (e.g. the output of cargo build before optimization).
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` size `wasm_path`
+pub fn size_report(wasm_path: &Path) -> Result<SizeReport, Error> {
+    let wasm = read_wasm(wasm_path)?;
+    report(&wasm, &wasm, None)
+}
+
+/// Reads the size report saved next to a contract built with [crate::BuildOptions::size_report].
+pub fn read_size_report(report_path: &Path) -> Result<SizeReport, Error> {
+    let content = std::fs::read(report_path)
+        .map_err(|e| Error::BuildFailure(format!("Fail to read {}: {e}", report_path.display())))?;
+    serde_json::from_slice(&content).map_err(|e| Error::BuildFailure(format!("Malformed size report: {e}")))
+}
+
+/// Returns the file name of the size report of a contract, e.g. `contract.size.json` for `contract.wasm`.
+pub fn size_report_file(wasm_file: &str) -> String {
+    format!("{}.size.json", wasm_file.trim_end_matches(".wasm"))
+}
+
+/// Generates the size report from the intermediate binaries of a build and the final binary, and saves
+/// it next to the final binary.
+pub(crate) fn save_build_size_report(
+    intermediates_path: &Path,
+    output_path: &Path,
+    wasm_file: &str,
+) -> Result<(), Error> {
+    let cargo_build = read_wasm(&intermediates_path.join(wasm_file))?;
+    let optimized = read_wasm(&intermediates_path.join(OPTIMIZED_WASM))?;
+    let snipped = read_wasm(&intermediates_path.join(SNIPPED_WASM))?;
+    let named = read_wasm(&intermediates_path.join(NAMED_WASM))?;
+    let final_wasm = read_wasm(&output_path.join(wasm_file))?;
+
+    let stages = StageSizes {
+        cargo_build: cargo_build.len(),
+        wasm_opt: optimized.len(),
+        wasm_snip: snipped.len(),
+        final_size: final_wasm.len(),
+        removed_by_wasm_opt: cargo_build.len().saturating_sub(optimized.len())
+            + snipped.len().saturating_sub(final_wasm.len()),
+        removed_by_wasm_snip: optimized.len().saturating_sub(snipped.len()),
+    };
+    let report = report(&final_wasm, &named, Some(stages))?;
+
+    let content = serde_json::to_vec_pretty(&report)
+        .map_err(|e| Error::BuildFailure(format!("Fail to save size report: {e}")))?;
+    std::fs::write(output_path.join(size_report_file(wasm_file)), content)
+        .map_err(|e| Error::BuildFailure(format!("Fail to save size report: {e}")))
+}
+
+/// Checks the size of the compiled contract at `wasm_path` against the maximum size and the baseline artifact
+/// in the build options. The build metadata and ABI sections are excluded from the sizes of both binaries.
+pub(crate) fn check_size_budget(wasm_path: &Path, wasm_file: &str, options: &BuildOptions) -> Result<(), Error> {
+    let size = appended_sections_stripped(&read_wasm(wasm_path)?)?.len();
+
+    if let Some(max_size) = options.max_wasm_size {
+        if size > max_size {
+            return Err(Error::WasmSizeExceeded { size, max_size });
+        }
+    }
+
+    if let Some(baseline) = &options.size_baseline {
+        let baseline_path = if baseline.wasm_path.is_dir() {
+            baseline.wasm_path.join(wasm_file)
+        } else {
+            baseline.wasm_path.clone()
+        };
+        let baseline_size = appended_sections_stripped(&read_wasm(&baseline_path)?)?.len();
+        let max_size = baseline_size as f64 * (1.0 + baseline.max_growth_percent / 100.0);
+        if size as f64 > max_size {
+            return Err(Error::WasmSizeGrowthExceeded {
+                size,
+                baseline_size,
+                max_growth_percent: baseline.max_growth_percent,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Removes the custom sections that are appended after post-processing, i.e. the build metadata and the ABI.
+fn appended_sections_stripped(wasm: &[u8]) -> Result<Vec<u8>, Error> {
+    crate::abi::strip_abi(&crate::metadata::strip_metadata(wasm)?)
+}
+
+fn read_wasm(wasm_path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(wasm_path)
+        .map_err(|e| Error::InvalidWasmFile(format!("Fail to read {}: {e}", wasm_path.display())))
+}
+
+/// Profiles the final binary `wasm`. The functions are taken from `named_wasm`, which can be the same binary.
+fn report(wasm: &[u8], named_wasm: &[u8], stages: Option<StageSizes>) -> Result<SizeReport, Error> {
+    let inspection = crate::inspect::inspect_bytes(wasm)?;
+    let module = walrus::ModuleConfig::new()
+        .parse(named_wasm)
+        .map_err(|e| Error::InvalidWasmFile(format!("{:?}", e)))?;
+
+    // Function bodies are in the same order as the local functions
+    let body_sizes = crate::wasm::function_body_sizes(named_wasm)?;
+    let mut functions: Vec<FunctionSize> = module
+        .funcs
+        .iter_local()
+        .zip(body_sizes)
+        .map(|((id, _), size)| {
+            let (name, crate_name) = match &module.funcs.get(id).name {
+                Some(name) => {
+                    let name = format!("{:#}", rustc_demangle::demangle(name));
+                    let crate_name = crate_of(&name);
+                    (name, crate_name)
+                }
+                None => (format!("func[{}]", id.index()), "(unnamed)".to_string()),
+            };
+            FunctionSize { name, crate_name, size }
+        })
+        .collect();
+    functions.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    let mut crates: HashMap<&str, CrateSize> = HashMap::new();
+    for function in &functions {
+        let entry = crates.entry(&function.crate_name).or_insert(CrateSize {
+            name: function.crate_name.clone(),
+            size: 0,
+            functions: 0,
+        });
+        entry.size += function.size;
+        entry.functions += 1;
+    }
+    let mut crates: Vec<CrateSize> = crates.into_values().collect();
+    crates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(SizeReport {
+        total_size: wasm.len(),
+        code_size: crate::wasm::function_body_sizes(wasm)?.iter().sum(),
+        data_size: inspection.data_segments.iter().map(|data| data.size).sum(),
+        stages,
+        functions,
+        crates,
+        data_segments: inspection.data_segments,
+    })
+}
+
+/// Returns the crate of a demangled function name, i.e. the first segment of its path. For trait implementations
+/// like `<alloc::string::String as core::fmt::Display>::fmt`, it is the crate of the implementing type. Implementations
+/// on primitive types (e.g. `<char>::escape_debug_ext`) are attributed to the crate of the trait, or `core` if there is
+/// no trait. Names that are not paths (e.g. `memcpy`) are grouped as `(other)`.
+fn crate_of(name: &str) -> String {
+    let path = name
+        .trim_start_matches(['<', '&', '*', '[', '('])
+        .trim_start_matches("mut ")
+        .trim_start_matches("dyn ");
+    let ident_len = path
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(path.len());
+    let (ident, rest) = path.split_at(ident_len);
+    if !ident.is_empty() && rest.starts_with("::") {
+        return ident.to_string();
+    }
+    if name.starts_with('<') {
+        return match name.split_once(" as ") {
+            Some((_, trait_path)) => crate_of(trait_path),
+            None => "core".to_string(),
+        };
+    }
+    "(other)".to_string()
+}
+
+impl Display for SizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Size: {} bytes (code {} bytes, data {} bytes)", self.total_size, self.code_size, self.data_size)?;
+
+        if let Some(stages) = &self.stages {
+            writeln!(f, "\nBuilding steps:")?;
+            writeln!(f, "  cargo build        {:>10} bytes", stages.cargo_build)?;
+            writeln!(f, "  wasm-opt -Oz       {:>10} bytes", stages.wasm_opt)?;
+            writeln!(f, "  wasm-snip          {:>10} bytes", stages.wasm_snip)?;
+            writeln!(f, "  wasm-opt --dce     {:>10} bytes", stages.final_size)?;
+            writeln!(f, "  removed by wasm-opt  {} bytes", stages.removed_by_wasm_opt)?;
+            writeln!(f, "  removed by wasm-snip {} bytes", stages.removed_by_wasm_snip)?;
+        }
+
+        writeln!(
+            f,
+            "\nLargest functions ({} of {}):",
+            self.functions.len().min(LARGEST_FUNCTIONS_SHOWN),
+            self.functions.len()
+        )?;
+        for function in self.functions.iter().take(LARGEST_FUNCTIONS_SHOWN) {
+            writeln!(f, "  {:>8} bytes  {}", function.size, function.name)?;
+        }
+
+        writeln!(f, "\nCrates:")?;
+        for crate_size in &self.crates {
+            writeln!(
+                f,
+                "  {:>8} bytes  {} ({} functions)",
+                crate_size.size, crate_size.name, crate_size.functions
+            )?;
+        }
+
+        writeln!(f, "\nData segments:")?;
+        for data in &self.data_segments {
+            let share = if self.data_size == 0 { 0.0 } else { data.size as f64 * 100.0 / self.data_size as f64 };
+            match data.offset {
+                Some(offset) => writeln!(f, "  {:>8} bytes  at offset {} ({share:.1}%)", data.size, offset)?,
+                None => writeln!(f, "  {:>8} bytes  ({share:.1}%)", data.size)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of validating a compiled contract against the rules that the ParallelChain runtime
+//! applies when a contract is deployed. The checks are:
+//! 1. Imports come only from the host functions provided by the runtime (module `env`), with matching signatures.
+//! 2. The exports `entrypoint`, `memory` and `alloc` that the runtime calls into exist. Contracts built with
+//!    pre-mainnet SDKs export `actions` instead of `entrypoint`, which is also accepted.
+//! 3. The linear memory does not exceed [MAX_MEMORY_PAGES].
+//! 4. No instructions or types from the threads, SIMD or reference types proposals are used.
+//!
+//! Floating-point instructions are reported separately by [find_float_instructions], because whether they
+//! fail the building process is configurable (see [crate::FloatPolicy]).
+
+use std::{collections::BTreeSet, fmt::Display, path::Path};
+
+use serde::Serialize;
+use walrus::{
+    ir::{Instr, LoadKind, StoreKind, Value, Visitor},
+    ExportItem, ImportKind, Module, TableKind, ValType,
+};
+
+use crate::error::Error;
+
+/// Maximum number of pages (64 KiB each) of the linear memory of a contract.
+pub const MAX_MEMORY_PAGES: u32 = 512;
+
+/// Host functions provided by the ParallelChain runtime in module `env`, with their signatures.
+const HOST_FUNCTIONS: [(&str, &str); 28] = [
+    ("set", "(i32, i32, i32, i32)"),
+    ("get", "(i32, i32, i32) -> i64"),
+    ("get_network_storage", "(i32, i32, i32) -> i64"),
+    ("balance", "() -> i64"),
+    ("block_height", "() -> i64"),
+    ("block_timestamp", "() -> i32"),
+    ("prev_block_hash", "(i32)"),
+    ("calling_account", "(i32)"),
+    ("current_account", "(i32)"),
+    ("method", "(i32) -> i32"),
+    ("arguments", "(i32) -> i32"),
+    ("amount", "() -> i64"),
+    ("is_internal_call", "() -> i32"),
+    ("transaction_hash", "(i32)"),
+    ("call", "(i32, i32, i32) -> i32"),
+    ("return_value", "(i32, i32)"),
+    ("transfer", "(i32)"),
+    ("defer_create_deposit", "(i32, i32)"),
+    ("defer_set_deposit_settings", "(i32, i32)"),
+    ("defer_topup_deposit", "(i32, i32)"),
+    ("defer_withdraw_deposit", "(i32, i32)"),
+    ("defer_stake_deposit", "(i32, i32)"),
+    ("defer_unstake_deposit", "(i32, i32)"),
+    ("_log", "(i32, i32)"),
+    ("sha256", "(i32, i32, i32)"),
+    ("keccak256", "(i32, i32, i32)"),
+    ("ripemd", "(i32, i32, i32)"),
+    ("verify_ed25519_signature", "(i32, i32, i32, i32) -> i32"),
+];
+
+/// Module name of the host functions.
+const HOST_MODULE: &str = "env";
+
+/// A rule of the ParallelChain runtime that the contract does not satisfy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Violation {
+    /// The import is not a host function provided by the runtime.
+    UnknownImport { module: String, name: String, kind: String },
+    /// The imported host function has a different signature from the one provided by the runtime.
+    ImportSignatureMismatch { name: String, expected: String, actual: String },
+    /// A required export is missing, or it is not of the expected kind or signature.
+    MissingExport { name: String, expected: String },
+    /// The initial or maximum number of pages of the linear memory exceeds [MAX_MEMORY_PAGES].
+    MemoryLimitExceeded { pages: u32, limit: u32 },
+    /// A disallowed WebAssembly proposal (threads, SIMD or reference types) is used.
+    DisallowedProposal { proposal: String, usage: String },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::UnknownImport { module, name, kind } => {
+                write!(f, "Import {kind} {module}.{name} is not provided by the runtime.")
+            }
+            Violation::ImportSignatureMismatch { name, expected, actual } => {
+                write!(f, "Import function {HOST_MODULE}.{name} has signature {actual}, expected {expected}.")
+            }
+            Violation::MissingExport { name, expected } => {
+                write!(f, "Required export {name} ({expected}) is missing.")
+            }
+            Violation::MemoryLimitExceeded { pages, limit } => {
+                write!(f, "Memory of {pages} pages exceeds the limit of {limit} pages.")
+            }
+            Violation::DisallowedProposal { proposal, usage } => {
+                write!(f, "Proposal {proposal} is not allowed, but {usage} is used.")
+            }
+        }
+    }
+}
+
+/// Floating-point instructions used in a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FloatUsage {
+    /// Name of the function from the name section, or `func[<index>]` if the name is not available.
+    pub function: String,
+    /// Distinct floating-point instructions used in the function (e.g. `F64Mul`), in sorted order.
+    pub instructions: Vec<String>,
+}
+
+impl Display for FloatUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.function, self.instructions.join(", "))
+    }
+}
+
+/// `validate_wasm` reads the WebAssembly binary at `wasm_path` and checks it against the rules of the
+/// ParallelChain runtime. It returns the violations found, which is empty if the contract is valid.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` validate `wasm_path`
+pub fn validate_wasm(wasm_path: &Path) -> Result<Vec<Violation>, Error> {
+    Ok(validate_module(&parse_module(wasm_path)?))
+}
+
+pub(crate) fn parse_module(wasm_path: &Path) -> Result<Module, Error> {
+    let wasm = std::fs::read(wasm_path)
+        .map_err(|e| Error::InvalidWasmFile(format!("Fail to read {}: {e}", wasm_path.display())))?;
+    walrus::ModuleConfig::new()
+        .parse(&wasm)
+        .map_err(|e| Error::InvalidWasmFile(format!("{:?}", e)))
+}
+
+pub(crate) fn validate_module(module: &Module) -> Vec<Violation> {
+    let mut violations = vec![];
+    check_imports(module, &mut violations);
+    check_exports(module, &mut violations);
+    check_memories(module, &mut violations);
+    check_proposals(module, &mut violations);
+    violations
+}
+
+/// `find_float_instructions` reads the WebAssembly binary at `wasm_path` and returns the functions that
+/// use floating-point instructions, in the order they are defined.
+pub fn find_float_instructions(wasm_path: &Path) -> Result<Vec<FloatUsage>, Error> {
+    Ok(float_usages(&parse_module(wasm_path)?))
+}
+
+pub(crate) fn float_usages(module: &Module) -> Vec<FloatUsage> {
+    let mut usages = vec![];
+    for (id, func) in module.funcs.iter_local() {
+        let mut instructions = FloatInstructions::default();
+        walrus::ir::dfs_in_order(&mut instructions, func, func.entry_block());
+        if !instructions.0.is_empty() {
+            usages.push(FloatUsage {
+                function: module
+                    .funcs
+                    .get(id)
+                    .name
+                    .clone()
+                    .unwrap_or(format!("func[{}]", id.index())),
+                instructions: instructions.0.into_iter().collect(),
+            });
+        }
+    }
+    usages
+}
+
+fn check_imports(module: &Module, violations: &mut Vec<Violation>) {
+    for import in module.imports.iter() {
+        let (kind, id) = match import.kind {
+            ImportKind::Function(id) => ("function", Some(id)),
+            ImportKind::Table(_) => ("table", None),
+            ImportKind::Memory(_) => ("memory", None),
+            ImportKind::Global(_) => ("global", None),
+        };
+        let expected = HOST_FUNCTIONS
+            .iter()
+            .find(|(name, _)| *name == import.name)
+            .map(|(_, signature)| *signature);
+        match (id, expected) {
+            (Some(id), Some(expected)) if import.module == HOST_MODULE => {
+                let actual = crate::inspect::function_signature(module, id);
+                if actual != expected {
+                    violations.push(Violation::ImportSignatureMismatch {
+                        name: import.name.clone(),
+                        expected: expected.to_string(),
+                        actual,
+                    });
+                }
+            }
+            _ => violations.push(Violation::UnknownImport {
+                module: import.module.clone(),
+                name: import.name.clone(),
+                kind: kind.to_string(),
+            }),
+        }
+    }
+}
+
+fn check_exports(module: &Module, violations: &mut Vec<Violation>) {
+    // Each export is required under one of the names.
+    let required: [(&[&str], Option<&str>); 3] = [
+        (&["entrypoint", "actions"], Some("()")),
+        (&["alloc"], Some("(i32) -> i32")),
+        (&["memory"], None),
+    ];
+    for (names, signature) in required {
+        let found = module.exports.iter().any(|export| {
+            names.contains(&export.name.as_str())
+                && match (export.item, signature) {
+                    (ExportItem::Function(id), Some(signature)) => {
+                        crate::inspect::function_signature(module, id) == signature
+                    }
+                    (ExportItem::Memory(_), None) => true,
+                    _ => false,
+                }
+        });
+        if !found {
+            violations.push(Violation::MissingExport {
+                name: names.join(" or "),
+                expected: match signature {
+                    Some(signature) => format!("function {signature}"),
+                    None => "memory".to_string(),
+                },
+            });
+        }
+    }
+}
+
+fn check_memories(module: &Module, violations: &mut Vec<Violation>) {
+    for memory in module.memories.iter() {
+        let pages = memory.maximum.unwrap_or(memory.initial).max(memory.initial);
+        if pages > MAX_MEMORY_PAGES {
+            violations.push(Violation::MemoryLimitExceeded {
+                pages,
+                limit: MAX_MEMORY_PAGES,
+            });
+        }
+        if memory.shared {
+            violations.push(disallowed("threads", "shared memory"));
+        }
+    }
+}
+
+fn check_proposals(module: &Module, violations: &mut Vec<Violation>) {
+    let mut usages = ProposalUsages::default();
+
+    // Value types in function signatures, locals and globals
+    let value_types = module
+        .types
+        .iter()
+        .flat_map(|ty| ty.params().iter().chain(ty.results().iter()).copied())
+        .chain(module.locals.iter().map(|local| local.ty()))
+        .chain(module.globals.iter().map(|global| global.ty))
+        .collect::<Vec<_>>();
+    for ty in value_types {
+        match ty {
+            ValType::V128 => usages.simd.get_or_insert("type v128".to_string()),
+            ValType::Anyref => usages.reference_types.get_or_insert("type anyref".to_string()),
+            _ => continue,
+        };
+    }
+
+    // Tables
+    if module.tables.iter().count() > 1 {
+        usages.reference_types.get_or_insert("multiple tables".to_string());
+    }
+    if module.tables.iter().any(|table| matches!(table.kind, TableKind::Anyref(_))) {
+        usages.reference_types.get_or_insert("table of anyref".to_string());
+    }
+
+    // Instructions
+    for (_, func) in module.funcs.iter_local() {
+        walrus::ir::dfs_in_order(&mut usages, func, func.entry_block());
+    }
+
+    if let Some(usage) = usages.threads {
+        violations.push(disallowed("threads", &usage));
+    }
+    if let Some(usage) = usages.simd {
+        violations.push(disallowed("SIMD", &usage));
+    }
+    if let Some(usage) = usages.reference_types {
+        violations.push(disallowed("reference types", &usage));
+    }
+}
+
+fn disallowed(proposal: &str, usage: &str) -> Violation {
+    Violation::DisallowedProposal {
+        proposal: proposal.to_string(),
+        usage: usage.to_string(),
+    }
+}
+
+/// Records the first usage of each disallowed proposal found in the instructions.
+#[derive(Default)]
+struct ProposalUsages {
+    threads: Option<String>,
+    simd: Option<String>,
+    reference_types: Option<String>,
+}
+
+impl<'instr> Visitor<'instr> for ProposalUsages {
+    fn visit_instr(&mut self, instr: &'instr Instr) {
+        let (usage, description) = match instr {
+            Instr::AtomicRmw(_)
+            | Instr::Cmpxchg(_)
+            | Instr::AtomicNotify(_)
+            | Instr::AtomicWait(_)
+            | Instr::AtomicFence(_) => (&mut self.threads, "atomic instruction"),
+            Instr::Load(load) => match load.kind {
+                LoadKind::I32 { atomic: true } | LoadKind::I64 { atomic: true } => {
+                    (&mut self.threads, "atomic instruction")
+                }
+                LoadKind::V128 => (&mut self.simd, "v128 instruction"),
+                _ => return,
+            },
+            Instr::Store(store) => match store.kind {
+                StoreKind::V128 => (&mut self.simd, "v128 instruction"),
+                StoreKind::I32 { atomic: true }
+                | StoreKind::I64 { atomic: true }
+                | StoreKind::I32_8 { atomic: true }
+                | StoreKind::I32_16 { atomic: true }
+                | StoreKind::I64_8 { atomic: true }
+                | StoreKind::I64_16 { atomic: true }
+                | StoreKind::I64_32 { atomic: true } => (&mut self.threads, "atomic instruction"),
+                _ => return,
+            },
+            Instr::Const(constant) if matches!(constant.value, Value::V128(_)) => {
+                (&mut self.simd, "v128 instruction")
+            }
+            Instr::Binop(binop) if is_simd_op(&format!("{:?}", binop.op)) => {
+                (&mut self.simd, "v128 instruction")
+            }
+            Instr::Unop(unop) if is_simd_op(&format!("{:?}", unop.op)) => {
+                (&mut self.simd, "v128 instruction")
+            }
+            Instr::V128Bitselect(_)
+            | Instr::V128Swizzle(_)
+            | Instr::V128Shuffle(_)
+            | Instr::LoadSplat(_) => (&mut self.simd, "v128 instruction"),
+            Instr::TableGet(_)
+            | Instr::TableSet(_)
+            | Instr::TableGrow(_)
+            | Instr::TableSize(_)
+            | Instr::RefNull(_)
+            | Instr::RefIsNull(_) => (&mut self.reference_types, "table or reference instruction"),
+            _ => return,
+        };
+        usage.get_or_insert(description.to_string());
+    }
+}
+
+/// Collects the names of floating-point instructions found in a function.
+#[derive(Default)]
+struct FloatInstructions(BTreeSet<String>);
+
+impl<'instr> Visitor<'instr> for FloatInstructions {
+    fn visit_instr(&mut self, instr: &'instr Instr) {
+        let name = match instr {
+            Instr::Const(constant) => match constant.value {
+                Value::F32(_) => "F32Const".to_string(),
+                Value::F64(_) => "F64Const".to_string(),
+                _ => return,
+            },
+            Instr::Load(load) => match load.kind {
+                LoadKind::F32 => "F32Load".to_string(),
+                LoadKind::F64 => "F64Load".to_string(),
+                _ => return,
+            },
+            Instr::Store(store) => match store.kind {
+                StoreKind::F32 => "F32Store".to_string(),
+                StoreKind::F64 => "F64Store".to_string(),
+                _ => return,
+            },
+            Instr::Binop(binop) => format!("{:?}", binop.op),
+            Instr::Unop(unop) => format!("{:?}", unop.op),
+            _ => return,
+        };
+        if name.contains("F32") || name.contains("F64") {
+            self.0.insert(name);
+        }
+    }
+}
+
+/// Returns true if the name of a binary or unary operator refers to a vector operation, e.g. `I8x16Add` or `V128Not`.
+fn is_simd_op(name: &str) -> bool {
+    ["V128", "x16", "x8", "x4", "x2"].iter().any(|lanes| name.contains(lanes))
+}
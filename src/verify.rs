@@ -0,0 +1,188 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of reproducibility verification. The source code is rebuilt in docker, and the
+//! resulting WebAssembly binary is compared with a given binary (e.g. a deployed contract) by SHA-256.
+//! If they do not match, the binaries are compared section by section.
+//!
+//! If the given binary contains the build metadata section (see [crate::metadata]) or the ABI section (see [crate::abi]),
+//! the sections are excluded from the comparison, because they are appended after post-processing and do not affect the code.
+//! The features and the docker image tag recorded in the build metadata are used to rebuild the source code.
+
+use std::path::PathBuf;
+
+use crate::{error::Error, metadata::BuildMetadata, BuildOptions, Config, DockerConfig, DockerOption};
+
+/// Result of verifying a WebAssembly binary against its source code.
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    /// File name of the rebuilt contract.
+    pub wasm_file: String,
+    /// SHA-256 (hex) of the given WebAssembly binary, excluding the build metadata and ABI sections.
+    pub expected_hash: String,
+    /// SHA-256 (hex) of the WebAssembly binary rebuilt from source.
+    pub actual_hash: String,
+    /// Comparison of each section. Empty if the hashes match.
+    pub section_diffs: Vec<SectionDiff>,
+    /// Build metadata embedded in the given WebAssembly binary, if any.
+    pub metadata: Option<BuildMetadata>,
+}
+
+impl VerifyReport {
+    /// Returns true if the rebuilt binary is identical to the given binary.
+    pub fn is_match(&self) -> bool {
+        self.expected_hash == self.actual_hash
+    }
+}
+
+/// Comparison of a section between the given binary and the rebuilt binary.
+#[derive(Debug, Clone)]
+pub struct SectionDiff {
+    /// Name of the section. For custom sections, it is the name stored in the section.
+    pub name: String,
+    /// Size of the section in the given binary. None if the section does not exist in it.
+    pub expected_size: Option<usize>,
+    /// Size of the section in the rebuilt binary. None if the section does not exist in it.
+    pub actual_size: Option<usize>,
+    /// Whether the contents of the section are identical in both binaries.
+    pub identical: bool,
+}
+
+/// `verify_contract` rebuilds the source code in docker and checks if the result is identical to the WebAssembly
+/// binary at `wasm_path`. The Cargo.lock in the source code directory is used if it exists.
+///
+/// If the binary contains the build metadata, the features and the docker image tag recorded in it are used when
+/// `features` or the tag in `docker_config` is None. Otherwise, they must be the same as the recorded ones, and
+/// [Error::BuildMetadataConflict] is returned if not.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` verify --source `source_path` --wasm `wasm_path`
+pub async fn verify_contract(
+    source_path: PathBuf,
+    wasm_path: PathBuf,
+    features: Option<Vec<String>>,
+    docker_config: DockerConfig,
+) -> Result<VerifyReport, Error> {
+    let given_wasm = std::fs::read(&wasm_path)
+        .map_err(|e| Error::InvalidWasmFile(format!("Fail to read {}: {e}", wasm_path.display())))?;
+    let metadata = crate::metadata::read_metadata(&given_wasm)?;
+    let expected_wasm = crate::abi::strip_abi(&crate::metadata::strip_metadata(&given_wasm)?)?;
+    let (features, docker_config) = rebuild_settings(metadata.as_ref(), features, docker_config)?;
+
+    // Rebuild the contract into a temporary folder. The build cache is not used, so that the contract is actually rebuilt.
+    let temp_dir = crate::cargo::random_temp_dir_name();
+    std::fs::create_dir_all(temp_dir.as_path()).map_err(|_| Error::CreateTempDir)?;
+    let result = Config {
+        source_path,
+        destination_path: Some(temp_dir.clone()),
+        build_options: BuildOptions {
+            locked: true,
+            no_cache: true,
+            features,
+            ..Default::default()
+        },
+        docker_option: DockerOption::Docker(docker_config),
+    }
+    .run()
+    .await
+    .and_then(|wasm_file| {
+        std::fs::read(temp_dir.join(&wasm_file))
+            .map(|actual_wasm| (wasm_file, actual_wasm))
+            .map_err(|e| Error::BuildFailure(e.to_string()))
+    });
+    let _ = std::fs::remove_dir_all(temp_dir);
+    let (wasm_file, actual_wasm) = result?;
+
+    let expected_hash = crate::wasm::sha256_hex(&expected_wasm);
+    let actual_hash = crate::wasm::sha256_hex(&actual_wasm);
+    let section_diffs = if expected_hash == actual_hash {
+        Vec::new()
+    } else {
+        diff_sections(&expected_wasm, &actual_wasm)?
+    };
+
+    Ok(VerifyReport {
+        wasm_file,
+        expected_hash,
+        actual_hash,
+        section_diffs,
+        metadata,
+    })
+}
+
+/// Returns the features and the docker settings to rebuild the source code with. The settings which are not given
+/// are taken from the build metadata, and the given ones must agree with it.
+fn rebuild_settings(
+    metadata: Option<&BuildMetadata>,
+    features: Option<Vec<String>>,
+    mut docker_config: DockerConfig,
+) -> Result<(Vec<String>, DockerConfig), Error> {
+    let Some(metadata) = metadata else {
+        return Ok((features.unwrap_or_default(), docker_config));
+    };
+
+    let features = match features {
+        Some(mut features) => {
+            features.sort();
+            features.dedup();
+            if features != metadata.features {
+                return Err(Error::BuildMetadataConflict(format!(
+                    "The features {features:?} are different from the features {:?} recorded in the binary.",
+                    metadata.features
+                )));
+            }
+            features
+        }
+        None => metadata.features.clone(),
+    };
+
+    // The tag is not recorded if the binary was built without docker
+    if let Some(recorded_tag) = &metadata.docker_image_tag {
+        match &docker_config.tag {
+            Some(tag) if tag != recorded_tag => {
+                return Err(Error::BuildMetadataConflict(format!(
+                    "The docker image tag {tag} is different from the tag {recorded_tag} recorded in the binary."
+                )))
+            }
+            Some(_) => {}
+            None => docker_config.tag = Some(recorded_tag.clone()),
+        }
+    }
+    Ok((features, docker_config))
+}
+
+/// Compares two WebAssembly binaries section by section. Sections are matched by their names and the
+/// order they appear.
+fn diff_sections(expected_wasm: &[u8], actual_wasm: &[u8]) -> Result<Vec<SectionDiff>, Error> {
+    let expected_sections = crate::wasm::sections(expected_wasm)?;
+    let mut actual_sections: Vec<Option<crate::wasm::Section>> = crate::wasm::sections(actual_wasm)?
+        .into_iter()
+        .map(Some)
+        .collect();
+
+    let mut diffs = vec![];
+    for expected in expected_sections {
+        let actual = actual_sections
+            .iter_mut()
+            .find(|actual| matches!(actual, Some(actual) if actual.id == expected.id && actual.name == expected.name))
+            .and_then(Option::take);
+        diffs.push(SectionDiff {
+            name: expected.name,
+            expected_size: Some(expected.payload.len()),
+            actual_size: actual.as_ref().map(|actual| actual.payload.len()),
+            identical: actual.is_some_and(|actual| actual.payload == expected.payload),
+        });
+    }
+    for actual in actual_sections.into_iter().flatten() {
+        diffs.push(SectionDiff {
+            name: actual.name,
+            expected_size: None,
+            actual_size: Some(actual.payload.len()),
+            identical: false,
+        });
+    }
+    Ok(diffs)
+}
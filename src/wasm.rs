@@ -0,0 +1,173 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implements a minimal reader of the WebAssembly binary format. It splits a module into its sections
+//! without decoding the section contents, so that the bytes of a compiled contract can be compared
+//! and hashed exactly as they are.
+
+use std::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// The magic number and version at the start of every WebAssembly module.
+const WASM_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// A section in a WebAssembly module.
+pub(crate) struct Section<'a> {
+    /// Section id. Custom sections have id 0.
+    pub id: u8,
+    /// Name of the section. For custom sections, it is the name stored in the section.
+    pub name: String,
+    /// Content of the section, excluding the section id and size.
+    pub payload: &'a [u8],
+    /// Byte range of the whole section (including the section id and size) in the module.
+    pub range: Range<usize>,
+}
+
+/// Splits the WebAssembly module into its sections in the order they appear.
+pub(crate) fn sections(wasm: &[u8]) -> Result<Vec<Section<'_>>, Error> {
+    if wasm.len() < WASM_HEADER.len() || wasm[..WASM_HEADER.len()] != WASM_HEADER {
+        return Err(Error::InvalidWasmFile("Missing WebAssembly header.".to_string()));
+    }
+
+    let mut sections = vec![];
+    let mut offset = WASM_HEADER.len();
+    while offset < wasm.len() {
+        let start = offset;
+        let id = wasm[offset];
+        offset += 1;
+        let size = read_u32(wasm, &mut offset)? as usize;
+        let payload = wasm
+            .get(offset..offset + size)
+            .ok_or(Error::InvalidWasmFile(format!("Section {id} exceeds the end of file.")))?;
+        offset += size;
+
+        let name = if id == 0 {
+            let mut name_offset = 0;
+            let name_len = read_u32(payload, &mut name_offset)? as usize;
+            payload
+                .get(name_offset..name_offset + name_len)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .ok_or(Error::InvalidWasmFile("Malformed custom section name.".to_string()))?
+        } else {
+            section_name(id).to_string()
+        };
+
+        sections.push(Section {
+            id,
+            name,
+            payload,
+            range: start..offset,
+        });
+    }
+    Ok(sections)
+}
+
+/// Returns the content of a custom section, excluding its name.
+pub(crate) fn custom_section_content<'a>(section: &Section<'a>) -> Result<&'a [u8], Error> {
+    let mut offset = 0;
+    let name_len = read_u32(section.payload, &mut offset)? as usize;
+    Ok(&section.payload[offset + name_len..])
+}
+
+/// Appends a custom section to the end of the WebAssembly module.
+pub(crate) fn append_custom_section(wasm: &mut Vec<u8>, name: &str, content: &[u8]) {
+    let mut payload = vec![];
+    write_u32(&mut payload, name.len() as u32);
+    payload.extend_from_slice(name.as_bytes());
+    payload.extend_from_slice(content);
+
+    wasm.push(0);
+    write_u32(wasm, payload.len() as u32);
+    wasm.extend_from_slice(&payload);
+}
+
+/// Returns the WebAssembly module without the custom sections of the given name.
+pub(crate) fn remove_custom_section(wasm: &[u8], name: &str) -> Result<Vec<u8>, Error> {
+    let mut result = wasm[..WASM_HEADER.len().min(wasm.len())].to_vec();
+    for section in sections(wasm)? {
+        if !(section.id == 0 && section.name == name) {
+            result.extend_from_slice(&wasm[section.range]);
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the sizes (in bytes) of the function bodies in the code section, in the order they are defined.
+pub(crate) fn function_body_sizes(wasm: &[u8]) -> Result<Vec<usize>, Error> {
+    let Some(code) = sections(wasm)?.into_iter().find(|section| section.id == 10) else {
+        return Ok(vec![]);
+    };
+    let mut offset = 0;
+    let count = read_u32(code.payload, &mut offset)?;
+    let mut sizes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let size = read_u32(code.payload, &mut offset)? as usize;
+        offset += size;
+        sizes.push(size);
+    }
+    if offset > code.payload.len() {
+        return Err(Error::InvalidWasmFile("Function body exceeds the end of code section.".to_string()));
+    }
+    Ok(sizes)
+}
+
+/// Returns the SHA-256 of the bytes as a lowercase hex string.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Reads an unsigned LEB128 encoded integer and moves the offset to the next byte after it.
+pub(crate) fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, Error> {
+    let mut result: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes
+            .get(*offset)
+            .ok_or(Error::InvalidWasmFile("Unexpected end of file.".to_string()))?;
+        *offset += 1;
+        if shift > 28 {
+            return Err(Error::InvalidWasmFile("Malformed integer encoding.".to_string()));
+        }
+        result |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+/// Writes an unsigned LEB128 encoded integer.
+pub(crate) fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        _ => "unknown",
+    }
+}
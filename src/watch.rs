@@ -0,0 +1,201 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Implementation of the watch mode, which rebuilds contracts when their source code changes.
+//!
+//! The source code directory and the path dependencies (see [crate::manifests::get_dependency_paths]) of each contract
+//! are watched. Changes are debounced, i.e. a rebuild starts after no change is seen for a while, so that saving many
+//! files at once triggers one rebuild. Only the contracts whose watched paths changed are rebuilt, and only if the content
+//! of the source code actually changed, so that the files written by the build itself do not trigger another build.
+
+use std::{
+    collections::HashSet,
+    ops::ControlFlow,
+    path::PathBuf,
+    time::Duration,
+};
+
+use notify::{RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+
+use crate::{error::Error, Config};
+
+/// Default time to wait for more changes before rebuilding.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// `watch` builds the contracts, then rebuilds them whenever their source code changes. At most `jobs` builds run in
+/// parallel (see [crate::batch]). `on_build` is called with the result of each build, and watching stops when it
+/// returns [ControlFlow::Break], or when `cancellation_token` is cancelled. The running builds are cancelled with it.
+///
+/// This method is equivalent to run the command:
+///
+/// `pchain_compile` build --source `source_path` --jobs `jobs` --watch
+pub async fn watch<F>(
+    configs: Vec<Config>,
+    jobs: usize,
+    debounce: Duration,
+    cancellation_token: CancellationToken,
+    mut on_build: F,
+) -> Result<(), Error>
+where
+    F: FnMut(&Config, &Result<String, Error>) -> ControlFlow<()>,
+{
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = sender.send(event.paths);
+        }
+    })
+    .map_err(|e| Error::WatchFailure(e.to_string()))?;
+
+    let mut contracts: Vec<WatchedContract> = configs.into_iter().map(WatchedContract::new).collect();
+    let mut watched_paths = HashSet::new();
+
+    // Initial build of all contracts
+    let all: Vec<usize> = (0..contracts.len()).collect();
+    if build(&mut contracts, &all, jobs, &cancellation_token, &mut on_build).await.is_break() {
+        return Ok(());
+    }
+
+    loop {
+        // Watch the paths that are new, e.g. a path dependency added to the manifest
+        for contract in &contracts {
+            for path in &contract.watched_paths {
+                if !watched_paths.contains(path) {
+                    watcher
+                        .watch(path, RecursiveMode::Recursive)
+                        .map_err(|e| Error::WatchFailure(format!("Fail to watch {}: {e}", path.display())))?;
+                    watched_paths.insert(path.clone());
+                }
+            }
+        }
+
+        // Wait for a change, then collect the changes until no change is seen within the debounce time
+        let mut changed_paths = tokio::select! {
+            paths = receiver.recv() => match paths {
+                Some(paths) => paths,
+                None => return Err(Error::WatchFailure("The watcher stopped unexpectedly.".to_string())),
+            },
+            _ = cancellation_token.cancelled() => return Ok(()),
+        };
+        while let Ok(Some(paths)) = tokio::time::timeout(debounce, receiver.recv()).await {
+            changed_paths.extend(paths);
+        }
+
+        // Rebuild the contracts whose source code changed
+        let affected: Vec<usize> = contracts
+            .iter()
+            .enumerate()
+            .filter(|(_, contract)| contract.is_affected_by(&changed_paths) && contract.has_changed())
+            .map(|(index, _)| index)
+            .collect();
+        if build(&mut contracts, &affected, jobs, &cancellation_token, &mut on_build).await.is_break() {
+            return Ok(());
+        }
+    }
+}
+
+/// Returns a short summary of a failed build. For compilation errors, it lists the error messages and their locations
+/// in the building log instead of the whole log.
+pub fn diagnostic_summary(error: &Error) -> String {
+    let Error::BuildFailureWithLogs(log) = error else {
+        return format!("{error}{}", error.detail());
+    };
+    let mut summary = vec![];
+    let mut lines = log.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with("error") {
+            summary.push(line.to_string());
+            if let Some(location) = lines.peek().filter(|next| next.trim_start().starts_with("-->")) {
+                summary.push(location.to_string());
+            }
+        }
+    }
+    if summary.is_empty() {
+        return format!("{error}{}", error.detail());
+    }
+    summary.join("\n")
+}
+
+/// A contract in watch mode.
+struct WatchedContract {
+    config: Config,
+    /// The source code directory and the path dependencies.
+    watched_paths: Vec<PathBuf>,
+    /// Hash of the content of the watched paths at the last build.
+    fingerprint: Option<String>,
+}
+
+impl WatchedContract {
+    fn new(config: Config) -> Self {
+        let mut contract = Self {
+            config,
+            watched_paths: vec![],
+            fingerprint: None,
+        };
+        contract.refresh();
+        contract
+    }
+
+    /// Updates the watched paths and the fingerprint from the current source code.
+    fn refresh(&mut self) {
+        let source_path = dunce::canonicalize(&self.config.source_path).unwrap_or(self.config.source_path.clone());
+        let mut dependencies = HashSet::new();
+        let _ = crate::manifests::get_dependency_paths(&source_path, &mut dependencies);
+        let mut dependencies: Vec<PathBuf> = dependencies.into_iter().map(PathBuf::from).collect();
+        dependencies.sort();
+
+        self.watched_paths = std::iter::once(source_path).chain(dependencies).collect();
+        self.fingerprint = self.current_fingerprint();
+    }
+
+    fn current_fingerprint(&self) -> Option<String> {
+        let mut hasher = Sha256::new();
+        for path in &self.watched_paths {
+            hasher.update(crate::metadata::source_tree_hash(path).ok()?);
+        }
+        Some(format!("{:x}", hasher.finalize()))
+    }
+
+    fn is_affected_by(&self, changed_paths: &[PathBuf]) -> bool {
+        changed_paths
+            .iter()
+            .any(|changed_path| self.watched_paths.iter().any(|path| changed_path.starts_with(path)))
+    }
+
+    /// Checks if the content of the watched paths is different from the last build.
+    fn has_changed(&self) -> bool {
+        self.fingerprint.is_none() || self.current_fingerprint() != self.fingerprint
+    }
+}
+
+/// Builds the contracts at the indices concurrently, and reports the results in the order of the indices.
+async fn build<F>(
+    contracts: &mut [WatchedContract],
+    indices: &[usize],
+    jobs: usize,
+    cancellation_token: &CancellationToken,
+    on_build: &mut F,
+) -> ControlFlow<()>
+where
+    F: FnMut(&Config, &Result<String, Error>) -> ControlFlow<()>,
+{
+    let configs = indices.iter().map(|index| contracts[*index].config.clone()).collect();
+    let results = crate::batch::build_all(configs, jobs, cancellation_token.clone(), |_, _| {}).await;
+
+    let mut flow = match cancellation_token.is_cancelled() {
+        true => ControlFlow::Break(()),
+        false => ControlFlow::Continue(()),
+    };
+    for (index, result) in indices.iter().zip(results) {
+        // Take the fingerprint after the build, so that the files written by the build are included.
+        contracts[*index].refresh();
+        if flow.is_continue() {
+            flow = on_build(&contracts[*index].config, &result);
+        }
+    }
+    flow
+}
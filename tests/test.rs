@@ -5,9 +5,9 @@
 
 //! Basic tests to demonstrate common usage of pchain_compile.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use pchain_compile::{DockerOption, BuildOptions, DockerConfig};
+use pchain_compile::{backend::{BuildOutput, BuildRequest}, BuildWarning, CancellationToken, DockerOption, BuildOptions, DockerConfig, FloatPolicy, OutputLayout, ProjectConfig, SandboxConfig, SizeBaseline, compat::ChangeKind, error::Error, run::ContractCall, scaffold::Template};
 
 #[tokio::test]
 async fn build_contract() {
@@ -65,7 +65,7 @@ async fn build_contract_with_docker() {
     let wasm_name = pchain_compile::Config {
         source_path,
         destination_path: Some(destination_path.clone()),
-        build_options: BuildOptions { locked: true },
+        build_options: BuildOptions { locked: true, ..Default::default() },
         docker_option: DockerOption::Docker(DockerConfig::default()),
     }
     .run()
@@ -91,7 +91,7 @@ async fn build_contract_without_docker() {
     let run_result = pchain_compile::Config {
         source_path,
         destination_path: Some(destination_path.clone()),
-        build_options: BuildOptions { locked: true },
+        build_options: BuildOptions { locked: true, ..Default::default() },
         docker_option: DockerOption::Dockerless,
     }
     .run() 
@@ -109,3 +109,1331 @@ async fn build_contract_without_docker() {
     let _ = std::fs::remove_file(destination_path.join(&wasm_name));
     assert_eq!(wasm_name, "hello_contract.wasm");
 }
+
+#[tokio::test]
+async fn build_contract_frozen_without_lockfile() {
+    let source_path = copy_hello_contract("frozen");
+    std::fs::remove_file(source_path.join("Cargo.lock")).unwrap();
+
+    let run_result = pchain_compile::Config {
+        source_path: source_path.to_path_buf(),
+        destination_path: None,
+        build_options: BuildOptions { frozen: true, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run()
+    .await;
+
+    assert!(matches!(run_result, Err(Error::LockfileNotFound)));
+}
+
+#[tokio::test]
+async fn verify_contract() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("verify");
+    let wasm_name = pchain_compile::Config {
+        source_path: source_path.clone(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { locked: true, ..Default::default() },
+        docker_option: DockerOption::Docker(DockerConfig::default()),
+    }
+    .run()
+    .await
+    .expect("Note: This test require installation of docker. Make sure the permission has been granted to run docker.");
+
+    let report = pchain_compile::verify_contract(
+        source_path,
+        destination_path.join(&wasm_name),
+        None,
+        DockerConfig::default(),
+    )
+    .await;
+
+    let report = report.unwrap();
+    assert!(report.is_match());
+    assert!(report.section_diffs.is_empty());
+}
+
+#[tokio::test]
+async fn verify_contract_with_conflicting_metadata() {
+    let folder = TempFolder::new("verify_metadata");
+    std::fs::create_dir_all(&folder).unwrap();
+    let wasm_path = folder.join("contract.wasm");
+    let metadata = pchain_compile::metadata::BuildMetadata {
+        pchain_compile_version: env!("CARGO_PKG_VERSION").to_string(),
+        docker_image_tag: Some("0.4.2".to_string()),
+        docker_image_digest: None,
+        rustc_version: None,
+        source_hash: String::new(),
+        cargo_lock_hash: None,
+        features: vec!["a".to_string(), "b".to_string()],
+    };
+    let mut module = walrus::Module::from_buffer(&contract_wasm()).unwrap();
+    module.customs.add(walrus::RawCustomSection {
+        name: pchain_compile::metadata::BUILD_METADATA_SECTION.to_string(),
+        data: serde_json::to_vec(&metadata).unwrap(),
+    });
+    std::fs::write(&wasm_path, module.emit_wasm()).unwrap();
+
+    let verify = |features: Option<Vec<&str>>, tag: Option<&str>| {
+        pchain_compile::verify_contract(
+            hello_contract_path(),
+            wasm_path.clone(),
+            features.map(|features| features.into_iter().map(str::to_string).collect()),
+            DockerConfig { tag: tag.map(str::to_string), ..Default::default() },
+        )
+    };
+    let different_features = verify(Some(vec!["a"]), None).await;
+    let different_tag = verify(Some(vec!["b", "a"]), Some("0.4.3")).await;
+
+    assert!(matches!(different_features, Err(Error::BuildMetadataConflict(_))));
+    assert!(matches!(different_tag, Err(Error::BuildMetadataConflict(_))));
+}
+
+#[tokio::test]
+async fn build_contract_with_metadata() {
+    let destination_path = TempFolder::new("metadata");
+    let build = |embed_metadata: bool| pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.join(embed_metadata.to_string())),
+        build_options: BuildOptions { locked: true, embed_metadata, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run();
+
+    let wasm_name = expect_built(build(true).await.and(build(false).await));
+    let wasm_with_metadata = std::fs::read(destination_path.join("true").join(&wasm_name)).unwrap();
+    let wasm_without_metadata = std::fs::read(destination_path.join("false").join(&wasm_name)).unwrap();
+
+    let metadata = pchain_compile::metadata::read_metadata(&wasm_with_metadata).unwrap().unwrap();
+    assert_eq!(metadata.pchain_compile_version, env!("CARGO_PKG_VERSION"));
+    assert!(metadata.docker_image_tag.is_none());
+    assert!(metadata.cargo_lock_hash.is_some());
+    assert!(pchain_compile::metadata::read_metadata(&wasm_without_metadata).unwrap().is_none());
+    assert_eq!(pchain_compile::metadata::strip_metadata(&wasm_with_metadata).unwrap(), wasm_without_metadata);
+}
+
+#[tokio::test]
+async fn inspect_contract() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("inspect", BuildOptions { locked: true, embed_metadata: true, ..Default::default() }).await;
+    let inspection = pchain_compile::inspect_wasm(&destination_path.join(&wasm_name));
+
+    let inspection = inspection.unwrap();
+    assert!(inspection.imports["env"].iter().any(|import| import.name == "_log"));
+    assert!(inspection.exports.iter().any(|export| export.name == "entrypoint"));
+    assert_eq!(inspection.memories.len(), 1);
+    assert!(inspection.custom_sections.iter().any(|section| section.name == "pchain.build"));
+    assert!(inspection.metadata.is_some());
+}
+
+#[test]
+fn validate_contract_exports() {
+    let wasm_path = std::env::temp_dir().join("pchain_compile_validate_exports.wasm");
+    let with_entrypoint = |name: &str| {
+        let mut module = walrus::Module::from_buffer(&contract_wasm()).unwrap();
+        let export = module.exports.iter_mut().find(|export| export.name == "entrypoint").unwrap();
+        export.name = name.to_string();
+        module.emit_wasm_file(&wasm_path).unwrap();
+        pchain_compile::validate_wasm(&wasm_path).unwrap()
+    };
+    let entrypoint_violations = with_entrypoint("entrypoint");
+    let actions_violations = with_entrypoint("actions");
+    let missing_violations = with_entrypoint("unknown");
+    let _ = std::fs::remove_file(&wasm_path);
+
+    assert!(entrypoint_violations.is_empty());
+    assert!(actions_violations.is_empty());
+    assert_eq!(
+        missing_violations,
+        vec![pchain_compile::validate::Violation::MissingExport {
+            name: "entrypoint or actions".to_string(),
+            expected: "function ()".to_string(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn validate_contract() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("validate", BuildOptions { locked: true, ..Default::default() }).await;
+    let wasm_path = destination_path.join(&wasm_name);
+    let violations = pchain_compile::validate_wasm(&wasm_path);
+
+    // Import a function that is not provided by the runtime
+    let invalid_wasm_path = destination_path.join("invalid.wasm");
+    let mut module = walrus::Module::from_file(&wasm_path).unwrap();
+    let ty = module.types.add(&[], &[]);
+    module.add_import_func("env", "unknown_host_function", ty);
+    module.emit_wasm_file(&invalid_wasm_path).unwrap();
+    let invalid_violations = pchain_compile::validate_wasm(&invalid_wasm_path);
+
+    assert!(violations.unwrap().is_empty());
+    assert_eq!(
+        invalid_violations.unwrap(),
+        vec![pchain_compile::validate::Violation::UnknownImport {
+            module: "env".to_string(),
+            name: "unknown_host_function".to_string(),
+            kind: "function".to_string(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn find_float_instructions() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("floats", BuildOptions { locked: true, float_policy: FloatPolicy::Error, ..Default::default() }).await;
+    let wasm_path = destination_path.join(&wasm_name);
+
+    // Add a function that multiplies two floating-point numbers
+    let float_wasm_path = destination_path.join("float.wasm");
+    let mut module = walrus::Module::from_file(&wasm_path).unwrap();
+    let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[walrus::ValType::F64]);
+    builder
+        .func_body()
+        .f64_const(1.5)
+        .f64_const(2.0)
+        .instr(walrus::ir::Binop { op: walrus::ir::BinaryOp::F64Mul });
+    let id = builder.finish(vec![], &mut module.funcs);
+    module.funcs.get_mut(id).name = Some("uses_float".to_string());
+    module.emit_wasm_file(&float_wasm_path).unwrap();
+    let usages = pchain_compile::validate::find_float_instructions(&float_wasm_path);
+
+    assert_eq!(
+        usages.unwrap(),
+        vec![pchain_compile::validate::FloatUsage {
+            function: "uses_float".to_string(),
+            instructions: vec!["F64Const".to_string(), "F64Mul".to_string()],
+        }]
+    );
+}
+
+#[tokio::test]
+async fn dockerless_toolchain() {
+    // The toolchain selected by rustup for the source code, e.g. by the file "rust-toolchain.toml", is used.
+    let source_path = TempFolder::new("toolchain_override");
+    std::fs::create_dir_all(&source_path).unwrap();
+    std::fs::write(source_path.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"pchain_compile_missing\"\n").unwrap();
+
+    // The toolchain of "cargo test" is passed down by RUSTUP_TOOLCHAIN, which takes precedence over the file.
+    std::env::remove_var("RUSTUP_TOOLCHAIN");
+    let backend = pchain_compile::backend::DockerlessBackend;
+    let toolchain = pchain_compile::BuildBackend::toolchain(&backend, &hello_contract_path()).await;
+    let missing_toolchain = pchain_compile::BuildBackend::toolchain(&backend, &source_path).await;
+
+    assert!(toolchain.is_some());
+    assert_eq!(missing_toolchain, None, "Note: This test require installation of rustup.");
+}
+
+#[tokio::test]
+async fn build_contract_with_size_report() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("size", BuildOptions { locked: true, size_report: true, ..Default::default() }).await;
+    let report = pchain_compile::size::read_size_report(
+        &destination_path.join(pchain_compile::size::size_report_file(&wasm_name)),
+    );
+    let wasm_size = std::fs::metadata(destination_path.join(&wasm_name)).map(|m| m.len() as usize);
+
+    let report = report.unwrap();
+    let stages = report.stages.unwrap();
+    assert_eq!(report.total_size, wasm_size.unwrap());
+    assert_eq!(stages.final_size, report.total_size);
+    assert_eq!(
+        stages.cargo_build - stages.final_size,
+        stages.removed_by_wasm_opt + stages.removed_by_wasm_snip
+    );
+    assert!(report.functions.iter().any(|function| function.name == "entrypoint"));
+    assert!(report.crates.iter().any(|crate_size| crate_size.name == "pchain_sdk"));
+    assert!(!report.data_segments.is_empty());
+}
+
+#[tokio::test]
+async fn build_contract_with_size_budget() {
+    let destination_path = TempFolder::new("size_budget");
+
+    // Exceeds the maximum size
+    let max_size_result = pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.join("max_size")),
+        build_options: BuildOptions { locked: true, max_wasm_size: Some(1000), ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run()
+    .await;
+
+    // Grows from an empty module
+    std::fs::create_dir_all(&destination_path).unwrap();
+    let baseline_path = destination_path.join("baseline.wasm");
+    std::fs::write(&baseline_path, [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00]).unwrap();
+    let baseline_result = pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.join("baseline")),
+        build_options: BuildOptions {
+            locked: true,
+            size_baseline: Some(SizeBaseline { wasm_path: baseline_path, max_growth_percent: 10.0 }),
+            ..Default::default()
+        },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run()
+    .await;
+
+    assert!(matches!(max_size_result, Err(Error::WasmSizeExceeded { max_size: 1000, .. })));
+    assert!(matches!(baseline_result, Err(Error::WasmSizeGrowthExceeded { baseline_size: 8, .. })));
+}
+
+#[test]
+fn build_contract_cli_exit_status() {
+    // The command fails if the contract exceeds the maximum size, or cannot be built at all
+    let destination_path = TempFolder::new("cli_exit_status");
+    std::fs::create_dir_all(&destination_path).unwrap();
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_pchain_compile"))
+        .current_dir(&destination_path)
+        .arg("build")
+        .arg("--source")
+        .arg(hello_contract_path())
+        .arg("--destination")
+        .arg(destination_path.join("output"))
+        .args(["--locked", "--no-cache", "--dockerless", "--max-wasm-size", "1000"])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(1));
+}
+
+#[tokio::test]
+async fn optimize_contract() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("optimize", BuildOptions { locked: true, ..Default::default() }).await;
+
+    let source_path = hello_contract_path();
+
+    // Post-processing the cargo output again gives the same binary as building.
+    let cargo_wasm = source_path
+        .join("target")
+        .join("wasm32-unknown-unknown")
+        .join("release")
+        .join(&wasm_name);
+    let output_path = destination_path.join("optimized").join(&wasm_name);
+    let result = pchain_compile::optimize_wasm(&cargo_wasm, &output_path, &BuildOptions::default());
+    let built = std::fs::read(destination_path.join(&wasm_name));
+    let optimized = std::fs::read(&output_path);
+
+    // Not a WebAssembly binary
+    let invalid_result = pchain_compile::optimize_wasm(
+        &source_path.join("Cargo.toml"),
+        &destination_path.join("invalid.wasm"),
+        &BuildOptions::default(),
+    );
+
+    result.unwrap();
+    assert_eq!(built.unwrap(), optimized.unwrap());
+    assert!(matches!(invalid_result, Err(Error::BuildFailure(_))));
+}
+
+#[test]
+fn run_contract_out_of_bounds() {
+    // The entrypoint logs 4 GiB at the end of the memory.
+    let mut module = walrus::Module::from_buffer(&contract_wasm()).unwrap();
+    let ty = module.types.add(&[walrus::ValType::I32, walrus::ValType::I32], &[]);
+    let (log, _) = module.add_import_func("env", "_log", ty);
+    let entrypoint = module.exports.iter().find(|export| export.name == "entrypoint").unwrap().item;
+    let walrus::ExportItem::Function(entrypoint) = entrypoint else { unreachable!() };
+    let entrypoint = module.funcs.get_mut(entrypoint).kind.unwrap_local_mut();
+    let block = entrypoint.entry_block();
+    entrypoint.builder_mut().instr_seq(block).i32_const(65536).i32_const(-1).call(log);
+    let wasm_path = std::env::temp_dir().join("pchain_compile_out_of_bounds.wasm");
+    module.emit_wasm_file(&wasm_path).unwrap();
+
+    let call = ContractCall { method: "hello".to_string(), ..Default::default() };
+    let run_result = pchain_compile::run_contract(&wasm_path, &call);
+    let _ = std::fs::remove_file(&wasm_path);
+
+    assert!(matches!(run_result, Err(Error::ContractRunFailure(msg)) if msg.contains("Out of bounds memory access")));
+}
+
+#[tokio::test]
+async fn run_contract() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("run", BuildOptions { locked: true, ..Default::default() }).await;
+    let wasm_path = destination_path.join(&wasm_name);
+    let call = |method: &str, args: &[&str], storage| {
+        pchain_compile::run_contract(
+            &wasm_path,
+            &ContractCall {
+                method: method.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                storage,
+                ..Default::default()
+            },
+        )
+    };
+    let hello_from = call("hello_from", &["Alice"], Default::default());
+    let set_many = call("hello_set_many", &[], Default::default());
+    let read_many = set_many
+        .as_ref()
+        .map(|result| call("hello_read_many", &[], result.storage.clone()));
+    let unknown_method = call("hello_to", &[], Default::default());
+
+    let hello_from = hello_from.unwrap();
+    assert_eq!(hello_from.logs[0].value, "Hello, Contract. From: Alice");
+    assert_eq!(hello_from.return_value.unwrap().decoded, Some(serde_json::json!(5)));
+    assert!(hello_from.storage_changes.is_empty());
+
+    assert_eq!(set_many.as_ref().unwrap().storage_changes.len(), 9);
+    let read_many = read_many.unwrap().unwrap();
+    assert_eq!(read_many.logs.len(), 9);
+    assert!(read_many.storage_changes.is_empty());
+
+    assert!(matches!(unknown_method, Err(Error::ContractRunFailure(_))));
+}
+
+#[test]
+fn encode_contract_arguments() {
+    use pchain_compile::run::encode_argument;
+
+    assert_eq!(encode_argument("String", "Alice").unwrap(), [&[5, 0, 0, 0][..], b"Alice"].concat());
+    assert_eq!(encode_argument("u32", "5").unwrap(), vec![5, 0, 0, 0]);
+    assert_eq!(encode_argument("i16", "-2").unwrap(), vec![0xfe, 0xff]);
+    assert!(encode_argument("u8", "256").is_err());
+    assert_eq!(encode_argument("bool", "true").unwrap(), vec![1]);
+    assert_eq!(encode_argument("Vec<u8>", "0x0102").unwrap(), vec![2, 0, 0, 0, 1, 2]);
+    assert_eq!(encode_argument("Vec<u16>", "[1, 2]").unwrap(), vec![2, 0, 0, 0, 1, 0, 2, 0]);
+    assert_eq!(encode_argument("Option<u8>", "null").unwrap(), vec![0]);
+    assert_eq!(encode_argument("(u8,bool)", "[7, false]").unwrap(), vec![7, 0]);
+    assert_eq!(encode_argument("PublicAddress", &format!("0x{}", "ab".repeat(32))).unwrap(), vec![0xab; 32]);
+    // Unsupported types are given as borsh-serialized bytes in hex
+    assert_eq!(encode_argument("MyStruct", "0x0a0b").unwrap(), vec![10, 11]);
+}
+
+#[tokio::test]
+async fn build_contract_with_cache() {
+    let destination_path = TempFolder::new("cache");
+    let build = |destination: &str, no_cache: bool| pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.join(destination)),
+        build_options: BuildOptions {
+            locked: true,
+            embed_metadata: true,
+            embed_abi: true,
+            size_report: true,
+            no_cache,
+            ..Default::default()
+        },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run();
+
+    let wasm_name = expect_built(build("first", false).await);
+    let built = std::fs::read(destination_path.join("first").join(&wasm_name)).unwrap();
+
+    // Mark the cached contract, so that a build returning it can be told apart.
+    let entry = pchain_compile::cache::cache_entries()
+        .unwrap()
+        .into_iter()
+        .find(|entry| std::fs::read(entry.path.join(&wasm_name)).is_ok_and(|cached| cached == built))
+        .expect("the build is saved in the cache");
+    let marked = [built.as_slice(), &[0, 5, 4, b'm', b'a', b'r', b'k']].concat();
+    std::fs::write(entry.path.join(&wasm_name), &marked).unwrap();
+
+    let cached_result = build("cached", false).await;
+    let cached = std::fs::read(destination_path.join("cached").join(&wasm_name));
+    let cached_size_report = destination_path
+        .join("cached")
+        .join(pchain_compile::size::size_report_file(&wasm_name))
+        .exists();
+    let rebuilt_result = build("rebuilt", true).await;
+    let rebuilt = std::fs::read(destination_path.join("rebuilt").join(&wasm_name));
+    let _ = std::fs::remove_dir_all(&entry.path);
+
+    cached_result.unwrap();
+    assert_eq!(cached.unwrap(), marked);
+    assert!(cached_size_report);
+    rebuilt_result.unwrap();
+    assert_eq!(rebuilt.unwrap(), built);
+}
+
+#[tokio::test]
+async fn build_contract_with_abi() {
+    let (destination_path, wasm_name) =
+        build_hello_contract("abi", BuildOptions { locked: true, embed_abi: true, ..Default::default() }).await;
+    let abi_json = std::fs::read(destination_path.join(pchain_compile::abi::abi_file(&wasm_name)));
+    let wasm = std::fs::read(destination_path.join(&wasm_name));
+
+    let abi: pchain_compile::abi::ContractAbi = serde_json::from_slice(&abi_json.unwrap()).unwrap();
+    assert_eq!(abi.contract, "HelloContract");
+    assert_eq!(
+        abi.methods.iter().map(|method| method.name.as_str()).collect::<Vec<_>>(),
+        vec!["hello", "hello_from", "hello_set_many", "hello_read_many", "i_say_hello"]
+    );
+    let hello_from = &abi.methods[1];
+    assert_eq!(hello_from.args.len(), 1);
+    assert_eq!(hello_from.args[0].name, "name");
+    assert_eq!(hello_from.args[0].ty, "String");
+    assert_eq!(hello_from.return_type.as_deref(), Some("u32"));
+    assert!(!hello_from.is_view);
+    assert_eq!(pchain_compile::abi::read_abi(&wasm.unwrap()).unwrap(), Some(abi));
+}
+
+#[test]
+fn generate_contract_abi() {
+    let expected = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("contracts").join("hello_contract.abi.json")).unwrap();
+    let expected: pchain_compile::abi::ContractAbi = serde_json::from_slice(&expected).unwrap();
+    assert_eq!(pchain_compile::abi::generate_abi(&hello_contract_path()).unwrap(), expected);
+}
+
+#[test]
+fn check_compatibility() {
+    let old_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("contracts")
+        .join("hello_contract")
+        .to_path_buf();
+
+    // Change the argument type of a method, add a method and a storage field
+    let new_path = std::env::temp_dir().join("hello_contract_compat");
+    std::fs::create_dir_all(new_path.join("src")).unwrap();
+    std::fs::copy(old_path.join("Cargo.toml"), new_path.join("Cargo.toml")).unwrap();
+    let source = std::fs::read_to_string(old_path.join("src").join("lib.rs"))
+        .unwrap()
+        .replace("struct HelloContract {}", "struct HelloContract { counter: u64 }")
+        .replace("fn hello_from(name :String)", "fn hello_from(name :Vec<u8>)")
+        .replace("fn i_say_hello()", "fn counter(&self) -> u64 { self.counter }\n\n    #[call]\n    fn i_say_hello()");
+    std::fs::write(new_path.join("src").join("lib.rs"), source).unwrap();
+
+    let same_report = pchain_compile::check_compatibility(&old_path, &old_path);
+    let report = pchain_compile::check_compatibility(&old_path, &new_path);
+    let _ = std::fs::remove_dir_all(&new_path);
+
+    assert!(same_report.unwrap().changes.is_empty());
+    let report = report.unwrap();
+    assert!(!report.is_compatible());
+    let changes: Vec<(ChangeKind, &str)> = report
+        .changes
+        .iter()
+        .map(|change| (change.kind, change.item.as_str()))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (ChangeKind::Breaking, "method hello_from"),
+            (ChangeKind::Compatible, "method counter"),
+            (ChangeKind::Compatible, "storage field 0 (counter)"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn watch_contract() {
+    let source_path = copy_hello_contract("watch");
+    let destination_path = TempFolder::new("watch_output");
+
+    let config = pchain_compile::Config {
+        source_path: source_path.to_path_buf(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { locked: true, no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    };
+
+    // Introduce a compilation error after the first build, and stop after the rebuild.
+    let mut results = vec![];
+    let watch_result = pchain_compile::watch(vec![config], 1, std::time::Duration::from_millis(200), CancellationToken::new(), |_, result| {
+        results.push(result.as_ref().map(|wasm_name| wasm_name.to_string()).map_err(pchain_compile::watch::diagnostic_summary));
+        if results.len() == 1 {
+            let lib_path = source_path.join("src").join("lib.rs");
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                let source = std::fs::read_to_string(&lib_path).unwrap();
+                std::fs::write(&lib_path, format!("{source}\nfn broken() -> u64 {{ \"not a number\" }}\n")).unwrap();
+            });
+            return std::ops::ControlFlow::Continue(());
+        }
+        std::ops::ControlFlow::Break(())
+    })
+    .await;
+
+    watch_result.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap(), "hello_contract.wasm");
+    let summary = results[1].as_ref().unwrap_err();
+    assert!(summary.contains("error[E0308]"), "{summary}");
+    assert!(summary.contains("src/lib.rs"), "{summary}");
+}
+
+#[test]
+fn new_contract() {
+    let path = std::env::temp_dir().join("pchain-compile-new-test");
+    let _ = std::fs::remove_dir_all(&path);
+
+    let name = pchain_compile::new_contract(&path, Template::Storage);
+    let manifest = std::fs::read_to_string(path.join("Cargo.toml"));
+    let library = std::fs::read_to_string(path.join("src").join("lib.rs"));
+    let exists_result = pchain_compile::new_contract(&path, Template::Minimal);
+    let init_result = pchain_compile::init_contract(&path, None, Template::Minimal);
+    let _ = std::fs::remove_dir_all(&path);
+
+    assert_eq!(name.unwrap(), "pchain-compile-new-test");
+    let manifest = manifest.unwrap();
+    assert!(manifest.contains("name = \"pchain-compile-new-test\""));
+    assert!(manifest.contains("crate-type = [\"cdylib\"]"));
+    assert!(manifest.contains(&format!("pchain-sdk = \"{}\"", pchain_compile::scaffold::PCHAIN_SDK_VERSION)));
+    assert!(library.unwrap().contains("struct PchainCompileNewTest {\n    count: u64,\n}"));
+    assert!(matches!(exists_result, Err(Error::ScaffoldFailure(_))));
+    assert!(matches!(init_result, Err(Error::ScaffoldFailure(_))));
+}
+
+#[test]
+fn read_project_config() {
+    let path = std::env::temp_dir().join("pchain_compile_project_test");
+    std::fs::create_dir_all(&path).unwrap();
+    std::fs::write(
+        path.join("pchain_compile.toml"),
+        "contracts = [\"contracts/a\", \"contracts/b\"]\ndestination = \"artifacts\"\nlocked = true\ndocker-tag = \"0.4.3\"\nfeatures = [\"mainnet\"]\nfloat-policy = \"error\"\n",
+    )
+    .unwrap();
+    let file_config = ProjectConfig::find(&path);
+
+    // Cargo.toml is used without pchain_compile.toml
+    std::fs::remove_file(path.join("pchain_compile.toml")).unwrap();
+    std::fs::write(
+        path.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[package.metadata.pchain]\ndockerless = true\n",
+    )
+    .unwrap();
+    let manifest_config = ProjectConfig::find(&path);
+    let _ = std::fs::remove_dir_all(&path);
+
+    let file_config = file_config.unwrap().unwrap();
+    assert_eq!(file_config.contracts, Some(vec![path.join("contracts/a"), path.join("contracts/b")]));
+    let manifest_config = manifest_config.unwrap().unwrap();
+    assert_eq!(manifest_config.contracts, Some(vec![path.join(".")]));
+    assert_eq!(manifest_config.dockerless, Some(true));
+
+    // Command line settings override the file
+    let project_config = file_config.clone().override_with(ProjectConfig {
+        contracts: Some(vec![path.join("contracts/c")]),
+        dockerless: Some(true),
+        ..Default::default()
+    });
+    let configs = project_config.configs().unwrap();
+    assert_eq!(configs.len(), 1);
+    assert_eq!(configs[0].source_path, path.join("contracts/c"));
+    assert_eq!(configs[0].destination_path, Some(path.join("artifacts")));
+    assert!(configs[0].build_options.locked);
+    assert_eq!(configs[0].build_options.features, vec!["mainnet".to_string()]);
+    assert_eq!(configs[0].build_options.float_policy, FloatPolicy::Error);
+    assert!(matches!(configs[0].docker_option, DockerOption::Dockerless));
+
+    // A docker setting from the command line cancels `dockerless` in the file
+    let project_config = manifest_config.override_with(ProjectConfig {
+        docker_host: Some("tcp://127.0.0.1:2375".to_string()),
+        ..Default::default()
+    });
+    assert!(matches!(
+        project_config.docker_option(),
+        DockerOption::Docker(DockerConfig { host: Some(host), .. }) if host == "tcp://127.0.0.1:2375"
+    ));
+
+    // ... and keeps the other docker settings in the file
+    let project_config = file_config.clone().override_with(ProjectConfig {
+        docker_timeout: Some(30),
+        ..Default::default()
+    });
+    assert!(matches!(
+        project_config.docker_option(),
+        DockerOption::Docker(DockerConfig { tag: Some(tag), timeout: Some(30), .. }) if tag == "0.4.3"
+    ));
+
+    // A flag set in the file is turned off from the command line
+    let project_config = file_config.clone().override_with(ProjectConfig {
+        locked: Some(false),
+        ..Default::default()
+    });
+    assert!(!project_config.configs().unwrap()[0].build_options.locked);
+
+    // The maximum size growth requires a baseline
+    let project_config = file_config.override_with(ProjectConfig {
+        max_size_growth: Some(5.0),
+        ..Default::default()
+    });
+    assert!(matches!(project_config.configs(), Err(Error::ProjectConfigFailure(_))));
+}
+
+#[tokio::test]
+async fn build_contract_with_lib_target() {
+    let source_path = copy_hello_contract("lib_target");
+    let destination_path = TempFolder::new("lib_target_output");
+    let manifest = std::fs::read_to_string(source_path.join("Cargo.toml")).unwrap();
+    let config = pchain_compile::Config {
+        source_path: source_path.to_path_buf(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { locked: true, no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    };
+
+    // The crate type is not cdylib
+    std::fs::write(source_path.join("Cargo.toml"), manifest.replace("[\"cdylib\"]", "[\"rlib\"]")).unwrap();
+    let rlib_result = config.clone().run().await;
+
+    // The library is renamed
+    std::fs::write(source_path.join("Cargo.toml"), manifest.replace("[lib]", "[lib]\nname = \"renamed_contract\"")).unwrap();
+    let renamed_result = config.run().await;
+
+    let renamed_exists = destination_path.join("renamed_contract.wasm").exists();
+
+    assert!(matches!(rlib_result, Err(Error::InvalidLibraryTarget(_))));
+    assert_eq!(renamed_result.unwrap(), "renamed_contract.wasm");
+    assert!(renamed_exists);
+}
+
+#[tokio::test]
+async fn build_contract_per_contract_layout() {
+    let destination_path = TempFolder::new("layout");
+    let run_result = pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions {
+            locked: true,
+            no_cache: true,
+            output_layout: OutputLayout::PerContract,
+            versioned_name: true,
+            ..Default::default()
+        },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run()
+    .await;
+
+    let output_path = destination_path.join("hello_contract");
+    let files = ["hello_contract-0.4.0.wasm", "Cargo.lock", "build.json", "build.log"]
+        .map(|file| output_path.join(file).exists());
+    let metadata = std::fs::read(output_path.join("build.json"));
+
+    assert_eq!(run_result.unwrap(), "hello_contract/hello_contract-0.4.0.wasm");
+    assert_eq!(files, [true; 4]);
+    let metadata: pchain_compile::metadata::BuildMetadata = serde_json::from_slice(&metadata.unwrap()).unwrap();
+    assert!(metadata.cargo_lock_hash.is_some());
+}
+
+#[tokio::test]
+async fn build_all_contracts() {
+    let contract_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("contracts");
+    let configs: Vec<pchain_compile::Config> = ["not_exist_a", "not_exist_b", "not_exist_c"]
+        .into_iter()
+        .map(|name| pchain_compile::Config {
+            source_path: contract_path.join(name),
+            docker_option: DockerOption::Dockerless,
+            ..Default::default()
+        })
+        .collect();
+
+    let mut reported = vec![];
+    let results = pchain_compile::build_all(configs, 2, CancellationToken::new(), |config, result| {
+        assert!(result.is_err());
+        reported.push(config.source_path.clone());
+    })
+    .await;
+
+    assert_eq!(reported.len(), 3);
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|result| matches!(result, Err(Error::InvalidSourcePath))));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn build_all_contracts_with_jobs_limit() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("jobs_limit");
+    let configs: Vec<pchain_compile::Config> = (0..5)
+        .map(|index| pchain_compile::Config {
+            source_path: source_path.clone(),
+            destination_path: Some(destination_path.join(index.to_string())),
+            build_options: BuildOptions { no_cache: true, ..Default::default() },
+            docker_option: DockerOption::Dockerless,
+        })
+        .collect();
+
+    let backend = std::sync::Arc::new(ConcurrencyBackend {
+        inner: pchain_compile::backend::FakeBackend::new(contract_wasm()),
+        ..Default::default()
+    });
+    let results = pchain_compile::batch::build_all_with_backend(configs, 2, backend.clone(), CancellationToken::new(), |_, _| {}).await;
+
+    assert!(results.iter().all(|result| matches!(result, Ok(wasm_file) if wasm_file == "hello_contract.wasm")));
+    assert_eq!(backend.inner.compilations(), 5);
+    assert_eq!(backend.max_running.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn build_all_contracts_with_own_lockfiles() {
+    // The contracts share the destination folder, but the metadata records the Cargo.lock of each contract.
+    let destination_path = TempFolder::new("own_lockfiles");
+    let sources = [TempFolder::new("lockfile_a"), TempFolder::new("lockfile_b")];
+    for source_path in &sources {
+        pchain_compile::new_contract(source_path, Template::Minimal).unwrap();
+        std::fs::write(source_path.join("Cargo.lock"), format!("# {}", source_path.display())).unwrap();
+    }
+    let configs = sources
+        .iter()
+        .map(|source_path| pchain_compile::Config {
+            source_path: source_path.to_path_buf(),
+            destination_path: Some(destination_path.to_path_buf()),
+            build_options: BuildOptions { embed_metadata: true, no_cache: true, ..Default::default() },
+            docker_option: DockerOption::Dockerless,
+        })
+        .collect();
+
+    let backend = std::sync::Arc::new(ConcurrencyBackend {
+        inner: pchain_compile::backend::FakeBackend::new(contract_wasm()),
+        ..Default::default()
+    });
+    let results = pchain_compile::batch::build_all_with_backend(configs, 2, backend, CancellationToken::new(), |_, _| {}).await;
+
+    for (source_path, result) in sources.iter().zip(results) {
+        let wasm = std::fs::read(destination_path.join(result.unwrap())).unwrap();
+        let metadata = pchain_compile::metadata::read_metadata(&wasm).unwrap().unwrap();
+        let cargo_lock = std::fs::read(source_path.join("Cargo.lock")).unwrap();
+        let cargo_lock_hash = format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(&cargo_lock));
+        assert_eq!(metadata.cargo_lock_hash, Some(cargo_lock_hash));
+    }
+}
+
+/// Backend that compiles by a [pchain_compile::backend::FakeBackend] after a delay, and records the maximum number
+/// of compilations running at the same time.
+#[derive(Default)]
+struct ConcurrencyBackend {
+    inner: pchain_compile::backend::FakeBackend,
+    running: std::sync::atomic::AtomicUsize,
+    max_running: std::sync::atomic::AtomicUsize,
+}
+
+impl pchain_compile::BuildBackend for ConcurrencyBackend {
+    async fn toolchain(&self, _source_path: &Path) -> Option<String> {
+        None
+    }
+
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        use std::sync::atomic::Ordering;
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let result = pchain_compile::BuildBackend::compile(&self.inner, request, cancellation_token).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+#[tokio::test]
+async fn build_contract_cancelled() {
+    let cancellation_token = CancellationToken::new();
+    cancellation_token.cancel();
+
+    let config = pchain_compile::Config {
+        source_path: hello_contract_path(),
+        docker_option: DockerOption::Dockerless,
+        ..Default::default()
+    };
+    let results = pchain_compile::build_all(vec![config.clone()], 1, cancellation_token.clone(), |_, _| {}).await;
+    assert!(matches!(results[0], Err(Error::BuildCancelled)));
+
+    let run_result = config.run_cancellable(cancellation_token).await;
+    assert!(matches!(run_result, Err(Error::BuildCancelled)));
+}
+
+#[tokio::test]
+async fn build_contract_cancelled_while_compiling() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("cancelled");
+    let backend = BlockingBackend::default();
+    let cancellation_token = CancellationToken::new();
+    let build = pchain_compile::Config {
+        source_path,
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run_with_backend(&backend, cancellation_token.clone());
+    let cancel = async {
+        backend.started.notified().await;
+        let working_path = backend.working_path.lock().unwrap().clone().unwrap();
+        let compiling = working_path.join("intermediate.wasm").exists();
+        cancellation_token.cancel();
+        (working_path, compiling)
+    };
+    let (run_result, (working_path, compiling)) = tokio::join!(build, cancel);
+
+    assert!(compiling);
+    assert!(matches!(run_result, Err(Error::BuildCancelled)));
+    assert!(!working_path.exists());
+    assert!(!destination_path.join("hello_contract.wasm").exists());
+}
+
+/// Backend that saves an intermediate file in the working folder, and then blocks until the build is cancelled.
+#[derive(Default)]
+struct BlockingBackend {
+    started: tokio::sync::Notify,
+    working_path: std::sync::Mutex<Option<std::path::PathBuf>>,
+}
+
+impl pchain_compile::BuildBackend for BlockingBackend {
+    async fn toolchain(&self, _source_path: &Path) -> Option<String> {
+        None
+    }
+
+    async fn compile(&self, request: &BuildRequest, cancellation_token: &CancellationToken) -> Result<BuildOutput, Error> {
+        std::fs::write(request.working_path.join("intermediate.wasm"), contract_wasm()).unwrap();
+        *self.working_path.lock().unwrap() = Some(request.working_path.clone());
+        self.started.notify_one();
+        cancellation_token.cancelled().await;
+        Err(Error::BuildCancelled)
+    }
+}
+
+#[tokio::test]
+async fn build_contract_with_fake_backend() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("backend");
+    let backend = pchain_compile::backend::FakeBackend::new(contract_wasm());
+    let config = pchain_compile::Config {
+        source_path,
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { size_report: true, no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Dockerless,
+    };
+    let run_result = config.clone().run_with_backend(&backend, CancellationToken::new()).await;
+
+    let files = ["hello_contract.wasm", "Cargo.lock", "hello_contract.size.json", "hello_contract.abi.json"]
+        .map(|file| destination_path.join(file).exists());
+
+    assert_eq!(run_result.unwrap(), "hello_contract.wasm");
+    assert_eq!(files, [true; 4]);
+    assert_eq!(backend.compilations(), 1);
+
+    let backend = pchain_compile::backend::FakeBackend::failing("error[E0425]: cannot find value `x` in this scope");
+    let run_result = config.run_with_backend(&backend, CancellationToken::new()).await;
+    assert!(matches!(run_result, Err(Error::BuildFailureWithLogs(log)) if log.contains("E0425")));
+}
+
+#[tokio::test]
+async fn build_contract_with_float_warning() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("float_warning");
+    let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let on_warning = warnings.clone();
+    let backend = pchain_compile::backend::FakeBackend::new(contract_wasm_with_entrypoint(|body| {
+        body.f32_const(1.0)
+            .f32_const(2.0)
+            .binop(walrus::ir::BinaryOp::F32Add)
+            .drop();
+    }));
+    let run_result = pchain_compile::Config {
+        source_path,
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions {
+            no_cache: true,
+            on_warning: Some(std::sync::Arc::new(move |warning| {
+                on_warning.lock().unwrap().push(warning.clone())
+            })),
+            ..Default::default()
+        },
+        docker_option: DockerOption::Dockerless,
+    }
+    .run_with_backend(&backend, CancellationToken::new())
+    .await;
+
+    assert_eq!(run_result.unwrap(), "hello_contract.wasm");
+    let warnings = warnings.lock().unwrap();
+    assert!(matches!(
+        warnings.as_slice(),
+        [BuildWarning::FloatInstructions { wasm_file, usages }] if wasm_file == "hello_contract.wasm" && usages.len() == 1
+    ));
+}
+
+#[tokio::test]
+async fn build_contract_without_abi() {
+    // The ABI cannot be generated, because the file of the module is generated in building.
+    let source_path = TempFolder::new("without_abi");
+    let destination_path = source_path.join("destination");
+    pchain_compile::new_contract(&source_path, Template::Minimal).unwrap();
+    let lib_rs = std::fs::read_to_string(source_path.join("src").join("lib.rs")).unwrap();
+    std::fs::write(source_path.join("src").join("lib.rs"), format!("mod generated;\n{lib_rs}")).unwrap();
+
+    let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let on_warning = warnings.clone();
+    let build = |embed_abi: bool| pchain_compile::Config {
+        source_path: source_path.to_path_buf(),
+        destination_path: Some(destination_path.clone()),
+        build_options: BuildOptions {
+            embed_abi,
+            no_cache: true,
+            on_warning: Some(std::sync::Arc::new({
+                let on_warning = on_warning.clone();
+                move |warning| on_warning.lock().unwrap().push(warning.clone())
+            })),
+            ..Default::default()
+        },
+        docker_option: DockerOption::Dockerless,
+    };
+    let backend = pchain_compile::backend::FakeBackend::new(contract_wasm());
+    let run_result = build(false).run_with_backend(&backend, CancellationToken::new()).await;
+    let abi_exists = destination_path.join("pchain_compile_without_abi.abi.json").exists();
+    let embedded_result = build(true).run_with_backend(&backend, CancellationToken::new()).await;
+
+    assert_eq!(run_result.unwrap(), "pchain_compile_without_abi.wasm");
+    assert!(!abi_exists);
+    assert!(matches!(
+        warnings.lock().unwrap().as_slice(),
+        [BuildWarning::AbiNotGenerated { wasm_file, .. }] if wasm_file == "pchain_compile_without_abi.wasm"
+    ));
+    assert!(matches!(embedded_result, Err(Error::AbiGenerationFailure(_))));
+}
+
+/// Returns the path of the test contract "hello_contract".
+fn hello_contract_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("contracts")
+        .join("hello_contract")
+}
+
+/// Builds "hello_contract" without docker into a [TempFolder] named after `name`, and returns the folder with the name
+/// of the binary.
+async fn build_hello_contract(name: &str, build_options: BuildOptions) -> (TempFolder, String) {
+    let destination_path = TempFolder::new(name);
+    let run_result = pchain_compile::Config {
+        source_path: hello_contract_path(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options,
+        docker_option: DockerOption::Dockerless,
+    }
+    .run()
+    .await;
+    (destination_path, expect_built(run_result))
+}
+
+/// Returns the name of the binary built without docker, or fails the test with its requirement.
+fn expect_built(run_result: Result<String, Error>) -> String {
+    match run_result {
+        Ok(wasm_name) => wasm_name,
+        Err(e) => {
+            println!("{:?}", e);
+            panic!("Note: This test require installation of target 'wasm32-unknown-unknown'. It can be installed by 'rustup add wasm32-unknown-unknown'");
+        }
+    }
+}
+
+/// Copies the source files of "hello_contract" to a [TempFolder] named after `name`, where they can be modified.
+fn copy_hello_contract(name: &str) -> TempFolder {
+    let contract_path = hello_contract_path();
+    let source_path = TempFolder::new(name);
+    std::fs::create_dir_all(source_path.join("src")).unwrap();
+    for file in [Path::new("Cargo.toml"), Path::new("Cargo.lock"), &Path::new("src").join("lib.rs")] {
+        std::fs::copy(contract_path.join(file), source_path.join(file)).unwrap();
+    }
+    source_path
+}
+
+/// A folder under the temporary directory which is removed when dropped, even if the test fails.
+struct TempFolder(PathBuf);
+
+impl TempFolder {
+    /// Names the folder "pchain_compile_<name>" and removes any content left by a previous run.
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("pchain_compile_{name}"));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+}
+
+impl std::ops::Deref for TempFolder {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempFolder {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFolder {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Returns a minimal WebAssembly binary that passes the validation of a contract.
+fn contract_wasm() -> Vec<u8> {
+    contract_wasm_with_entrypoint(|_| {})
+}
+
+/// Returns the binary of [contract_wasm] with the instructions added by `body` in the function "entrypoint".
+fn contract_wasm_with_entrypoint(body: impl FnOnce(&mut walrus::InstrSeqBuilder)) -> Vec<u8> {
+    let mut module = walrus::Module::with_config(walrus::ModuleConfig::new());
+    let memory = module.memories.add_local(false, 1, None);
+    module.exports.add("memory", memory);
+
+    let mut entrypoint = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+    body(&mut entrypoint.func_body());
+    let entrypoint = entrypoint.finish(vec![], &mut module.funcs);
+    module.exports.add("entrypoint", entrypoint);
+
+    let size = module.locals.add(walrus::ValType::I32);
+    let mut alloc = walrus::FunctionBuilder::new(&mut module.types, &[walrus::ValType::I32], &[walrus::ValType::I32]);
+    alloc.func_body().local_get(size);
+    let alloc = alloc.finish(vec![size], &mut module.funcs);
+    module.exports.add("alloc", alloc);
+
+    module.emit_wasm()
+}
+
+#[tokio::test]
+async fn build_contract_with_docker_connection() {
+    let source_path = hello_contract_path();
+    let config = |docker_config: DockerConfig| pchain_compile::Config {
+        source_path: source_path.clone(),
+        build_options: BuildOptions { no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Docker(docker_config),
+        ..Default::default()
+    };
+
+    let invalid_api_version = config(DockerConfig {
+        api_version: Some("latest".to_string()),
+        ..Default::default()
+    })
+    .run()
+    .await;
+    assert!(matches!(invalid_api_version, Err(Error::InvalidDockerConnection(_))));
+
+    let https_without_certificates = config(DockerConfig {
+        host: Some("https://127.0.0.1:2376".to_string()),
+        ..Default::default()
+    })
+    .run()
+    .await;
+    assert!(matches!(https_without_certificates, Err(Error::InvalidDockerConnection(_))));
+
+    let socket_with_certificates = config(DockerConfig {
+        host: Some("unix:///var/run/docker.sock".to_string()),
+        tls_cert_path: Some(std::env::temp_dir()),
+        ..Default::default()
+    })
+    .run()
+    .await;
+    assert!(matches!(socket_with_certificates, Err(Error::InvalidDockerConnection(_))));
+
+    let unreachable_host = config(DockerConfig {
+        host: Some("tcp://127.0.0.1:1".to_string()),
+        timeout: Some(5),
+        ..Default::default()
+    })
+    .run()
+    .await;
+    assert!(matches!(unreachable_host, Err(Error::DockerDaemonFailure)));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn build_contract_with_docker_cli() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let source_path = hello_contract_path();
+    let cli_path = TempFolder::new("docker_cli");
+    let destination_path = cli_path.join("destination");
+
+    // A fake CLI that records the commands, and returns the compiled contract from the folder "result".
+    std::fs::create_dir_all(cli_path.join("result")).unwrap();
+    std::fs::write(cli_path.join("result").join("hello_contract.wasm"), contract_wasm()).unwrap();
+    std::fs::write(cli_path.join("result").join("Cargo.lock"), "# Cargo.lock").unwrap();
+    let script = format!(
+        "#!/bin/sh\necho \"$1\" >> {log}\ncase \"$1 $2\" in\n  \"cp -\") cat > /dev/null ;;\n  cp*) tar -C {dir} -cf - result ;;\nesac\n",
+        log = cli_path.join("commands.log").display(),
+        dir = cli_path.display(),
+    );
+    let cli = cli_path.join("docker");
+    std::fs::write(&cli, script).unwrap();
+    std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let run_result = pchain_compile::Config {
+        source_path,
+        destination_path: Some(destination_path.clone()),
+        build_options: BuildOptions { no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Docker(DockerConfig {
+            cli: Some(cli.to_str().unwrap().to_string()),
+            ..Default::default()
+        }),
+    }
+    .run()
+    .await;
+
+    let wasm_exists = destination_path.join("hello_contract.wasm").exists();
+    let commands = std::fs::read_to_string(cli_path.join("commands.log")).unwrap_or_default();
+
+    assert_eq!(run_result.unwrap(), "hello_contract.wasm");
+    assert!(wasm_exists);
+    let commands: Vec<&str> = commands.lines().collect();
+    assert_eq!(commands[..4], ["pull", "create", "start", "cp"]);
+    assert!(commands.contains(&"exec"));
+    assert_eq!(commands.last(), Some(&"rm"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn build_contract_dropped_with_docker_cli() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cli_path = TempFolder::new("docker_cli_dropped");
+    std::fs::create_dir_all(&cli_path).unwrap();
+
+    // A fake CLI that records the commands, and never finishes compiling.
+    let script = format!(
+        "#!/bin/sh\necho \"$1\" >> {log}\ncase \"$1 $2\" in\n  \"cp -\") cat > /dev/null ;;\n  exec*) sleep 30 ;;\nesac\n",
+        log = cli_path.join("commands.log").display(),
+    );
+    let cli = cli_path.join("docker");
+    std::fs::write(&cli, script).unwrap();
+    std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let commands = || std::fs::read_to_string(cli_path.join("commands.log")).unwrap_or_default();
+    let wait_for = |command: &'static str| async move {
+        for _ in 0..100 {
+            if commands().lines().any(|line| line == command) {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        false
+    };
+
+    // Drop the build while it is compiling, as aborting its task does.
+    let build = tokio::spawn(
+        pchain_compile::Config {
+            source_path: hello_contract_path(),
+            destination_path: Some(cli_path.join("destination")),
+            build_options: BuildOptions { no_cache: true, ..Default::default() },
+            docker_option: DockerOption::Docker(DockerConfig {
+                cli: Some(cli.to_str().unwrap().to_string()),
+                ..Default::default()
+            }),
+        }
+        .run(),
+    );
+    let compiling = wait_for("exec").await;
+    build.abort();
+    let aborted = build.await.is_err_and(|e| e.is_cancelled());
+    let removed = wait_for("rm").await;
+
+    assert!(compiling);
+    assert!(aborted);
+    assert!(removed);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn build_contract_with_local_docker_image() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let source_path = hello_contract_path();
+    let cli_path = TempFolder::new("docker_cli_local_image");
+    let destination_path = cli_path.join("destination");
+
+    // A fake CLI that has the image built by "pchain_compile image build" with tag 0.4.2.
+    std::fs::create_dir_all(cli_path.join("result")).unwrap();
+    std::fs::write(cli_path.join("result").join("hello_contract.wasm"), contract_wasm()).unwrap();
+    let script = format!(
+        "#!/bin/sh\necho \"$1\" >> {log}\ncase \"$1 $2\" in\n  \"image inspect\") test \"$3\" = localhost/pchain_compile_local:0.4.2 || exit 1 ;;\n  \"cp -\") cat > /dev/null ;;\n  cp*) tar -C {dir} -cf - result ;;\nesac\n",
+        log = cli_path.join("commands.log").display(),
+        dir = cli_path.display(),
+    );
+    let cli = cli_path.join("docker");
+    std::fs::write(&cli, script).unwrap();
+    std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let build = |tag: &str| pchain_compile::Config {
+        source_path: source_path.clone(),
+        destination_path: Some(destination_path.clone()),
+        build_options: BuildOptions { no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Docker(DockerConfig {
+            tag: Some(tag.to_string()),
+            cli: Some(cli.to_str().unwrap().to_string()),
+            local_image: true,
+            ..Default::default()
+        }),
+    }
+    .run();
+    let run_result = build("0.4.2").await;
+    let missing_image = build("mainnet01").await;
+
+    let commands = std::fs::read_to_string(cli_path.join("commands.log")).unwrap_or_default();
+
+    assert_eq!(run_result.unwrap(), "hello_contract.wasm");
+    assert!(matches!(missing_image, Err(Error::LocalImageNotFound(image)) if image == "localhost/pchain_compile_local:mainnet01"));
+    assert!(!commands.lines().any(|command| command == "pull"));
+}
+
+#[test]
+fn docker_image_versions() {
+    let versions = pchain_compile::image::image_versions("mainnet01").unwrap();
+    assert_eq!(versions.rustc, "1.66.1");
+    assert_eq!(versions.wasm_snip, "0.4.0");
+    assert_eq!(versions.binaryen, "109");
+    assert!(pchain_compile::image::image_versions(env!("CARGO_PKG_VERSION")).is_some());
+    assert!(pchain_compile::image::image_versions("0.4.2").is_some());
+    assert!(pchain_compile::image::image_versions("0.1.0").is_none());
+
+    for arg in ["RUST_VERSION", "WASM_SNIP_VERSION", "BINARYEN_VERSION"] {
+        assert!(pchain_compile::image::DOCKERFILE.contains(&format!("ARG {arg}")));
+    }
+}
+
+#[tokio::test]
+async fn build_docker_image_unknown_tag() {
+    let result = pchain_compile::image::build_image(
+        DockerConfig {
+            tag: Some("0.1.0".to_string()),
+            ..Default::default()
+        },
+        |_| {},
+    )
+    .await;
+    assert!(matches!(result, Err(pchain_compile::error::Error::UnkownDockerImageTag(tag)) if tag == "0.1.0"));
+}
+
+#[tokio::test]
+async fn build_contract_in_sandbox() {
+    let source_path = hello_contract_path();
+    let destination_path = TempFolder::new("sandbox");
+    let run_result = pchain_compile::Config {
+        source_path: source_path.clone(),
+        destination_path: Some(destination_path.to_path_buf()),
+        build_options: BuildOptions { no_cache: true, ..Default::default() },
+        docker_option: DockerOption::Sandbox(SandboxConfig::default()),
+    }
+    .run()
+    .await;
+
+    let wasm_name = match run_result {
+        Ok(wasm_name) => wasm_name,
+        Err(e) => {
+            println!("{:?}", e);
+            panic!("Note: This test require installation of bubblewrap and target 'wasm32-unknown-unknown'.");
+        }
+    };
+    let wasm_exists = destination_path.join(&wasm_name).exists();
+    assert_eq!(wasm_name, "hello_contract.wasm");
+    assert!(wasm_exists);
+
+    let missing_bwrap = pchain_compile::Config {
+        source_path,
+        docker_option: DockerOption::Sandbox(SandboxConfig {
+            bwrap: Some("not_exist_bwrap".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+    .run()
+    .await;
+    assert!(matches!(missing_bwrap, Err(Error::SandboxFailure(_))));
+}